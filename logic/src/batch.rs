@@ -0,0 +1,252 @@
+//! Checking a whole library at once.
+//!
+//! A theorem is checked once every theorem it uses, as found by
+//! `Library::theorems_used`, has passed. If one of them failed or was
+//! skipped, the theorem is skipped instead. With the `sync` feature the
+//! checks run on several threads, each taking the next theorem whose
+//! dependencies are resolved; otherwise they run on the calling thread.
+
+use std::fmt;
+use std::sync::{Condvar, Mutex};
+#[cfg(feature = "sync")]
+use std::thread;
+
+use check::Error;
+use doc::Library;
+
+#[derive(Default)]
+pub struct Report {
+	pub passed: Vec<String>,
+	/// Theorems without a proof.
+	pub axioms: Vec<String>,
+	pub failed: Vec<(String, Error)>,
+	/// Theorems not checked, with a dependency that failed or was skipped.
+	pub skipped: Vec<(String, String)>,
+}
+
+impl Report {
+	pub fn ok(&self) -> bool {
+		self.failed.is_empty() && self.skipped.is_empty()
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (name, e) in self.failed.iter() {
+			writeln!(f, "failed\t{}\t{}", name, e)?;
+		}
+		for (name, dep) in self.skipped.iter() {
+			writeln!(f, "skipped\t{}\tdepends on {}", name, dep)?;
+		}
+		writeln!(f, "{} passed, {} axioms, {} failed, {} skipped",
+			self.passed.len(), self.axioms.len(), self.failed.len(), self.skipped.len())
+	}
+}
+
+#[derive(Clone, PartialEq)]
+enum Status {
+	Pending,
+	Running,
+	Passed,
+	Axiom,
+	Failed(Error),
+	/// Skipped because of the dependency at the index.
+	Skipped(usize),
+}
+
+struct State {
+	status: Vec<Status>,
+	ready: Vec<usize>,
+	running: usize,
+}
+
+struct Batch<'a> {
+	library: &'a Library,
+	/// The theorems each theorem uses.
+	deps: Vec<Vec<usize>>,
+	/// The theorems using each theorem.
+	users: Vec<Vec<usize>>,
+	state: Mutex<State>,
+	changed: Condvar,
+}
+
+impl <'a> Batch<'a> {
+	fn new(library: &'a Library) -> Batch<'a> {
+		let n = library.theorems.len();
+		let deps: Vec<Vec<usize>> = (0..n).map(|i| library.theorems_used(i)).collect();
+		let mut users = vec![vec!(); n];
+		for (i, d) in deps.iter().enumerate() {
+			for j in d.iter() {
+				users[*j].push(i);
+			}
+		}
+
+		let mut state = State { status: vec![Status::Pending; n], ready: vec!(), running: 0 };
+		for i in (0..n).rev() {
+			if deps[i].is_empty() {
+				state.ready.push(i);
+			}
+		}
+		Batch { library, deps, users, state: Mutex::new(state), changed: Condvar::new() }
+	}
+
+	/// Checks theorems until none is left to check.
+	fn work(&self) {
+		let mut state = self.state.lock().unwrap();
+		loop {
+			let i = match state.ready.pop() {
+				Some(i) => i,
+				None if state.running == 0 => break,
+				None => {
+					state = self.changed.wait(state).unwrap();
+					continue;
+				}
+			};
+			state.status[i] = Status::Running;
+			state.running += 1;
+			drop(state);
+
+			let status = match &self.library.theorems[i].proof {
+				None => Status::Axiom,
+				Some(_) => match self.library.check(i) {
+					Ok(()) => Status::Passed,
+					Err(e) => Status::Failed(e),
+				},
+			};
+
+			state = self.state.lock().unwrap();
+			state.running -= 1;
+			self.resolve(&mut state, i, status);
+			self.changed.notify_all();
+		}
+	}
+
+	/// Records the status of `i`, and queues or skips the theorems waiting
+	/// only for it.
+	fn resolve(&self, state: &mut State, i: usize, status: Status) {
+		let mut todo = vec!((i, status));
+		while let Some((i, status)) = todo.pop() {
+			state.status[i] = status;
+			for u in self.users[i].iter() {
+				if state.status[*u] != Status::Pending {
+					continue;
+				}
+				let mut waiting = false;
+				let mut blocked = None;
+				for d in self.deps[*u].iter() {
+					match state.status[*d] {
+						Status::Pending | Status::Running => waiting = true,
+						Status::Failed(_) | Status::Skipped(_) => blocked = Some(*d),
+						Status::Passed | Status::Axiom => (),
+					}
+				}
+				if waiting {
+					continue;
+				}
+				match blocked {
+					Some(d) => {
+						// Marked now, so it is not pushed twice.
+						state.status[*u] = Status::Skipped(d);
+						todo.push((*u, Status::Skipped(d)));
+					}
+					None => state.ready.push(*u),
+				}
+			}
+		}
+	}
+
+	fn report(self) -> Report {
+		let library = self.library;
+		let names = |i: usize| library.theorems[i].name.clone();
+		let mut report = Report::default();
+		for (i, status) in self.state.into_inner().unwrap().status.into_iter().enumerate() {
+			match status {
+				Status::Passed => report.passed.push(names(i)),
+				Status::Axiom => report.axioms.push(names(i)),
+				Status::Failed(e) => report.failed.push((names(i), e)),
+				Status::Skipped(d) => report.skipped.push((names(i), names(d))),
+				// Never ready, as it depends on itself through a cycle.
+				Status::Pending | Status::Running => report.failed.push((names(i), Error::Cycle)),
+			}
+		}
+		report
+	}
+}
+
+/// Checks every theorem of the library, on up to `threads` threads with the
+/// `sync` feature.
+pub fn check_library(library: &Library, threads: usize) -> Report {
+	let batch = Batch::new(library);
+	#[cfg(feature = "sync")]
+	thread::scope(|s| {
+		for _ in 0..threads.max(1) {
+			s.spawn(|| batch.work());
+		}
+	});
+	#[cfg(not(feature = "sync"))]
+	{
+		let _ = threads;
+		batch.work();
+	}
+	batch.report()
+}
+
+/// The number of threads to check with by default.
+pub fn default_threads() -> usize {
+	#[cfg(feature = "sync")]
+	return thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	#[cfg(not(feature = "sync"))]
+	1
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use deduction::Deduction;
+	use doc::Theorem;
+	use formula::{Formula, ConstName, Form};
+	use Ptr;
+
+	fn atom(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn theorem(name: &str, statement: Formula, proof: Option<Deduction>) -> Theorem {
+		Theorem {
+			name: name.to_string(),
+			statement,
+			description: String::new(),
+			theory: String::new(),
+			proof,
+			source: String::new(),
+		}
+	}
+
+	/// Runs on several threads with the `sync` feature.
+	#[test]
+	fn check_library_on_several_threads() {
+		let (p, q) = (atom("p"), atom("q"));
+		let mut library = Library::default();
+		library.theorems.push(theorem("q", (*q).clone(), None));
+		library.theorems.push(theorem("p", (*p).clone(), Some(Deduction::AndExtract(p.clone(), q.clone()))));
+		library.theorems.push(theorem("pq", Formula::And(p.clone(), q.clone()), Some(Deduction::AndIntro(p.clone(), q.clone()))));
+		library.theorems.push(theorem("bad", Formula::False, Some(Deduction::TrueIntro)));
+		let after = Deduction::OrIntro(Formula::False.ptr(), Formula::True.ptr());
+		library.theorems.push(theorem("after", Formula::Or(Formula::False.ptr(), Formula::True.ptr()), Some(after)));
+		let trivial: Vec<String> = (0..16).map(|i| format!("t{}", i)).collect();
+		for name in trivial.iter() {
+			library.theorems.push(theorem(name, Formula::True, Some(Deduction::TrueIntro)));
+		}
+
+		let report = check_library(&library, 4);
+		assert_eq!(report.passed, trivial);
+		assert_eq!(report.axioms, vec!("q".to_string()));
+		assert_eq!(report.failed, vec!(
+			("p".to_string(), Error::Cycle),
+			("pq".to_string(), Error::Cycle),
+			("bad".to_string(), Error::NotDeduced),
+		));
+		assert_eq!(report.skipped, vec!(("after".to_string(), "bad".to_string())));
+		assert!(!report.ok());
+	}
+}
@@ -0,0 +1,294 @@
+//! Incremental checking of the theorems of a library.
+//!
+//! Each theorem with a proof is checked against the statements of the
+//! theorems it uses and the constants they mention, as found by
+//! `Library::theorems_used` and `Library::constants_used`. A check is
+//! recorded in a `Cache` under the content hashes of the statement, the
+//! proof and those dependencies, so it is only repeated when one of them
+//! changes.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use deps;
+use doc::Library;
+use formula::{Formula, ConstName};
+use hash::{Content, ContentHash, ContentHasher};
+use knowledge_base::KnowledgeBase;
+use Ptr;
+
+/// The context a theorem of a library is checked in.
+pub struct Context {
+	pub theorems: Vec<(String, Formula)>,
+	pub constants: Vec<ConstName>,
+}
+
+impl KnowledgeBase for Context {
+	fn contains(&self, form: &Formula) -> bool {
+		self.theorems.iter().any(|(_, f)| f == form)
+	}
+
+	fn has_const(&self, c: &ConstName) -> bool {
+		self.constants.contains(c)
+	}
+
+	fn constants(&self) -> Vec<ConstName> {
+		self.constants.clone()
+	}
+
+	fn theorems(&self) -> Vec<Formula> {
+		self.theorems.iter().map(|(_, f)| f.clone()).collect()
+	}
+
+	fn theorem(&self, name: &ConstName) -> Option<Formula> {
+		match name {
+			ConstName::String(s) => self.theorems.iter()
+				.find(|(n, _)| n == &**s)
+				.map(|(_, f)| f.clone()),
+			ConstName::Int(_) => None,
+		}
+	}
+}
+
+/// A successful check of a proof.
+#[derive(Clone, PartialEq)]
+pub struct Entry {
+	pub theorem: String,
+	pub statement: ContentHash,
+	pub proof: ContentHash,
+	/// The hash of the names and statements of the theorems used and the
+	/// constants mentioned.
+	pub dependencies: ContentHash,
+}
+
+#[derive(Clone, Default)]
+pub struct Cache {
+	pub entries: Vec<Entry>,
+}
+
+impl Cache {
+	pub fn contains(&self, e: &Entry) -> bool {
+		self.entries.contains(e)
+	}
+
+	/// Records a check, replacing any earlier one of the same theorem.
+	pub fn insert(&mut self, e: Entry) {
+		self.entries.retain(|x| x.theorem != e.theorem);
+		self.entries.push(e);
+	}
+}
+
+/// Why the proof of a theorem was rejected.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Error {
+	/// A step of the proof is invalid.
+	Step(String),
+	/// Every step is valid, but the statement is not among the conclusions.
+	NotDeduced,
+	/// The checker panicked on the proof.
+	Panic(String),
+	/// The theorem uses itself through the theorems it uses, or uses a
+	/// theorem that does.
+	Cycle,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Step(e) => write!(f, "{}", e),
+			Error::NotDeduced => write!(f, "Check: The proof does not deduce the statement"),
+			Error::Panic(e) => write!(f, "Check: The checker panicked: {}", e),
+			Error::Cycle => write!(f, "Check: The theorem depends on a cycle of theorems"),
+		}
+	}
+}
+
+pub enum Outcome {
+	/// The proof was checked now.
+	Checked,
+	/// The same proof was checked against the same dependencies before.
+	Cached,
+	Failed(Error),
+	/// The theorem has no proof.
+	Axiom,
+}
+
+impl Library {
+	/// The context the theorem at `i` is checked in. Constants its proof
+	/// defines are left out, so the definitions can be checked.
+	pub fn context(&self, i: usize) -> Context {
+		let mut defined = vec!();
+		if let Some(d) = &self.theorems[i].proof {
+			deps::definitions(d, &mut defined);
+		}
+		Context {
+			theorems: self.theorems_used(i).into_iter()
+				.map(|j| (self.theorems[j].name.clone(), self.theorems[j].statement.clone()))
+				.collect(),
+			constants: self.constants_used(i).into_iter()
+				.map(|j| ConstName::String(Ptr::new(self.constants[j].name.clone())))
+				.filter(|c| !defined.contains(c))
+				.collect(),
+		}
+	}
+
+	/// The cache entry a successful check of the theorem at `i` records, or
+	/// `None` for an axiom.
+	pub fn cache_entry(&self, i: usize) -> Option<Entry> {
+		let t = &self.theorems[i];
+		let proof = t.proof.as_ref()?;
+
+		let context = self.context(i);
+		let mut h = ContentHasher::default();
+		h.u64(context.theorems.len() as u64);
+		for (name, f) in context.theorems.iter() {
+			h.str(name);
+			f.content(&mut h);
+		}
+		context.constants.content(&mut h);
+
+		Some(Entry {
+			theorem: t.name.clone(),
+			statement: t.statement.content_hash(),
+			proof: proof.content_hash(),
+			dependencies: h.finish(),
+		})
+	}
+
+	/// Checks the proof of the theorem at `i` in its context. A panic of
+	/// the checker is caught and reported as `Error::Panic`.
+	pub fn check(&self, i: usize) -> Result<(), Error> {
+		let t = &self.theorems[i];
+		let proof = match &t.proof {
+			Some(d) => d,
+			None => return Ok(()),
+		};
+
+		let result = panic::catch_unwind(AssertUnwindSafe(|| proof.apply_work(self.context(i))))
+			.map_err(|p| Error::Panic(match p.downcast_ref::<&str>() {
+				Some(s) => s.to_string(),
+				None => p.downcast_ref::<String>().cloned().unwrap_or_default(),
+			}))?;
+		match result.error() {
+			Some(e) => Err(Error::Step(e.to_string())),
+			None => if result.contains(&t.statement) {
+				Ok(())
+			} else {
+				Err(Error::NotDeduced)
+			}
+		}
+	}
+
+	/// Whether each theorem uses itself through the theorems it uses, or
+	/// uses a theorem that does.
+	pub fn cyclic(&self) -> Vec<bool> {
+		let n = self.theorems.len();
+		let deps: Vec<Vec<usize>> = (0..n).map(|i| self.theorems_used(i)).collect();
+		let mut waiting: Vec<usize> = deps.iter().map(|d| d.len()).collect();
+		let mut users = vec![vec!(); n];
+		for (i, d) in deps.iter().enumerate() {
+			for j in d.iter() {
+				users[*j].push(i);
+			}
+		}
+
+		// Theorems are resolved once all the theorems they use are, so
+		// those left are on a cycle or wait for one.
+		let mut todo: Vec<usize> = (0..n).filter(|i| waiting[*i] == 0).collect();
+		while let Some(i) = todo.pop() {
+			for u in users[i].iter() {
+				waiting[*u] -= 1;
+				if waiting[*u] == 0 {
+					todo.push(*u);
+				}
+			}
+		}
+		waiting.iter().map(|w| *w > 0).collect()
+	}
+
+	/// Checks every theorem whose proof or dependencies changed since it was
+	/// recorded in `cache`, and records the successful checks. Theorems
+	/// depending on a cycle fail with `Error::Cycle`.
+	pub fn recheck(&self, cache: &mut Cache) -> Vec<(String, Outcome)> {
+		let cyclic = self.cyclic();
+		let mut out = vec!();
+		for (i, t) in self.theorems.iter().enumerate() {
+			let entry = match self.cache_entry(i) {
+				Some(e) => e,
+				None => {
+					out.push((t.name.clone(), Outcome::Axiom));
+					continue;
+				}
+			};
+			let outcome = if cyclic[i] {
+				Outcome::Failed(Error::Cycle)
+			} else if cache.contains(&entry) {
+				Outcome::Cached
+			} else {
+				match self.check(i) {
+					Ok(()) => {
+						cache.insert(entry);
+						Outcome::Checked
+					}
+					Err(e) => Outcome::Failed(e),
+				}
+			};
+			out.push((t.name.clone(), outcome));
+		}
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use deduction::Deduction;
+	use doc::Theorem;
+
+	fn atom(s: &str) -> Ptr<Formula> {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn theorem(name: &str, statement: Formula, proof: Option<Deduction>) -> Theorem {
+		Theorem {
+			name: name.to_string(),
+			statement,
+			description: String::new(),
+			theory: String::new(),
+			proof,
+			source: String::new(),
+		}
+	}
+
+	#[test]
+	fn recheck_rejects_circular_proofs() {
+		let (p, q) = (atom("p"), atom("q"));
+		let pq = Formula::And(p.clone(), q.clone());
+		let mut library = Library::default();
+		library.theorems.push(theorem("q", (*q).clone(), None));
+		library.theorems.push(theorem("p", (*p).clone(), Some(Deduction::AndExtract(p.clone(), q.clone()))));
+		library.theorems.push(theorem("pq", pq, Some(Deduction::AndIntro(p.clone(), q.clone()))));
+		library.theorems.push(theorem("r", Formula::True, Some(Deduction::TrueIntro)));
+
+		let outcomes = library.recheck(&mut Cache::default());
+		let failed: Vec<&str> = outcomes.iter()
+			.filter(|(_, o)| matches!(o, Outcome::Failed(Error::Cycle)))
+			.map(|(n, _)| n.as_str())
+			.collect();
+		assert_eq!(failed, vec!("p", "pq"));
+		assert!(matches!(outcomes[3].1, Outcome::Checked));
+	}
+
+	#[test]
+	fn check_finds_a_failed_step_before_the_last() {
+		let (p, q) = (atom("p"), atom("q"));
+		let proof = Deduction::Sequence(vec!(Deduction::AndExtract(p, q), Deduction::TrueIntro));
+		let mut library = Library::default();
+		library.theorems.push(theorem("t", Formula::True, Some(proof)));
+
+		assert!(matches!(library.check(0), Err(Error::Step(_))));
+		let mut cache = Cache::default();
+		assert!(matches!(library.recheck(&mut cache)[0].1, Outcome::Failed(Error::Step(_))));
+		assert!(cache.entries.is_empty());
+	}
+}
@@ -3,10 +3,44 @@ use formula::Form;
 use formula::ConstName;
 use knowledge_base::ResultBase;
 use knowledge_base::KnowledgeBase;
+use unify;
+use unify::{Vars, Substitution};
 use Ptr;
 
+use serde::Serialize;
+use serde::Deserialize;
+
 pub type Work = Ptr<Deduction>;
 
+/// The relations which may link the steps of a `Calc` chain.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CalcRel {
+	Eq,
+	IFF,
+	Implies,
+}
+
+impl CalcRel {
+	pub fn relate(self, l: Form, r: Form) -> Formula {
+		match self {
+			CalcRel::Eq => Formula::Eq(l, r),
+			CalcRel::IFF => Formula::IFF(l, r),
+			CalcRel::Implies => Formula::Implies(l, r),
+		}
+	}
+
+	/// The relation of a chain made of a link of `self` followed by one of
+	/// `other`.
+	pub fn compose(self, other: CalcRel) -> CalcRel {
+		match (self, other) {
+			(CalcRel::Eq, r) | (r, CalcRel::Eq) => r,
+			(CalcRel::IFF, CalcRel::IFF) => CalcRel::IFF,
+			_ => CalcRel::Implies,
+		}
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Deduction {
 	EmptyStep,
 	IFFIntro((Form, Work), (Form, Work)),
@@ -27,8 +61,14 @@ pub enum Deduction {
 	ExistsExtract((ConstName, Form), ConstName),
 	ForAllExtract((ConstName, Form), Form),
 	ForAllIntro((ConstName, Form), Work),
+	ForAllMatch(Form),
+	ForAllBy(ConstName, Form),
 	Sequence(Vec<Deduction>),
 	Let(ConstName, Vec<ConstName>, Form),
+	TrueIntro,
+	Contradiction(Form, Work),
+	Calc(Form, Vec<(CalcRel, Form, Work)>),
+	Name(ConstName, Form),
 }
 
 impl Deduction {
@@ -42,11 +82,18 @@ impl Deduction {
 		self.apply_work_inner(ResultBase::new(k))
 	}
 
+	/// Checks a subproof within the current context. Unlike `deduced`, this
+	/// does not wrap the context in a new root, so nested subproofs do not
+	/// instantiate ever deeper `ResultBase` types.
+	fn deduced_inner<K: KnowledgeBase>(&self, k: ResultBase<K>, thm: &Formula) -> bool {
+		self.apply_work_inner(k).contains(thm)
+	}
+
 	fn apply_work_inner<K: KnowledgeBase>(&self, k: ResultBase<K>) -> ResultBase<K> {		
 		match self {
 			Deduction::EmptyStep => k,
 			Deduction::AndIntro(f1, f2) => self.and_intro(k, f1.clone(), f2.clone()),
-			Deduction::AndExtract(f1, f2) => self.and_intro(k, f1.clone(), f2.clone()),
+			Deduction::AndExtract(f1, f2) => self.and_extract(k, f1.clone(), f2.clone()),
 			Deduction::OrIntro(f1, f2) => self.or_intro(k, f1.clone(), f2.clone()),
 			Deduction::OrExtract(f1, f2, f3) => self.or_extract(k, f1, f2, f3.clone()),
 			Deduction::ImplyIntro(f1, f2, w) => self.implies_intro(k, f1.clone(), f2.clone(), w.clone()),
@@ -61,11 +108,17 @@ impl Deduction {
 			Deduction::ForAllIntro(f, w) => self.forall_intro(k, f, w.clone()),
 			Deduction::ForAllExtract(f, w) => self.forall_extract(k, f, w.clone()),
 			Deduction::ForAllSeqExtract(f, w) => self.forallseq_extract(k, f, w.clone()),
+			Deduction::ForAllMatch(f) => self.forall_match(k, f.clone()),
+			Deduction::ForAllBy(c, f) => self.forall_by(k, c, f.clone()),
 			Deduction::ExistsIntro(f, w) => self.exists_intro(k, f, w.clone()),
 			Deduction::ExistsExtract(f, w) => self.exists_extract(k, f, w.clone()),
 			Deduction::Let(c, v, f) => self.alias(k, c, v, f.clone()),
 			Deduction::Sequence(v) => self.sequence(k, v),
 			Deduction::SubstReduce(f1, c, f2) => self.sub_reduce(k, f1.clone(), c, f2.clone()),
+			Deduction::TrueIntro => k.result_form(Formula::True),
+			Deduction::Contradiction(f1, w) => self.contradiction(k, f1.clone(), w.clone()),
+			Deduction::Calc(f, v) => self.calc(k, f.clone(), v),
+			Deduction::Name(c, f) => self.name(k, c, f.clone()),
 			//_ => panic!("")
 		}
 	}
@@ -127,9 +180,13 @@ impl Deduction {
 	pub fn exists_extract<K: KnowledgeBase>(&self, k: ResultBase<K>, f: &(ConstName, Form), v: ConstName) 
 	-> ResultBase<K>{
 
-		if !k.has_const(&v) {
+		let ex = Formula::Exists(f.0.clone(), f.1.clone());
+
+		if !k.contains(&ex) {
+			ResultBase::Err(format!("ExistsExtract: Did not deduce formula {}", ex.to_string()))
+		} else if !k.has_const(&v) {
 			let thm = f.1.substitute(&f.0, &Formula::Const(v.clone()));
-			k.result_form(thm)
+			k.result_const(v).result_form(thm)
 		} else {
 			ResultBase::Err(format!("ExistsExtract: Cannot redefine constant {}", v.to_string()))
 		}
@@ -141,7 +198,7 @@ impl Deduction {
 			let assume = k.result_const(f.0.clone());
 			let thm = f.1.substitute(&f.0, &Formula::Const(f.0.clone()));
 			
-			if w.deduced(assume, &thm) {
+			if w.deduced_inner(assume, &thm) {
 				k.result_form(Formula::ForAll(f.0.clone(), f.1.clone()))
 			} else {
 				ResultBase::Err(format!("ForAllIntro: Did not deduce formula {}", thm.to_string()))
@@ -175,6 +232,243 @@ impl Deduction {
 		}
 	}
 
+	/// Deduces `thm` as an instance of a `ForAll` or `ForAllSeq` formula of
+	/// the context, finding the witnesses by matching.
+	pub fn forall_match<K: KnowledgeBase>(&self, k: ResultBase<K>, thm: Form)
+	-> ResultBase<K> {
+		let found = k.theorems().iter()
+			.filter_map(|f| Deduction::instantiate(f, &thm))
+			.map(|d| d.apply_work_inner(k.clone()))
+			.find(|r| r.contains(&thm));
+
+		match found {
+			Some(r) => r,
+			None => ResultBase::Err(format!("ForAllMatch: No deduced formula generalizes {}", thm.to_string())),
+		}
+	}
+
+	/// Deduces `thm` as an instance of the theorem named `c`, finding the
+	/// witnesses by matching.
+	pub fn forall_by<K: KnowledgeBase>(&self, k: ResultBase<K>, c: &ConstName, thm: Form)
+	-> ResultBase<K> {
+		let f = match k.theorem(c) {
+			Some(f) => f,
+			None => return ResultBase::Err(format!("ForAllBy: Unknown theorem {}", c.to_string())),
+		};
+
+		match Deduction::instantiate(&f, &thm) {
+			Some(d) => d.apply_work_inner(k),
+			None => ResultBase::Err(format!("ForAllBy: {} is not an instance of {}", thm.to_string(), f.to_string())),
+		}
+	}
+
+	/// Names a formula of the context, such as an assumption, so that a later
+	/// `ForAllBy` of the same subproof can instantiate it.
+	pub fn name<K: KnowledgeBase>(&self, k: ResultBase<K>, c: &ConstName, f: Form)
+	-> ResultBase<K> {
+		if k.contains(&f) {
+			k.result_named(c.clone(), f)
+		} else {
+			ResultBase::Err(format!("Name: Did not deduce theorem: {}", f.to_string()))
+		}
+	}
+
+	/// Returns the `ForAllExtract` and `ForAllSeqExtract` steps deducing
+	/// `instance` from `quantified`, instantiating as few of its leading
+	/// quantifiers as needed. Quantified variables which do not occur are
+	/// instantiated with `True`.
+	pub fn instantiate(quantified: &Formula, instance: &Formula) -> Option<Deduction> {
+		let mut prefix = vec!();
+		let mut body = quantified;
+		while let Formula::ForAll(_, e) | Formula::ForAllSeq(_, _, e) = body {
+			prefix.push(body);
+			body = e;
+			if let Some(d) = Deduction::instantiate_prefix(&prefix, body, instance) {
+				return Some(d);
+			}
+		}
+		None
+	}
+
+	fn instantiate_prefix(prefix: &[&Formula], body: &Formula, instance: &Formula) -> Option<Deduction> {
+		let mut terms = vec!();
+		let mut seqs = vec!();
+		for q in prefix.iter() {
+			match q {
+				Formula::ForAll(v, _) => terms.push(v.clone()),
+				Formula::ForAllSeq(ar, v, _) => seqs.push((*ar, v.clone())),
+				_ => unreachable!(),
+			}
+		}
+		let sub = unify::matching(body, instance, &Vars::Only(terms, seqs))?;
+
+		let mut steps = vec!();
+		let mut current = (*prefix[0]).clone();
+		for _ in prefix.iter() {
+			current = match &current {
+				Formula::ForAll(v, e) => {
+					let t = sub.term(v).cloned().unwrap_or(Formula::True);
+					steps.push(Deduction::ForAllExtract((v.clone(), e.clone()), t.clone().ptr()));
+					e.substitute(v, &t)
+				}
+				Formula::ForAllSeq(ar, v, e) => {
+					let t = sub.seq(*ar, v).cloned()
+						.unwrap_or_else(|| vec!(Formula::True; *ar as usize));
+					steps.push(Deduction::ForAllSeqExtract((*ar, v.clone(), e.clone()), t.clone()));
+					e.substitute_seq(*ar, v, &t)
+				}
+				_ => unreachable!(),
+			};
+		}
+
+		if current == *instance {
+			Some(Deduction::Sequence(steps))
+		} else {
+			Deduction::instantiate_apart(prefix, body, &sub, instance)
+		}
+	}
+
+	/// Instantiates a `ForAll` prefix whose witnesses mention the variables
+	/// it binds, so extracting them one at a time would capture them. The
+	/// prefix is first generalized again over fresh variables, which are
+	/// then extracted instead.
+	fn instantiate_apart(prefix: &[&Formula], body: &Formula, sub: &Substitution, instance: &Formula)
+	-> Option<Deduction> {
+		let mut vars: Vec<ConstName> = vec!();
+		for q in prefix.iter() {
+			match q {
+				Formula::ForAll(v, _) if !vars.contains(v) => vars.push(v.clone()),
+				_ => return None,
+			}
+		}
+
+		let base = sub.terms.iter()
+			.map(|(_, t)| t.max_int())
+			.fold(prefix[0].max_int().max(instance.max_int()), |a, b| a.max(b));
+		let fresh: Vec<ConstName> = (1..=vars.len() as u64).map(|i| ConstName::Int(base + i)).collect();
+		let konst = |c: &ConstName| Formula::Const(c.clone());
+
+		// The prefix over the fresh variables, as `ForAll(p0, ... ForAll(pn, b))`.
+		let renamed = vars.iter().zip(fresh.iter())
+			.fold(body.clone(), |b, (v, p)| b.substitute(v, &Formula::Free(p.clone())));
+		let generalized = fresh.iter().rev()
+			.fold(renamed, |b, p| Formula::ForAll(p.clone(), b.ptr()));
+
+		let mut extract = vec!();
+		let mut current = prefix[0].clone();
+		for (v, p) in vars.iter().zip(fresh.iter()) {
+			current = match &current {
+				Formula::ForAll(_, e) => {
+					extract.push(Deduction::ForAllExtract((v.clone(), e.clone()), konst(p).ptr()));
+					e.substitute(v, &konst(p))
+				}
+				_ => unreachable!(),
+			};
+		}
+
+		// The generalizations, each with the outer fresh variables as constants.
+		let mut bodies = vec!();
+		let mut current = generalized.clone();
+		for p in fresh.iter() {
+			current = match &current {
+				Formula::ForAll(_, e) => {
+					bodies.push(e.clone());
+					e.substitute(p, &konst(p))
+				}
+				_ => unreachable!(),
+			};
+		}
+		let intro = fresh.iter().zip(bodies).rev()
+			.fold(Deduction::Sequence(extract), |w, (p, b)|
+				Deduction::ForAllIntro((p.clone(), b), Ptr::new(w)));
+
+		let mut steps = vec!(intro);
+		let mut current = generalized;
+		for (v, p) in vars.iter().zip(fresh.iter()) {
+			current = match &current {
+				Formula::ForAll(_, e) => {
+					let t = sub.term(v).cloned().unwrap_or(Formula::True);
+					steps.push(Deduction::ForAllExtract((p.clone(), e.clone()), t.clone().ptr()));
+					e.substitute(p, &t)
+				}
+				_ => unreachable!(),
+			};
+		}
+
+		if current == *instance {
+			Some(Deduction::Sequence(steps))
+		} else {
+			None
+		}
+	}
+
+	/// Checks each link `(rel, next, w)` of the chain, where `w` must deduce
+	/// `rel(previous, next)`, and deduces the composed relation between
+	/// `start` and the last term. An empty chain deduces `Eq(start, start)`.
+	pub fn calc<K: KnowledgeBase>(&self, k: ResultBase<K>, start: Form, links: &[(CalcRel, Form, Work)])
+	-> ResultBase<K> {
+		let hole = ConstName::Int(links.iter()
+			.map(|(_, f, _)| f.max_int())
+			.fold(start.max_int(), |a, b| a.max(b)) + 1);
+		let free = Formula::Free(hole.clone()).ptr();
+		let seq = |v: Vec<Deduction>| Ptr::new(Deduction::Sequence(v));
+
+		let mut k = Deduction::EqualityIntro(start.clone()).apply_work_inner(k);
+		let mut rel = CalcRel::Eq;
+		let mut prev = start.clone();
+		for (r, next, w) in links.iter() {
+			let link = r.relate(prev.clone(), next.clone());
+			if !w.deduced_inner(k.clone(), &link) {
+				return ResultBase::Err(format!("Calc: Did not deduce link {}", link.to_string()));
+			}
+			k = k.result_form(link);
+
+			let steps = match (rel, *r) {
+				(rel, CalcRel::Eq) => vec!(Deduction::Substitution(
+					prev.clone(), next.clone(), hole.clone(),
+					rel.relate(start.clone(), free.clone()).ptr())),
+				(CalcRel::Eq, r) => vec!(
+					Deduction::EqualityIntro(start.clone()),
+					Deduction::Substitution(
+						start.clone(), prev.clone(), hole.clone(),
+						Formula::Eq(free.clone(), start.clone()).ptr()),
+					Deduction::Substitution(
+						prev.clone(), start.clone(), hole.clone(),
+						r.relate(free.clone(), next.clone()).ptr())),
+				(CalcRel::IFF, CalcRel::IFF) => vec!(Deduction::IFFIntro(
+					(start.clone(), seq(vec!(
+						Deduction::IFFExtract(start.clone(), prev.clone()),
+						Deduction::IFFExtract(prev.clone(), next.clone())))),
+					(next.clone(), seq(vec!(
+						Deduction::IFFExtract(prev.clone(), next.clone()),
+						Deduction::IFFExtract(start.clone(), prev.clone())))))),
+				(rel, r) => {
+					let extract = |rel: CalcRel, a: &Form, b: &Form| match rel {
+						CalcRel::IFF => Deduction::IFFExtract(a.clone(), b.clone()),
+						_ => Deduction::ImplyExtract(a.clone(), b.clone()),
+					};
+					vec!(Deduction::ImplyIntro(start.clone(), next.clone(), seq(vec!(
+						extract(rel, &start, &prev),
+						extract(r, &prev, next)))))
+				}
+			};
+			k = Deduction::Sequence(steps).apply_work_inner(k);
+			rel = rel.compose(*r);
+			prev = next.clone();
+
+			if k.is_err() {
+				return k;
+			}
+		}
+
+		let thm = rel.relate(start, prev);
+		if k.contains(&thm) {
+			k
+		} else {
+			ResultBase::Err(format!("Calc: Did not deduce {}", thm.to_string()))
+		}
+	}
+
 	pub fn substitution<K: KnowledgeBase>(&self, 
 		k: ResultBase<K>, 
 		f1: Form,
@@ -234,7 +528,7 @@ impl Deduction {
 		let c1 = k.contains(&f1);
 		let c2 = k.contains(&f2);
 		if c1 || c2 {
-			k.result_form(Formula::And(f1.clone(), f2.clone()))
+			k.result_form(Formula::Or(f1.clone(), f2.clone()))
 		} else {
 			ResultBase::Err(format!("OrIntro: Did not deduced either f1 = {} or f2 = {}", 
 				f2.to_string(),
@@ -250,9 +544,13 @@ impl Deduction {
 	-> ResultBase<K> {
 		let k1 = k.result_ptr(f1.0.clone());
 		let k2 = k.result_ptr(f2.0.clone());
-		let c1 = f1.1.deduced(k1, &f3);
-		let c2 = f2.1.deduced(k2, &f3);
-		if c1 && c2 {
+		let c1 = f1.1.deduced_inner(k1, &f3);
+		let c2 = f2.1.deduced_inner(k2, &f3);
+		if !k.contains(&Formula::Or(f1.0.clone(), f2.0.clone())) {
+			ResultBase::Err(format!("OrExtract: Did not deduced f1|f2 = {} || {}",
+				f1.0.to_string(),
+				f2.0.to_string()))
+		} else if c1 && c2 {
 			k.result_ptr(f3.clone())
 		} else if !c1 {
 			ResultBase::Err(format!("OrExtract: Did not deduced f1->f3 = {}", f2.0.to_string()))
@@ -264,7 +562,7 @@ impl Deduction {
 	pub fn not_intro<K: KnowledgeBase>(&self, k: ResultBase<K>, f1: Form, work: Work) 
 	-> ResultBase<K> {
 		let assume = k.result_ptr(f1.clone());
-		let c = work.deduced(assume, &Formula::False);
+		let c = work.deduced_inner(assume, &Formula::False);
 
 		if c {
 			k.result_form(Formula::Not(f1))
@@ -273,6 +571,18 @@ impl Deduction {
 		}
 	}
 
+	pub fn contradiction<K: KnowledgeBase>(&self, k: ResultBase<K>, f1: Form, work: Work) 
+	-> ResultBase<K> {
+		let assume = k.result_form(Formula::Not(f1.clone()));
+		let c = work.deduced_inner(assume, &Formula::False);
+
+		if c {
+			k.result_ptr(f1)
+		} else {
+			ResultBase::Err(format!("Contradiction: Did not reach contradiction from Not({})", f1.to_string()))
+		}
+	}
+
 	pub fn not_extract<K: KnowledgeBase>(&self, k: ResultBase<K>, f1: Form) 
 	-> ResultBase<K> {
 		let c1 = k.contains(&f1);
@@ -330,8 +640,8 @@ impl Deduction {
 	-> ResultBase<K> {
 		let k1 = k.result_ptr(f1.0.clone());
 		let k2 = k.result_ptr(f2.0.clone());
-		let c1 = f1.1.deduced(k1, &f2.0);
-		let c2 = f2.1.deduced(k2, &f1.0);
+		let c1 = f1.1.deduced_inner(k1, &f2.0);
+		let c2 = f2.1.deduced_inner(k2, &f1.0);
 
 		if c1 && c2 {
 			k.result_form(Formula::IFF(f1.0.clone(), f2.0.clone()))
@@ -345,7 +655,7 @@ impl Deduction {
 	pub fn implies_intro<K: KnowledgeBase>(&self, k: ResultBase<K>, f1: Form, f2: Form, w: Work) 
 	-> ResultBase<K> {
 		let k1 = k.result_ptr(f1.clone());
-		let c1 = w.deduced(k1, &f2);
+		let c1 = w.deduced_inner(k1, &f2);
 
 		if c1 {
 			k.result_form(Formula::Implies(f1.clone(), f2.clone()))
@@ -353,4 +663,103 @@ impl Deduction {
 			ResultBase::Err(format!("ImpliesIntro: Did not deduced f2->f1 = {}", f1.to_string()))
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Facts(Vec<Formula>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.contains(form)
+		}
+
+		fn has_const(&self, _c: &ConstName) -> bool {
+			false
+		}
+	}
+
+	fn atom(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn and(l: &Form, r: &Form) -> Form { Formula::And(l.clone(), r.clone()).ptr() }
+	fn or(l: &Form, r: &Form) -> Form { Formula::Or(l.clone(), r.clone()).ptr() }
+	fn not(f: &Form) -> Form { Formula::Not(f.clone()).ptr() }
+
+	#[test]
+	fn and_extract_needs_the_conjunction() {
+		let (p, q) = (atom("p"), atom("q"));
+		let d = Deduction::AndExtract(p.clone(), q.clone());
+		assert!(d.deduced(Facts(vec!((*and(&p, &q)).clone())), &p));
+		assert!(d.deduced(Facts(vec!((*and(&p, &q)).clone())), &q));
+		assert!(d.apply_work(Facts(vec!((*p).clone(), (*q).clone()))).is_err());
+	}
+
+	#[test]
+	fn or_intro_concludes_the_disjunction() {
+		let (p, q) = (atom("p"), atom("q"));
+		let d = Deduction::OrIntro(p.clone(), q.clone());
+		assert!(d.deduced(Facts(vec!((*p).clone())), &or(&p, &q)));
+		assert!(!d.deduced(Facts(vec!((*p).clone())), &and(&p, &q)));
+		assert!(d.apply_work(Facts(vec!())).is_err());
+	}
+
+	#[test]
+	fn or_extract_needs_the_disjunction() {
+		let (p, q) = (atom("p"), atom("q"));
+		let case = |f: &Form| (f.clone(), Ptr::new(Deduction::OrIntro(q.clone(), p.clone())));
+		let d = Deduction::OrExtract(case(&p), case(&q), or(&q, &p));
+		assert!(d.deduced(Facts(vec!((*or(&p, &q)).clone())), &or(&q, &p)));
+		assert!(d.apply_work(Facts(vec!())).is_err());
+	}
+
+	#[test]
+	fn true_intro() {
+		assert!(Deduction::TrueIntro.deduced(Facts(vec!()), &Formula::True));
+	}
+
+	#[test]
+	fn contradiction_concludes_from_absurd_negation() {
+		let p = atom("p");
+		let d = Deduction::Contradiction(p.clone(), Ptr::new(Deduction::NotExtract(not(&p))));
+		assert!(d.deduced(Facts(vec!((*not(&not(&p))).clone())), &p));
+		assert!(d.apply_work(Facts(vec!())).is_err());
+	}
+
+	#[test]
+	fn exists_extract_needs_the_existential() {
+		let (x, w) = (ConstName::String(Ptr::new("x".to_string())), ConstName::String(Ptr::new("w".to_string())));
+		let body = Formula::Eq(Formula::Free(x.clone()).ptr(), atom("p")).ptr();
+		let ex = Formula::Exists(x.clone(), body.clone());
+		let extract = || Deduction::ExistsExtract((x.clone(), body.clone()), w.clone());
+		let d = extract();
+		let witnessed = Formula::Eq(Formula::Const(w.clone()).ptr(), atom("p"));
+		assert!(d.deduced(Facts(vec!(ex.clone())), &witnessed));
+		assert!(d.apply_work(Facts(vec!(ex.clone()))).has_const(&w));
+		assert!(d.apply_work(Facts(vec!())).is_err());
+		let twice = Deduction::Sequence(vec!(extract(), extract()));
+		assert!(twice.apply_work(Facts(vec!(ex))).is_err());
+	}
+
+	#[test]
+	fn forall_by_names_an_assumption() {
+		let (x, a, q) = (ConstName::String(Ptr::new("x".to_string())), atom("a"), atom("q"));
+		let h = ConstName::String(Ptr::new("h".to_string()));
+		let all = Formula::ForAll(x.clone(), or(&Formula::Free(x.clone()).ptr(), &q)).ptr();
+		let instance = or(&a, &q);
+		let body = Deduction::Sequence(vec!(
+			Deduction::Name(h.clone(), all.clone()),
+			Deduction::ForAllBy(h.clone(), instance.clone()),
+		));
+		let d = Deduction::ImplyIntro(all.clone(), instance.clone(), Ptr::new(body));
+		assert!(d.deduced(Facts(vec!()), &Formula::Implies(all.clone(), instance.clone())));
+
+		let unnamed = Deduction::ImplyIntro(all.clone(), instance.clone(),
+			Ptr::new(Deduction::ForAllBy(h.clone(), instance.clone())));
+		assert!(unnamed.apply_work(Facts(vec!())).is_err());
+		assert!(Deduction::Name(h, all).apply_work(Facts(vec!())).is_err());
+	}
+}
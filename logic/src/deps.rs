@@ -0,0 +1,321 @@
+//! Dependencies between the theorems of a library.
+//!
+//! A theorem without a proof is an axiom. A theorem depends on the
+//! theorems its proof uses, as found by `Library::theorems_used`, and on
+//! the definitions of the constants it mentions or introduces with `Let`.
+//! Each `Let` of a proof is a definition named after its constant, unless
+//! the proof is nothing but definitions, in which case the theorem itself
+//! is the definition of those constants.
+
+use std::fmt::Write;
+
+use deduction::Deduction;
+use doc::Library;
+use formula::ConstName;
+use pretty::{self, Style};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+	Axiom,
+	Definition,
+	Theorem,
+}
+
+impl Kind {
+	pub fn name(self) -> &'static str {
+		match self {
+			Kind::Axiom => "axiom",
+			Kind::Definition => "definition",
+			Kind::Theorem => "theorem",
+		}
+	}
+}
+
+pub struct Node {
+	pub name: String,
+	pub kind: Kind,
+	/// The constant of a definition made with `Let` within a proof. Such
+	/// nodes are named after their constant, and are kept apart from the
+	/// theorems of the library even when the names are the same.
+	pub constant: Option<ConstName>,
+	/// The indices of the nodes this one depends on directly.
+	pub deps: Vec<usize>,
+}
+
+#[derive(Default)]
+pub struct Graph {
+	pub nodes: Vec<Node>,
+}
+
+impl Graph {
+	/// The index of the theorem with the given name, adding it if needed.
+	pub fn node(&mut self, name: &str, kind: Kind) -> usize {
+		match self.find(name) {
+			Some(i) => i,
+			None => self.add(Node { name: name.to_string(), kind, constant: None, deps: vec!() }),
+		}
+	}
+
+	/// The index of the definition of a constant made with `Let`, adding it
+	/// if needed.
+	pub fn definition(&mut self, c: &ConstName) -> usize {
+		match self.nodes.iter().position(|n| n.constant.as_ref() == Some(c)) {
+			Some(i) => i,
+			None => self.add(Node {
+				name: pretty::name(c, Style::Ascii),
+				kind: Kind::Definition,
+				constant: Some(c.clone()),
+				deps: vec!(),
+			}),
+		}
+	}
+
+	fn add(&mut self, node: Node) -> usize {
+		self.nodes.push(node);
+		self.nodes.len() - 1
+	}
+
+	/// The index of the theorem with the given name.
+	pub fn find(&self, name: &str) -> Option<usize> {
+		self.nodes.iter().position(|n| n.constant.is_none() && n.name == name)
+	}
+
+	/// Records that the node `from` depends on the node `to`.
+	pub fn depend(&mut self, from: usize, to: usize) {
+		if from != to && !self.nodes[from].deps.contains(&to) {
+			self.nodes[from].deps.push(to);
+		}
+	}
+
+	pub fn from_library(library: &Library) -> Graph {
+		let mut g = Graph::default();
+		let mut defined: Vec<(ConstName, usize)> = vec!();
+
+		for t in library.theorems.iter() {
+			let mut lets = vec!();
+			let kind = match &t.proof {
+				None => Kind::Axiom,
+				Some(d) => if definitions(d, &mut lets) { Kind::Definition } else { Kind::Theorem },
+			};
+			let i = g.node(&t.name, kind);
+			for c in lets {
+				let j = if kind == Kind::Definition {
+					i
+				} else {
+					let j = g.definition(&c);
+					g.depend(i, j);
+					j
+				};
+				defined.push((c, j));
+			}
+		}
+
+		for (i, t) in library.theorems.iter().enumerate() {
+			let from = g.find(&t.name).unwrap();
+			for j in library.theorems_used(i) {
+				let to = g.find(&library.theorems[j].name).unwrap();
+				g.depend(from, to);
+			}
+			let names = t.statement.constants();
+			for (c, j) in defined.iter() {
+				if names.contains(c) {
+					g.depend(from, *j);
+				}
+			}
+		}
+		g
+	}
+
+	/// The nodes `name` depends on, directly or not.
+	pub fn dependencies(&self, name: &str) -> Vec<&Node> {
+		match self.find(name) {
+			Some(i) => self.closure(i, |g, j| g.nodes[j].deps.clone()),
+			None => vec!(),
+		}
+	}
+
+	/// The nodes depending on `name`, directly or not: what would lose its
+	/// proof if `name` were removed.
+	pub fn users(&self, name: &str) -> Vec<&Node> {
+		match self.find(name) {
+			Some(i) => self.closure(i, |g, j| (0..g.nodes.len()).filter(|k| g.nodes[*k].deps.contains(&j)).collect()),
+			None => vec!(),
+		}
+	}
+
+	/// The axioms `name` depends on.
+	pub fn axioms(&self, name: &str) -> Vec<&Node> {
+		self.dependencies(name).into_iter().filter(|n| n.kind == Kind::Axiom).collect()
+	}
+
+	/// The nodes reachable from `start` through `next`, without `start`
+	/// itself.
+	fn closure<F: Fn(&Graph, usize) -> Vec<usize>>(&self, start: usize, next: F) -> Vec<&Node> {
+		let mut seen = vec!(start);
+		let mut todo = vec!(start);
+		while let Some(i) = todo.pop() {
+			for j in next(self, i) {
+				if !seen.contains(&j) {
+					seen.push(j);
+					todo.push(j);
+				}
+			}
+		}
+		seen[1..].iter().map(|i| &self.nodes[*i]).collect()
+	}
+
+	/// The graph in Graphviz DOT, with edges from each node to its
+	/// dependencies. Nodes are numbered, so a definition may share the name
+	/// of a theorem. Axioms are boxes and definitions ellipses.
+	pub fn dot(&self) -> String {
+		let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+		let mut out = String::from("digraph dependencies {\n");
+		for (i, n) in self.nodes.iter().enumerate() {
+			let shape = match n.kind {
+				Kind::Axiom => "box",
+				Kind::Definition => "ellipse",
+				Kind::Theorem => "plaintext",
+			};
+			let _ = writeln!(out, "\tn{} [label={}, shape={}];", i, quote(&n.name), shape);
+		}
+		for (i, n) in self.nodes.iter().enumerate() {
+			for d in n.deps.iter() {
+				let _ = writeln!(out, "\tn{} -> n{};", i, d);
+			}
+		}
+		out.push_str("}\n");
+		out
+	}
+}
+
+/// Collects the constants the deduction defines with `Let`, and returns
+/// whether it does nothing else.
+pub fn definitions(d: &Deduction, out: &mut Vec<ConstName>) -> bool {
+	match d {
+		Deduction::Let(c, _, _) => {
+			out.push(c.clone());
+			true
+		}
+		Deduction::EmptyStep => true,
+		Deduction::Sequence(v) => {
+			// Every step is visited, so all the definitions are collected.
+			let mut only = true;
+			for d in v.iter() {
+				only &= definitions(d, out);
+			}
+			only
+		}
+		Deduction::ImplyIntro(_, _, w) | Deduction::NotIntro(_, w) | Deduction::Contradiction(_, w)
+		| Deduction::ForAllIntro(_, w) => {
+			definitions(w, out);
+			false
+		}
+		Deduction::IFFIntro((_, w1), (_, w2)) | Deduction::OrExtract((_, w1), (_, w2), _) => {
+			definitions(w1, out);
+			definitions(w2, out);
+			false
+		}
+		Deduction::Calc(_, links) => {
+			links.iter().for_each(|(_, _, w)| { definitions(w, out); });
+			false
+		}
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use doc::Theorem;
+	use formula::Formula;
+	use Ptr;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn atom(s: &str) -> Formula {
+		Formula::Const(name(s))
+	}
+
+	fn theorem(n: &str, statement: Formula, proof: Option<Deduction>) -> Theorem {
+		Theorem {
+			name: n.to_string(),
+			theory: "T".to_string(),
+			description: String::new(),
+			statement,
+			proof,
+			source: String::new(),
+		}
+	}
+
+	/// Two axioms `p` and `q`, a definition of `c`, a lemma `p & q` and a
+	/// theorem mentioning `c` proven from the lemma. `inner` defines `d`
+	/// within its proof, next to an axiom also named `d`.
+	fn graph() -> Graph {
+		let (p, q, c) = (atom("p").ptr(), atom("q").ptr(), atom("c").ptr());
+		let pq = Formula::And(p.clone(), q.clone()).ptr();
+		let library = Library {
+			theorems: vec!(
+				theorem("ax_p", (*p).clone(), None),
+				theorem("ax_q", (*q).clone(), None),
+				theorem("def", Formula::True, Some(Deduction::Let(name("c"), vec!(), p.clone()))),
+				theorem("lemma", (*pq).clone(), Some(Deduction::AndIntro(p.clone(), q.clone()))),
+				theorem("thm", Formula::Or(pq.clone(), c.clone()),
+					Some(Deduction::OrIntro(pq.clone(), c.clone()))),
+				theorem("d", Formula::True, None),
+				theorem("inner", Formula::And(q.clone(), p.clone()), Some(Deduction::Sequence(vec!(
+					Deduction::Let(name("d"), vec!(), p.clone()),
+					Deduction::AndIntro(q, p)))))),
+			constants: vec!(),
+		};
+		Graph::from_library(&library)
+	}
+
+	fn names(nodes: Vec<&Node>) -> Vec<String> {
+		let mut v: Vec<String> = nodes.iter().map(|n| format!("{} {}", n.kind.name(), n.name)).collect();
+		v.sort();
+		v
+	}
+
+	#[test]
+	fn dependencies_are_transitive() {
+		let g = graph();
+		assert_eq!(names(g.dependencies("thm")),
+			vec!("axiom ax_p", "axiom ax_q", "definition def", "theorem lemma"));
+		assert_eq!(names(g.dependencies("lemma")), vec!("axiom ax_p", "axiom ax_q"));
+		assert_eq!(names(g.axioms("thm")), vec!("axiom ax_p", "axiom ax_q"));
+		assert!(g.dependencies("ax_p").is_empty());
+		assert!(g.dependencies("missing").is_empty());
+	}
+
+	#[test]
+	fn users_are_transitive() {
+		let g = graph();
+		assert_eq!(names(g.users("ax_p")), vec!("theorem inner", "theorem lemma", "theorem thm"));
+		assert_eq!(names(g.users("def")), vec!("theorem thm"));
+		assert!(g.users("thm").is_empty());
+	}
+
+	#[test]
+	fn definitions_within_proofs_are_kept_apart() {
+		let g = graph();
+		let d = g.nodes.iter().position(|n| n.constant == Some(name("d"))).unwrap();
+		assert!(g.find("d") != Some(d));
+		assert!(g.nodes[g.find("d").unwrap()].kind == Kind::Axiom);
+		assert!(g.nodes[g.find("inner").unwrap()].deps.contains(&d));
+		assert!(g.users("d").is_empty());
+	}
+
+	#[test]
+	fn dot_draws_every_edge() {
+		let g = graph();
+		let dot = g.dot();
+		let lemma = g.find("lemma").unwrap();
+		assert!(dot.starts_with("digraph dependencies {\n"));
+		assert!(dot.contains("\tn0 [label=\"ax_p\", shape=box];\n"));
+		assert!(dot.contains(&format!("\tn{} -> n0;\n", lemma)));
+		let edges: usize = g.nodes.iter().map(|n| n.deps.len()).sum();
+		assert_eq!(dot.matches(" -> ").count(), edges);
+	}
+}
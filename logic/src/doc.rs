@@ -0,0 +1,328 @@
+//! Static HTML documentation of a library of theorems.
+//!
+//! The site has an index of the theories with a search box, and a page per
+//! theory listing its constants and theorems. Each theorem shows its
+//! statement, description and proof as a Fitch-style listing, with links to
+//! the theorems and constants it uses. A theorem uses another when its
+//! proof cites the other's statement from the context, or names it through
+//! `ForAllBy`.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+
+use deduction::Deduction;
+use formula::{Formula, ConstName};
+use pretty::{self, Style};
+use render;
+
+pub struct Theorem {
+	pub name: String,
+	pub theory: String,
+	pub description: String,
+	pub statement: Formula,
+	/// The checked proof, or `None` if only its source is known.
+	pub proof: Option<Deduction>,
+	pub source: String,
+}
+
+pub struct Constant {
+	pub name: String,
+	pub theory: String,
+	pub description: String,
+}
+
+#[derive(Default)]
+pub struct Library {
+	pub theorems: Vec<Theorem>,
+	pub constants: Vec<Constant>,
+}
+
+impl Library {
+	/// The theories in order of first appearance.
+	pub fn theories(&self) -> Vec<&str> {
+		let mut out: Vec<&str> = vec!();
+		let names = self.theorems.iter().map(|t| t.theory.as_str())
+			.chain(self.constants.iter().map(|c| c.theory.as_str()));
+		for t in names {
+			if !out.contains(&t) {
+				out.push(t);
+			}
+		}
+		out
+	}
+
+	/// The indices of the theorems the theorem at `i` uses.
+	pub fn theorems_used(&self, i: usize) -> Vec<usize> {
+		let proof = match &self.theorems[i].proof {
+			Some(d) => d,
+			None => return vec!(),
+		};
+		let context: Vec<Formula> = self.theorems.iter().enumerate()
+			.filter(|(j, _)| *j != i)
+			.map(|(_, t)| t.statement.clone())
+			.collect();
+		let uses = render::uses(proof, &context);
+
+		let mut out = vec!();
+		for (j, t) in self.theorems.iter().enumerate() {
+			let named = uses.theorems.iter().any(|c| match c {
+				ConstName::String(s) => **s == t.name,
+				ConstName::Int(_) => false,
+			});
+			if j != i && (named || uses.formulas.contains(&t.statement)) {
+				out.push(j);
+			}
+		}
+		out
+	}
+
+	/// The indices of the constants occurring in the statement of the
+	/// theorem at `i` or the theorems it uses.
+	pub fn constants_used(&self, i: usize) -> Vec<usize> {
+		let mut names = self.theorems[i].statement.constants();
+		for j in self.theorems_used(i) {
+			names.extend(self.theorems[j].statement.constants());
+		}
+		self.constants.iter().enumerate()
+			.filter(|(_, c)| names.iter().any(|n| match n {
+				ConstName::String(s) => **s == c.name,
+				ConstName::Int(_) => false,
+			}))
+			.map(|(j, _)| j)
+			.collect()
+	}
+
+	/// Writes the site into the directory `out`, creating it if needed.
+	pub fn write_site(&self, out: &Path) -> Result<(), String> {
+		fs::create_dir_all(out).map_err(|e| format!("Doc: Cannot create {}: {}", out.display(), e))?;
+		let write = |name: &str, text: String| fs::write(out.join(name), text)
+			.map_err(|e| format!("Doc: Cannot write {}: {}", name, e));
+
+		write("style.css", STYLE.to_string())?;
+		write("search.js", SEARCH.to_string())?;
+		write("search-index.js", self.search_index())?;
+		write("index.html", self.index())?;
+		for theory in self.theories() {
+			write(&page(theory), self.theory_page(theory))?;
+		}
+		Ok(())
+	}
+
+	fn index(&self) -> String {
+		let mut body = String::from("<h1>Theories</h1>\n<ul>\n");
+		for theory in self.theories() {
+			let count = self.theorems.iter().filter(|t| t.theory == theory).count();
+			body.push_str(&format!("<li><a href=\"{}\">{}</a> ({} theorems)</li>\n",
+				page(theory), escape(theory), count));
+		}
+		body.push_str("</ul>\n");
+		body.push_str("<h2>Search</h2>\n");
+		body.push_str("<input id=\"search\" type=\"search\" placeholder=\"Name, statement or description\" oninput=\"search(this.value)\">\n");
+		body.push_str("<ul id=\"results\"></ul>\n");
+		body.push_str("<script src=\"search-index.js\"></script>\n<script src=\"search.js\"></script>\n");
+		html("Theories", &body)
+	}
+
+	fn theory_page(&self, theory: &str) -> String {
+		let mut body = format!("<nav><a href=\"index.html\">Index</a></nav>\n<h1>{}</h1>\n", escape(theory));
+
+		let constants: Vec<&Constant> = self.constants.iter().filter(|c| c.theory == theory).collect();
+		if !constants.is_empty() {
+			body.push_str("<section>\n<h2>Constants</h2>\n<dl>\n");
+			for c in constants {
+				body.push_str(&format!("<dt id=\"{}\"><code>{}</code></dt>\n<dd>{}</dd>\n",
+					anchor("const", &c.name), escape(&c.name), escape(&c.description)));
+			}
+			body.push_str("</dl>\n</section>\n");
+		}
+
+		for (i, t) in self.theorems.iter().enumerate().filter(|(_, t)| t.theory == theory) {
+			body.push_str(&format!("<section class=\"theorem\" id=\"{}\">\n<h2>{}</h2>\n",
+				anchor("thm", &t.name), escape(&t.name)));
+			body.push_str(&format!("<p class=\"statement\">{}</p>\n", escape(&pretty::unicode(&t.statement))));
+			if !t.description.is_empty() {
+				body.push_str(&format!("<p>{}</p>\n", escape(&t.description)));
+			}
+
+			let proof = match &t.proof {
+				Some(d) => render::fitch(d, &[], Style::Unicode),
+				None => t.source.clone(),
+			};
+			if !proof.is_empty() {
+				body.push_str(&format!("<details>\n<summary>Proof</summary>\n<pre>{}</pre>\n</details>\n", escape(&proof)));
+			}
+
+			let mut links: Vec<String> = self.theorems_used(i).into_iter().map(|j| {
+				let u = &self.theorems[j];
+				format!("<a href=\"{}#{}\">{}</a>", page(&u.theory), anchor("thm", &u.name), escape(&u.name))
+			}).collect();
+			links.extend(self.constants_used(i).into_iter().map(|j| {
+				let c = &self.constants[j];
+				format!("<a href=\"{}#{}\"><code>{}</code></a>", page(&c.theory), anchor("const", &c.name), escape(&c.name))
+			}));
+			if !links.is_empty() {
+				body.push_str(&format!("<p class=\"uses\">Uses: {}</p>\n", links.join(", ")));
+			}
+			body.push_str("</section>\n");
+		}
+		html(theory, &body)
+	}
+
+	fn search_index(&self) -> String {
+		let mut entries = vec!();
+		for t in self.theorems.iter() {
+			entries.push(serde_json::json!({
+				"name": t.name,
+				"theory": t.theory,
+				"url": format!("{}#{}", page(&t.theory), anchor("thm", &t.name)),
+				"text": format!("{} {}", pretty::unicode(&t.statement), t.description),
+			}));
+		}
+		for c in self.constants.iter() {
+			entries.push(serde_json::json!({
+				"name": c.name,
+				"theory": c.theory,
+				"url": format!("{}#{}", page(&c.theory), anchor("const", &c.name)),
+				"text": c.description,
+			}));
+		}
+		format!("var SEARCH_INDEX = {};\n", serde_json::Value::Array(entries))
+	}
+}
+
+/// The file name of the page of a theory.
+fn page(theory: &str) -> String {
+	format!("theory-{}.html", slug(theory))
+}
+
+fn anchor(kind: &str, name: &str) -> String {
+	format!("{}-{}", kind, slug(name))
+}
+
+/// Keeps letters, digits, `-` and `_`, and writes other characters as `.`
+/// followed by their code point in six hex digits, so distinct names give
+/// distinct slugs.
+fn slug(s: &str) -> String {
+	s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+		c.to_string()
+	} else {
+		format!(".{:06x}", c as u32)
+	}).collect()
+}
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html(title: &str, body: &str) -> String {
+	format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+		<link rel=\"stylesheet\" href=\"style.css\">\n</head>\n<body>\n{}</body>\n</html>\n", escape(title), body)
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; padding: 1em; }
+.theorem { border-top: 1px solid #ccc; }
+.statement { font-size: 1.2em; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.uses { color: #555; }
+";
+
+const SEARCH: &str = "function search(query) {
+	var results = document.getElementById('results');
+	results.innerHTML = '';
+	query = query.toLowerCase();
+	if (!query) {
+		return;
+	}
+	SEARCH_INDEX.forEach(function (entry) {
+		if ((entry.name + ' ' + entry.text).toLowerCase().indexOf(query) < 0) {
+			return;
+		}
+		var link = document.createElement('a');
+		link.href = entry.url;
+		link.textContent = entry.name + ' (' + entry.theory + ')';
+		var item = document.createElement('li');
+		item.appendChild(link);
+		results.appendChild(item);
+	});
+}
+";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	fn atom(s: &str) -> Formula {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string())))
+	}
+
+	fn theorem(name: &str, theory: &str, statement: Formula, proof: Option<Deduction>) -> Theorem {
+		Theorem {
+			name: name.to_string(),
+			theory: theory.to_string(),
+			description: String::new(),
+			statement,
+			proof,
+			source: String::new(),
+		}
+	}
+
+	fn constant(name: &str, theory: &str) -> Constant {
+		Constant { name: name.to_string(), theory: theory.to_string(), description: String::new() }
+	}
+
+	/// `p` and `q` are axioms of one theory, and `p & q` is proven from them
+	/// in another.
+	fn library() -> Library {
+		let (p, q) = (atom("p"), atom("q"));
+		let both = Formula::And(p.clone().ptr(), q.clone().ptr());
+		let proof = Deduction::AndIntro(p.clone().ptr(), q.clone().ptr());
+		Library {
+			theorems: vec!(
+				theorem("p <1>", "Base", p, None),
+				theorem("q", "Base", q, None),
+				theorem("both", "Derived & more", both, Some(proof))),
+			constants: vec!(constant("p", "Base"), constant("q", "Base"), constant("r", "Base")),
+		}
+	}
+
+	#[test]
+	fn slugs_are_distinct_and_escapes_are_html() {
+		assert_eq!(slug("Group_theory-2"), "Group_theory-2");
+		assert_eq!(slug("a b"), "a.000020b");
+		assert_eq!(slug("a.000020b"), "a.00002e000020b");
+		assert_eq!(slug("é1"), ".0000e91");
+		assert_eq!(page("Sets & maps"), "theory-Sets.000020.000026.000020maps.html");
+		assert_eq!(escape("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+	}
+
+	#[test]
+	fn uses_follow_the_proof() {
+		let l = library();
+		assert!(l.theories() == vec!("Base", "Derived & more"));
+		assert!(l.theorems_used(2) == vec!(0, 1));
+		assert!(l.theorems_used(0).is_empty());
+		assert!(l.constants_used(2) == vec!(0, 1));
+	}
+
+	#[test]
+	fn site_links_theories_and_theorems() {
+		let dir = std::env::temp_dir().join(format!("logic-doc-{}", std::process::id()));
+		library().write_site(&dir).unwrap();
+		let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+
+		let index = read("index.html");
+		assert!(index.contains("<a href=\"theory-Base.html\">Base</a> (2 theorems)"));
+		assert!(index.contains("Derived &amp; more</a> (1 theorems)"));
+		let base = read("theory-Base.html");
+		assert!(base.contains("<h2>p &lt;1&gt;</h2>"));
+		assert!(base.contains("id=\"thm-p.000020.00003c1.00003e\""));
+		let derived = read(&page("Derived & more"));
+		assert!(derived.contains("<a href=\"theory-Base.html#thm-q\">q</a>"));
+		assert!(derived.contains("<a href=\"theory-Base.html#const-p\"><code>p</code></a>"));
+		assert!(read("search-index.js").starts_with("var SEARCH_INDEX = ["));
+		let _ = fs::remove_dir_all(&dir);
+	}
+}
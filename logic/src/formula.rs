@@ -106,7 +106,7 @@ impl Formula {
 			Formula::Not(l)        => Formula::Not(l.substitute(c, f).ptr()),
 			Formula::Relation(l)   => Formula::Relation(
 				l.iter().map(|x| x.substitute(c, f)).collect()),
-			Formula::ForAllSeq(_, _, e) => e.substitute(c, f),
+			Formula::ForAllSeq(a, v, e) => Formula::ForAllSeq(*a, v.clone(), e.substitute(c, f).ptr()),
 			o@Formula::ForAll(v, e) => if c == v {
 				o.clone()
 			} else {
@@ -176,6 +176,53 @@ impl Formula {
 		}
 	}
 
+	/// Returns the largest integer name used anywhere in the formula, so
+	/// fresh names can be chosen above it.
+	pub fn max_int(&self) -> u64 {
+		match self {
+			Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+			| Formula::Or(l, r) | Formula::Implies(l, r) => l.max_int().max(r.max_int()),
+			Formula::Not(l) => l.max_int(),
+			Formula::Relation(l) => l.iter().map(|x| match x {
+				Expr::Formula(e) => e.max_int(),
+				Expr::Head(s) | Expr::Seq(s) => s.max_int(),
+			}).max().unwrap_or(0),
+			Formula::Subst(body, v, sub) => body.max_int().max(v.max_int()).max(sub.max_int()),
+			Formula::ForAllSeq(_, v, e) | Formula::ForAll(v, e) | Formula::Exists(v, e) =>
+				v.max_int().max(e.max_int()),
+			Formula::Free(v) | Formula::Const(v) => v.max_int(),
+			Formula::True | Formula::False => 0,
+		}
+	}
+
+	/// Returns the constants of the formula, in order of first occurrence.
+	pub fn constants(&self) -> Vec<ConstName> {
+		let mut out = vec!();
+		self.constants_inner(&mut out);
+		out
+	}
+
+	fn constants_inner(&self, out: &mut Vec<ConstName>) {
+		match self {
+			Formula::Const(c) => if !out.contains(c) {
+				out.push(c.clone());
+			}
+			Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+			| Formula::Or(l, r) | Formula::Implies(l, r) | Formula::Subst(l, _, r) => {
+				l.constants_inner(out);
+				r.constants_inner(out);
+			}
+			Formula::Not(e) | Formula::ForAllSeq(_, _, e) | Formula::ForAll(_, e) | Formula::Exists(_, e) =>
+				e.constants_inner(out),
+			Formula::Relation(l) => for x in l.iter() {
+				if let Expr::Formula(e) = x {
+					e.constants_inner(out);
+				}
+			}
+			Formula::Free(_) | Formula::True | Formula::False => (),
+		}
+	}
+
 	pub fn ptr(self) -> Form { Form::new(self) }
 }
 
@@ -239,6 +286,15 @@ pub enum ConstName {
 	Int(u64)
 }
 
+impl ConstName {
+	fn max_int(&self) -> u64 {
+		match self {
+			ConstName::String(_) => 0,
+			ConstName::Int(i) => *i,
+		}
+	}
+}
+
 impl ToString for ConstName {
 	fn to_string(&self) -> String {
 		match self {
@@ -374,6 +430,13 @@ impl Seq {
 		}	
 	}
 
+	fn max_int(&self) -> u64 {
+		match self {
+			Seq::Free(_, v) => v.max_int(),
+			Seq::Tail(p) => p.max_int(),
+		}
+	}
+
 	pub fn ptr(self) -> Ptr<Seq> { Ptr::new(self) }
 }
 
@@ -385,3 +448,25 @@ impl Seq {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	#[test]
+	fn substitute_keeps_binders() {
+		let (x, p, q) = (Formula::Free(name("x")).ptr(), Formula::Const(name("p")).ptr(), Formula::Const(name("q")));
+		let eq = Formula::Eq(x.clone(), p.clone()).ptr();
+		let subst = Formula::Eq(q.clone().ptr(), p.clone()).ptr();
+		let seq = Formula::ForAllSeq(1, name("s"), eq.clone());
+		assert!(seq.substitute(&name("x"), &q) == Formula::ForAllSeq(1, name("s"), subst.clone()));
+		let all = Formula::ForAll(name("y"), eq.clone());
+		assert!(all.substitute(&name("x"), &q) == Formula::ForAll(name("y"), subst));
+		let shadowed = Formula::ForAll(name("x"), eq);
+		assert!(shadowed.substitute(&name("x"), &q) == shadowed);
+	}
+}
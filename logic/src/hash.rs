@@ -0,0 +1,328 @@
+//! Stable content hashes of formulas and deductions.
+//!
+//! `impl Hash for Formula` ignores names, so that alpha-equivalent formulas
+//! land in the same bucket, and depends on the standard hasher, which may
+//! change between releases. Content hashes instead cover every name and
+//! variant, agree with `==`, and are computed with 128-bit FNV-1a over a
+//! fixed encoding, so they can be stored and compared across runs.
+
+use std::fmt;
+
+use deduction::{Deduction, CalcRel};
+use formula::{Formula, ConstName, Expr, Seq};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ContentHash(pub u128);
+
+impl ContentHash {
+	/// Reads a hash written by `Display`.
+	pub fn parse(s: &str) -> Option<ContentHash> {
+		if s.len() != 32 {
+			return None;
+		}
+		u128::from_str_radix(s, 16).ok().map(ContentHash)
+	}
+}
+
+impl fmt::Display for ContentHash {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:032x}", self.0)
+	}
+}
+
+pub struct ContentHasher(u128);
+
+const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+impl Default for ContentHasher {
+	fn default() -> ContentHasher {
+		ContentHasher(OFFSET)
+	}
+}
+
+impl ContentHasher {
+	pub fn bytes(&mut self, bytes: &[u8]) {
+		for b in bytes.iter() {
+			self.0 ^= *b as u128;
+			self.0 = self.0.wrapping_mul(PRIME);
+		}
+	}
+
+	pub fn u64(&mut self, v: u64) {
+		self.bytes(&v.to_le_bytes());
+	}
+
+	/// Writes the length first, so consecutive strings cannot run together.
+	pub fn str(&mut self, s: &str) {
+		self.u64(s.len() as u64);
+		self.bytes(s.as_bytes());
+	}
+
+	pub fn tag(&mut self, t: u8) {
+		self.bytes(&[t]);
+	}
+
+	pub fn finish(&self) -> ContentHash {
+		ContentHash(self.0)
+	}
+}
+
+/// Values with a stable content hash.
+pub trait Content {
+	fn content(&self, h: &mut ContentHasher);
+
+	fn content_hash(&self) -> ContentHash {
+		let mut h = ContentHasher::default();
+		self.content(&mut h);
+		h.finish()
+	}
+}
+
+impl <T: Content> Content for [T] {
+	fn content(&self, h: &mut ContentHasher) {
+		h.u64(self.len() as u64);
+		self.iter().for_each(|x| x.content(h));
+	}
+}
+
+impl <T: Content> Content for Vec<T> {
+	fn content(&self, h: &mut ContentHasher) {
+		self.as_slice().content(h);
+	}
+}
+
+impl Content for ConstName {
+	fn content(&self, h: &mut ContentHasher) {
+		match self {
+			ConstName::String(s) => {
+				h.tag(0);
+				h.str(s);
+			}
+			ConstName::Int(i) => {
+				h.tag(1);
+				h.u64(*i);
+			}
+		}
+	}
+}
+
+impl Content for Seq {
+	fn content(&self, h: &mut ContentHasher) {
+		match self {
+			Seq::Free(ar, v) => {
+				h.tag(0);
+				h.u64(*ar);
+				v.content(h);
+			}
+			Seq::Tail(s) => {
+				h.tag(1);
+				s.content(h);
+			}
+		}
+	}
+}
+
+impl Content for Expr {
+	fn content(&self, h: &mut ContentHasher) {
+		match self {
+			Expr::Formula(f) => {
+				h.tag(0);
+				f.content(h);
+			}
+			Expr::Head(s) => {
+				h.tag(1);
+				s.content(h);
+			}
+			Expr::Seq(s) => {
+				h.tag(2);
+				s.content(h);
+			}
+		}
+	}
+}
+
+impl Content for Formula {
+	fn content(&self, h: &mut ContentHasher) {
+		match self {
+			Formula::True => h.tag(0),
+			Formula::False => h.tag(1),
+			Formula::Eq(l, r) => pair(h, 2, l, r),
+			Formula::IFF(l, r) => pair(h, 3, l, r),
+			Formula::And(l, r) => pair(h, 4, l, r),
+			Formula::Or(l, r) => pair(h, 5, l, r),
+			Formula::Implies(l, r) => pair(h, 6, l, r),
+			Formula::Not(e) => {
+				h.tag(7);
+				e.content(h);
+			}
+			Formula::Relation(v) => {
+				h.tag(8);
+				v.content(h);
+			}
+			Formula::Subst(body, v, sub) => {
+				h.tag(9);
+				body.content(h);
+				v.content(h);
+				sub.content(h);
+			}
+			Formula::ForAllSeq(ar, v, e) => {
+				h.tag(10);
+				h.u64(*ar);
+				v.content(h);
+				e.content(h);
+			}
+			Formula::ForAll(v, e) => binder(h, 11, v, e),
+			Formula::Exists(v, e) => binder(h, 12, v, e),
+			Formula::Free(v) => {
+				h.tag(13);
+				v.content(h);
+			}
+			Formula::Const(v) => {
+				h.tag(14);
+				v.content(h);
+			}
+		}
+	}
+}
+
+fn pair(h: &mut ContentHasher, tag: u8, l: &Formula, r: &Formula) {
+	h.tag(tag);
+	l.content(h);
+	r.content(h);
+}
+
+fn binder(h: &mut ContentHasher, tag: u8, v: &ConstName, e: &Formula) {
+	h.tag(tag);
+	v.content(h);
+	e.content(h);
+}
+
+impl Content for CalcRel {
+	fn content(&self, h: &mut ContentHasher) {
+		h.tag(match self {
+			CalcRel::Eq => 0,
+			CalcRel::IFF => 1,
+			CalcRel::Implies => 2,
+		});
+	}
+}
+
+impl Content for Deduction {
+	fn content(&self, h: &mut ContentHasher) {
+		match self {
+			Deduction::EmptyStep => h.tag(0),
+			Deduction::IFFIntro((a, w1), (b, w2)) => {
+				h.tag(1);
+				a.content(h);
+				w1.content(h);
+				b.content(h);
+				w2.content(h);
+			}
+			Deduction::IFFExtract(a, b) => pair(h, 2, a, b),
+			Deduction::SubstReduce(body, c, sub) => {
+				h.tag(3);
+				body.content(h);
+				c.content(h);
+				sub.content(h);
+			}
+			Deduction::AndIntro(a, b) => pair(h, 4, a, b),
+			Deduction::AndExtract(a, b) => pair(h, 5, a, b),
+			Deduction::OrIntro(a, b) => pair(h, 6, a, b),
+			Deduction::OrExtract((a, w1), (b, w2), c) => {
+				h.tag(7);
+				a.content(h);
+				w1.content(h);
+				b.content(h);
+				w2.content(h);
+				c.content(h);
+			}
+			Deduction::ImplyIntro(a, b, w) => {
+				pair(h, 8, a, b);
+				w.content(h);
+			}
+			Deduction::ImplyExtract(a, b) => pair(h, 9, a, b),
+			Deduction::NotIntro(a, w) => {
+				h.tag(10);
+				a.content(h);
+				w.content(h);
+			}
+			Deduction::NotExtract(a) => {
+				h.tag(11);
+				a.content(h);
+			}
+			Deduction::EqualityIntro(a) => {
+				h.tag(12);
+				a.content(h);
+			}
+			Deduction::Substitution(a, b, c, sub) => {
+				pair(h, 13, a, b);
+				c.content(h);
+				sub.content(h);
+			}
+			Deduction::ForAllSeqExtract((ar, v, e), ts) => {
+				h.tag(14);
+				h.u64(*ar);
+				v.content(h);
+				e.content(h);
+				ts.content(h);
+			}
+			Deduction::ExistsIntro((v, e), t) => {
+				binder(h, 15, v, e);
+				t.content(h);
+			}
+			Deduction::ExistsExtract((v, e), c) => {
+				binder(h, 16, v, e);
+				c.content(h);
+			}
+			Deduction::ForAllExtract((v, e), t) => {
+				binder(h, 17, v, e);
+				t.content(h);
+			}
+			Deduction::ForAllIntro((v, e), w) => {
+				binder(h, 18, v, e);
+				w.content(h);
+			}
+			Deduction::ForAllMatch(f) => {
+				h.tag(19);
+				f.content(h);
+			}
+			Deduction::ForAllBy(c, f) => {
+				h.tag(20);
+				c.content(h);
+				f.content(h);
+			}
+			Deduction::Sequence(v) => {
+				h.tag(21);
+				v.content(h);
+			}
+			Deduction::Let(c, vars, f) => {
+				h.tag(22);
+				c.content(h);
+				vars.content(h);
+				f.content(h);
+			}
+			Deduction::TrueIntro => h.tag(23),
+			Deduction::Contradiction(a, w) => {
+				h.tag(24);
+				a.content(h);
+				w.content(h);
+			}
+			Deduction::Calc(start, links) => {
+				h.tag(25);
+				start.content(h);
+				h.u64(links.len() as u64);
+				for (rel, next, w) in links.iter() {
+					rel.content(h);
+					next.content(h);
+					w.content(h);
+				}
+			}
+			Deduction::Name(c, f) => {
+				h.tag(26);
+				c.content(h);
+				f.content(h);
+			}
+		}
+	}
+}
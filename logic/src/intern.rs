@@ -0,0 +1,422 @@
+//! Hash-consed formulas.
+//!
+//! An `Arena` stores each distinct formula once, as a `Node` whose children
+//! are the `Id`s of other nodes, and each distinct name once, as a `Symbol`.
+//! Formulas interned in the same arena are equal exactly when their ids
+//! are, so comparing and hashing them takes constant time. `substitute`
+//! works on ids and remembers its results, so a subformula shared by many
+//! formulas is rewritten once.
+//!
+//! Interning is opt-in: formulas go in with `Arena::intern` and come back
+//! with `Arena::formula`, and `InternedBase` is a knowledge base built on an
+//! arena.
+
+use std::collections::{HashMap, HashSet};
+
+use formula::{self, Formula, ConstName, Expr};
+use knowledge_base::KnowledgeBase;
+use Ptr;
+
+/// An interned string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Symbol(u32);
+
+/// An interned formula.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Id(u32);
+
+impl Id {
+	pub fn index(self) -> usize {
+		self.0 as usize
+	}
+}
+
+/// A `ConstName` with its string interned.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Name {
+	Symbol(Symbol),
+	Int(u64),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Seq {
+	Free(u64, Name),
+	Tail(Box<Seq>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Arg {
+	Formula(Id),
+	Head(Seq),
+	Seq(Seq),
+}
+
+/// A formula whose subformulas are interned.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+	True,
+	False,
+	Eq(Id, Id),
+	IFF(Id, Id),
+	Relation(Vec<Arg>),
+	And(Id, Id),
+	Or(Id, Id),
+	Not(Id),
+	Implies(Id, Id),
+	Subst(Id, Name, Id),
+	ForAllSeq(u64, Name, Id),
+	ForAll(Name, Id),
+	Exists(Name, Id),
+	Free(Name),
+	Const(Name),
+}
+
+#[derive(Default)]
+pub struct Symbols {
+	/// Kept as pointers, so names read back share their strings.
+	strings: Vec<Ptr<String>>,
+	table: HashMap<String, Symbol>,
+}
+
+impl Symbols {
+	pub fn intern(&mut self, s: &str) -> Symbol {
+		if let Some(sym) = self.table.get(s) {
+			return *sym;
+		}
+		let sym = Symbol(self.strings.len() as u32);
+		self.strings.push(Ptr::new(s.to_string()));
+		self.table.insert(s.to_string(), sym);
+		sym
+	}
+
+	pub fn find(&self, s: &str) -> Option<Symbol> {
+		self.table.get(s).cloned()
+	}
+
+	pub fn get(&self, sym: Symbol) -> &str {
+		&self.strings[sym.0 as usize]
+	}
+}
+
+#[derive(Default)]
+pub struct Arena {
+	pub symbols: Symbols,
+	nodes: Vec<Node>,
+	table: HashMap<Node, Id>,
+	substituted: HashMap<(Id, Name, Id), Id>,
+}
+
+impl Arena {
+	pub fn node(&self, id: Id) -> &Node {
+		&self.nodes[id.index()]
+	}
+
+	/// The number of distinct formulas interned.
+	pub fn len(&self) -> usize {
+		self.nodes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.nodes.is_empty()
+	}
+
+	/// The id of the node, adding it if it is new.
+	pub fn add(&mut self, n: Node) -> Id {
+		if let Some(id) = self.table.get(&n) {
+			return *id;
+		}
+		let id = Id(self.nodes.len() as u32);
+		self.nodes.push(n.clone());
+		self.table.insert(n, id);
+		id
+	}
+
+	pub fn name(&mut self, c: &ConstName) -> Name {
+		match c {
+			ConstName::String(s) => Name::Symbol(self.symbols.intern(s)),
+			ConstName::Int(i) => Name::Int(*i),
+		}
+	}
+
+	pub fn const_name(&self, n: Name) -> ConstName {
+		match n {
+			Name::Symbol(s) => ConstName::String(self.symbols.strings[s.0 as usize].clone()),
+			Name::Int(i) => ConstName::Int(i),
+		}
+	}
+
+	pub fn intern(&mut self, f: &Formula) -> Id {
+		lower(self, f).unwrap()
+	}
+
+	/// The id of the formula if it was interned, without adding it.
+	pub fn find(&self, f: &Formula) -> Option<Id> {
+		lower(&mut Lookup(self), f)
+	}
+
+	pub fn formula(&self, id: Id) -> Formula {
+		match self.node(id) {
+			Node::True => Formula::True,
+			Node::False => Formula::False,
+			Node::Eq(l, r) => Formula::Eq(self.form(*l), self.form(*r)),
+			Node::IFF(l, r) => Formula::IFF(self.form(*l), self.form(*r)),
+			Node::Relation(args) => Formula::Relation(args.iter().map(|a| match a {
+				Arg::Formula(e) => Expr::Formula(self.formula(*e)),
+				Arg::Head(s) => Expr::Head(self.seq(s)),
+				Arg::Seq(s) => Expr::Seq(self.seq(s)),
+			}).collect()),
+			Node::And(l, r) => Formula::And(self.form(*l), self.form(*r)),
+			Node::Or(l, r) => Formula::Or(self.form(*l), self.form(*r)),
+			Node::Not(e) => Formula::Not(self.form(*e)),
+			Node::Implies(l, r) => Formula::Implies(self.form(*l), self.form(*r)),
+			Node::Subst(body, v, sub) => Formula::Subst(self.form(*body), self.const_name(*v), self.form(*sub)),
+			Node::ForAllSeq(ar, v, e) => Formula::ForAllSeq(*ar, self.const_name(*v), self.form(*e)),
+			Node::ForAll(v, e) => Formula::ForAll(self.const_name(*v), self.form(*e)),
+			Node::Exists(v, e) => Formula::Exists(self.const_name(*v), self.form(*e)),
+			Node::Free(v) => Formula::Free(self.const_name(*v)),
+			Node::Const(v) => Formula::Const(self.const_name(*v)),
+		}
+	}
+
+	fn form(&self, id: Id) -> formula::Form {
+		self.formula(id).ptr()
+	}
+
+	fn seq(&self, s: &Seq) -> formula::Seq {
+		match s {
+			Seq::Free(ar, v) => formula::Seq::Free(*ar, self.const_name(*v)),
+			Seq::Tail(s) => formula::Seq::Tail(self.seq(s).ptr()),
+		}
+	}
+
+	/// `Formula::substitute` on interned formulas: replaces the free
+	/// variable `c` in `id` with `by`.
+	pub fn substitute(&mut self, id: Id, c: Name, by: Id) -> Id {
+		if let Some(r) = self.substituted.get(&(id, c, by)) {
+			return *r;
+		}
+		let sub = |a: &mut Arena, e: Id| a.substitute(e, c, by);
+		let r = match self.node(id).clone() {
+			Node::Free(v) if v == c => by,
+			Node::ForAll(v, _) | Node::Exists(v, _) | Node::Subst(_, v, _) if v == c => id,
+			Node::True | Node::False | Node::Free(_) | Node::Const(_) => id,
+			Node::Eq(l, r) => { let n = Node::Eq(sub(self, l), sub(self, r)); self.add(n) }
+			Node::IFF(l, r) => { let n = Node::IFF(sub(self, l), sub(self, r)); self.add(n) }
+			Node::And(l, r) => { let n = Node::And(sub(self, l), sub(self, r)); self.add(n) }
+			Node::Or(l, r) => { let n = Node::Or(sub(self, l), sub(self, r)); self.add(n) }
+			Node::Implies(l, r) => { let n = Node::Implies(sub(self, l), sub(self, r)); self.add(n) }
+			Node::Not(e) => { let n = Node::Not(sub(self, e)); self.add(n) }
+			Node::Relation(args) => {
+				let args = args.into_iter().map(|a| match a {
+					Arg::Formula(e) => Arg::Formula(sub(self, e)),
+					a => a,
+				}).collect();
+				self.add(Node::Relation(args))
+			}
+			Node::Subst(body, v, s) => { let n = Node::Subst(sub(self, body), v, sub(self, s)); self.add(n) }
+			Node::ForAllSeq(ar, v, e) => { let n = Node::ForAllSeq(ar, v, sub(self, e)); self.add(n) }
+			Node::ForAll(v, e) => { let n = Node::ForAll(v, sub(self, e)); self.add(n) }
+			Node::Exists(v, e) => { let n = Node::Exists(v, sub(self, e)); self.add(n) }
+		};
+		self.substituted.insert((id, c, by), r);
+		r
+	}
+}
+
+/// Where `lower` finds the ids of names and nodes: an arena adds them, a
+/// `Lookup` only finds those already there.
+trait Table {
+	fn get_name(&mut self, c: &ConstName) -> Option<Name>;
+	fn get_node(&mut self, n: Node) -> Option<Id>;
+}
+
+impl Table for Arena {
+	fn get_name(&mut self, c: &ConstName) -> Option<Name> {
+		Some(Arena::name(self, c))
+	}
+
+	fn get_node(&mut self, n: Node) -> Option<Id> {
+		Some(self.add(n))
+	}
+}
+
+struct Lookup<'a>(&'a Arena);
+
+impl <'a> Table for Lookup<'a> {
+	fn get_name(&mut self, c: &ConstName) -> Option<Name> {
+		match c {
+			ConstName::String(s) => self.0.symbols.find(s).map(Name::Symbol),
+			ConstName::Int(i) => Some(Name::Int(*i)),
+		}
+	}
+
+	fn get_node(&mut self, n: Node) -> Option<Id> {
+		self.0.table.get(&n).cloned()
+	}
+}
+
+fn lower<T: Table>(t: &mut T, f: &Formula) -> Option<Id> {
+	let n = match f {
+		Formula::True => Node::True,
+		Formula::False => Node::False,
+		Formula::Eq(l, r) => Node::Eq(lower(t, l)?, lower(t, r)?),
+		Formula::IFF(l, r) => Node::IFF(lower(t, l)?, lower(t, r)?),
+		Formula::Relation(v) => {
+			let mut args = vec!();
+			for e in v.iter() {
+				args.push(match e {
+					Expr::Formula(e) => Arg::Formula(lower(t, e)?),
+					Expr::Head(s) => Arg::Head(lower_seq(t, s)?),
+					Expr::Seq(s) => Arg::Seq(lower_seq(t, s)?),
+				});
+			}
+			Node::Relation(args)
+		}
+		Formula::And(l, r) => Node::And(lower(t, l)?, lower(t, r)?),
+		Formula::Or(l, r) => Node::Or(lower(t, l)?, lower(t, r)?),
+		Formula::Not(e) => Node::Not(lower(t, e)?),
+		Formula::Implies(l, r) => Node::Implies(lower(t, l)?, lower(t, r)?),
+		Formula::Subst(body, v, sub) => Node::Subst(lower(t, body)?, t.get_name(v)?, lower(t, sub)?),
+		Formula::ForAllSeq(ar, v, e) => Node::ForAllSeq(*ar, t.get_name(v)?, lower(t, e)?),
+		Formula::ForAll(v, e) => Node::ForAll(t.get_name(v)?, lower(t, e)?),
+		Formula::Exists(v, e) => Node::Exists(t.get_name(v)?, lower(t, e)?),
+		Formula::Free(v) => Node::Free(t.get_name(v)?),
+		Formula::Const(v) => Node::Const(t.get_name(v)?),
+	};
+	t.get_node(n)
+}
+
+fn lower_seq<T: Table>(t: &mut T, s: &formula::Seq) -> Option<Seq> {
+	Some(match s {
+		formula::Seq::Free(ar, v) => Seq::Free(*ar, t.get_name(v)?),
+		formula::Seq::Tail(s) => Seq::Tail(Box::new(lower_seq(t, s)?)),
+	})
+}
+
+/// A knowledge base of interned theorems, where `contains` looks the
+/// formula up by id instead of comparing it with every theorem.
+#[derive(Default)]
+pub struct InternedBase {
+	pub arena: Arena,
+	theorems: Vec<Id>,
+	proven: HashSet<Id>,
+	constants: Vec<Name>,
+	declared: HashSet<Name>,
+}
+
+impl InternedBase {
+	pub fn add_theorem(&mut self, f: &Formula) -> Id {
+		let id = self.arena.intern(f);
+		if self.proven.insert(id) {
+			self.theorems.push(id);
+		}
+		id
+	}
+
+	pub fn add_constant(&mut self, c: &ConstName) {
+		let n = self.arena.name(c);
+		if self.declared.insert(n) {
+			self.constants.push(n);
+		}
+	}
+}
+
+impl KnowledgeBase for InternedBase {
+	fn contains(&self, form: &Formula) -> bool {
+		self.arena.find(form).is_some_and(|id| self.proven.contains(&id))
+	}
+
+	fn has_const(&self, c: &ConstName) -> bool {
+		Lookup(&self.arena).get_name(c).is_some_and(|n| self.declared.contains(&n))
+	}
+
+	fn constants(&self) -> Vec<ConstName> {
+		self.constants.iter().map(|n| self.arena.const_name(*n)).collect()
+	}
+
+	fn theorems(&self) -> Vec<Formula> {
+		self.theorems.iter().map(|id| self.arena.formula(*id)).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn free(s: &str) -> formula::Form {
+		Formula::Free(name(s)).ptr()
+	}
+
+	fn atom(s: &str) -> formula::Form {
+		Formula::Const(name(s)).ptr()
+	}
+
+	/// Formulas with `x` free, bound and shadowed under every connective.
+	fn formulas() -> Vec<Formula> {
+		let (x, y, p) = (free("x"), free("y"), atom("p"));
+		let eq = Formula::Eq(x.clone(), y.clone()).ptr();
+		vec!(
+			Formula::True,
+			(*x).clone(),
+			(*p).clone(),
+			Formula::Eq(x.clone(), p.clone()),
+			Formula::IFF(eq.clone(), p.clone()),
+			Formula::And(eq.clone(), eq.clone()),
+			Formula::Or(x.clone(), Formula::Not(x.clone()).ptr()),
+			Formula::Implies(eq.clone(), Formula::False.ptr()),
+			Formula::Relation(vec!(
+				Expr::Formula((*x).clone()),
+				Expr::Head(formula::Seq::Free(1, name("x"))),
+				Expr::Seq(formula::Seq::Tail(formula::Seq::Free(2, name("s")).ptr())))),
+			Formula::ForAll(name("x"), eq.clone()),
+			Formula::ForAll(name("y"), eq.clone()),
+			Formula::Exists(name("x"), eq.clone()),
+			Formula::Exists(name("z"), Formula::And(eq.clone(), free("z")).ptr()),
+			Formula::Subst(eq.clone(), name("x"), x.clone()),
+			Formula::Subst(eq.clone(), name("y"), x.clone()),
+			Formula::ForAllSeq(1, name("x"), eq.clone()),
+		)
+	}
+
+	#[test]
+	fn intern_round_trips() {
+		let mut a = Arena::default();
+		for f in formulas() {
+			let id = a.intern(&f);
+			assert!(a.formula(id) == f);
+			assert_eq!(a.intern(&f), id);
+			assert_eq!(a.find(&f), Some(id));
+		}
+		let n = a.len();
+		assert_eq!(a.find(&Formula::Not(atom("q"))), None);
+		assert_eq!(a.len(), n);
+	}
+
+	#[test]
+	fn substitute_agrees_with_formula() {
+		let by = Formula::And(atom("p"), free("y")).ptr();
+		let mut a = Arena::default();
+		let (x, b) = (a.name(&name("x")), a.intern(&by));
+		for f in formulas() {
+			let id = a.intern(&f);
+			let r = a.substitute(id, x, b);
+			assert!(a.formula(r) == f.substitute(&name("x"), &by));
+			assert_eq!(a.substitute(id, x, b), r);
+		}
+	}
+
+	#[test]
+	fn substitute_without_occurrence_keeps_id() {
+		let mut a = Arena::default();
+		let f = Formula::ForAll(name("x"), Formula::Eq(free("x"), atom("p")).ptr());
+		let (id, x, b) = (a.intern(&f), a.name(&name("x")), a.intern(&Formula::True));
+		assert_eq!(a.substitute(id, x, b), id);
+		let g = Formula::And(atom("p"), atom("q"));
+		let id = a.intern(&g);
+		assert_eq!(a.substitute(id, x, b), id);
+	}
+}
@@ -23,8 +23,34 @@ pub trait KnowledgeBase {
 
 	/// Returns whether or not the constant has been defined/declared.
 	fn has_const(&self, c: &formula::ConstName) -> bool;
+
+	/// Returns the declared constants, for implementors able to enumerate
+	/// them. Defaults to none.
+	fn constants(&self) -> Vec<formula::ConstName> {
+		vec!()
+	}
+
+	/// Returns the proven formulas, for implementors able to enumerate them.
+	/// Defaults to none.
+	fn theorems(&self) -> Vec<formula::Formula> {
+		vec!()
+	}
+
+	/// Returns the theorem stored under the given name, if any.
+	fn theorem(&self, _name: &formula::ConstName) -> Option<formula::Formula> {
+		None
+	}
 }
 
+/// A knowledge base that can be shared between threads. With the `sync`
+/// feature, a `ResultBase` over one is itself a `SharedBase`, so a context
+/// can be handed to worker threads through a `KBasePtr`.
+#[cfg(feature = "sync")]
+pub trait SharedBase: KnowledgeBase + Send + Sync {}
+
+#[cfg(feature = "sync")]
+impl <K: KnowledgeBase + Send + Sync> SharedBase for K {}
+
 
 /// ContextBase implementation for proofs. Uses a knowledge base implementor
 /// as its root, and is extended as needed.
@@ -34,6 +60,9 @@ pub enum ResultBase<K> {
 	Formula(formula::Formula, Ptr<ResultBase<K>>),
 	FormPtr(formula::Form, Ptr<ResultBase<K>>),
 	Const(formula::ConstName, Ptr<ResultBase<K>>),
+	/// A formula of the context named within the proof, such as an
+	/// assumption, so that `ForAllBy` can refer to it.
+	Named(formula::ConstName, formula::Form, Ptr<ResultBase<K>>),
 }
 
 
@@ -45,6 +74,7 @@ impl <K> Clone for ResultBase<K> {
 			ResultBase::Formula(f, k) => ResultBase::Formula(f.clone(), k.clone()),
 			ResultBase::FormPtr(f, k) => ResultBase::FormPtr(f.clone(), k.clone()),
 			ResultBase::Const(f, k) => ResultBase::Const(f.clone(), k.clone()),
+			ResultBase::Named(c, f, k) => ResultBase::Named(c.clone(), f.clone(), k.clone()),
 		}
 	}
 }
@@ -77,6 +107,11 @@ impl <K: KnowledgeBase> ResultBase<K> {
 		ResultBase::Const(sq, Ptr::new(self.clone()))
 	}
 
+	/// Names a formula of this result base.
+	pub fn result_named(&self, name: formula::ConstName, form: formula::Form) -> ResultBase<K> {
+		ResultBase::Named(name, form, self.ptr())
+	}
+
 
 	pub fn is_err(&self) -> bool {
 		match self {
@@ -84,6 +119,20 @@ impl <K: KnowledgeBase> ResultBase<K> {
 			_ => false
 		}
 	}
+
+	/// The first error of the chain. Steps applied after a failed one are
+	/// added on top of its `Err`, so the last link may hide it.
+	pub fn error(&self) -> Option<&str> {
+		let mut k = self;
+		loop {
+			k = match k {
+				ResultBase::Err(e) => return Some(e),
+				ResultBase::Root(_) => return None,
+				ResultBase::Formula(_, p) | ResultBase::FormPtr(_, p) | ResultBase::Const(_, p)
+				| ResultBase::Named(_, _, p) => p,
+			};
+		}
+	}
 }
 
 impl <K: KnowledgeBase> KnowledgeBase for ResultBase<K> {
@@ -94,6 +143,7 @@ impl <K: KnowledgeBase> KnowledgeBase for ResultBase<K> {
 			ResultBase::Formula(f, kbase) => form == f || kbase.contains(form),
 			ResultBase::FormPtr(f, kbase) => f.deref() == form || kbase.contains(form),
 			ResultBase::Const(_, kbase) => kbase.contains(form),
+			ResultBase::Named(_, _, kbase) => kbase.contains(form),
 		}
 	}
 
@@ -104,7 +154,113 @@ impl <K: KnowledgeBase> KnowledgeBase for ResultBase<K> {
 			ResultBase::FormPtr(_, k) => k.has_const(c),
 			ResultBase::Const(cr, k) => PartialEq::eq(cr, c) || k.has_const(c),	
 			ResultBase::Formula(_, kbase) => kbase.has_const(c),
+			ResultBase::Named(_, _, kbase) => kbase.has_const(c),
 		} 
 	}
+
+	fn constants(&self) -> Vec<formula::ConstName> {
+		match self {
+			ResultBase::Err(_) => vec!(),
+			ResultBase::Root(r) => r.constants(),
+			ResultBase::FormPtr(_, k) => k.constants(),
+			ResultBase::Const(cr, k) => {
+				let mut v = k.constants();
+				v.push(cr.clone());
+				v
+			}
+			ResultBase::Formula(_, kbase) => kbase.constants(),
+			ResultBase::Named(_, _, kbase) => kbase.constants(),
+		}
+	}
+
+	/// Lists the formulas of the context, most recently deduced first.
+	fn theorems(&self) -> Vec<formula::Formula> {
+		match self {
+			ResultBase::Err(_) => vec!(),
+			ResultBase::Root(r) => r.theorems(),
+			ResultBase::Formula(f, kbase) => {
+				let mut v = vec!(f.clone());
+				v.append(&mut kbase.theorems());
+				v
+			}
+			ResultBase::FormPtr(f, kbase) => {
+				let mut v = vec!(f.deref().clone());
+				v.append(&mut kbase.theorems());
+				v
+			}
+			ResultBase::Const(_, kbase) => kbase.theorems(),
+			ResultBase::Named(_, _, kbase) => kbase.theorems(),
+		}
+	}
+
+	/// Names given within the proof shadow those of the root.
+	fn theorem(&self, name: &formula::ConstName) -> Option<formula::Formula> {
+		match self {
+			ResultBase::Err(_) => None,
+			ResultBase::Named(c, f, _) if c == name => Some(f.deref().clone()),
+			ResultBase::Named(_, _, kbase) => kbase.theorem(name),
+			ResultBase::Root(r) => r.theorem(name),
+			ResultBase::Formula(_, kbase) => kbase.theorem(name),
+			ResultBase::FormPtr(_, kbase) => kbase.theorem(name),
+			ResultBase::Const(_, kbase) => kbase.theorem(name),
+		}
+	}
 }
 
+/// Fails to compile if a pointer that cannot cross threads is left in the
+/// formulas, deductions or contexts of a `sync` build.
+#[cfg(feature = "sync")]
+#[allow(dead_code)]
+fn assert_shared<K: SharedBase>() {
+	fn shared<T: Send + Sync>() {}
+	fn shared_base<T: SharedBase>() {}
+
+	shared::<formula::Formula>();
+	shared::<formula::ConstName>();
+	shared::<::deduction::Deduction>();
+	shared_base::<ResultBase<K>>();
+	shared::<KBasePtr<ResultBase<K>>>();
+}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+	use super::*;
+	use std::thread;
+	use deduction::Deduction;
+	use formula::{Formula, ConstName, Form};
+
+	struct Facts(Vec<Formula>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.contains(form)
+		}
+
+		fn has_const(&self, _c: &ConstName) -> bool {
+			false
+		}
+	}
+
+	fn atom(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	#[test]
+	fn contexts_are_shared_between_threads() {
+		let (p, q) = (atom("p"), atom("q"));
+		let root = ResultBase::new(Facts(vec!(Formula::And(p.clone(), q.clone()))));
+		let kbase: KBasePtr<ResultBase<Facts>> = root.result_form(Formula::True).ptr();
+
+		thread::scope(|s| {
+			for i in 0..4 {
+				let (kbase, p, q) = (kbase.clone(), p.clone(), q.clone());
+				s.spawn(move || {
+					let goal = if i % 2 == 0 { &p } else { &q };
+					let d = Deduction::AndExtract(p.clone(), q.clone());
+					assert!(d.deduced((*kbase).clone(), goal));
+					assert!(Deduction::EmptyStep.deduced((*kbase).clone(), &Formula::True));
+				});
+			}
+		});
+	}
+}
@@ -1,11 +1,38 @@
 extern crate either;
 extern crate serde;
+extern crate serde_json;
 
-//use std::sync::Arc;
+#[cfg(feature = "sync")]
+use std::sync::Arc;
+#[cfg(not(feature = "sync"))]
 use std::rc::Rc;
 
+/// `Arc` with the `sync` feature, so formulas and deductions can be shared
+/// between threads, and `Rc` otherwise.
+#[cfg(feature = "sync")]
+pub type Ptr<K> = Arc<K>;
+#[cfg(not(feature = "sync"))]
 pub type Ptr<K> = Rc<K>;
 
 pub mod formula;
 pub mod deduction;
-pub mod knowledge_base;
\ No newline at end of file
+pub mod knowledge_base;
+pub mod prover;
+pub mod normal;
+pub mod skolem;
+pub mod unify;
+pub mod rewrite;
+pub mod tptp;
+pub mod smtlib;
+pub mod sat;
+pub mod model;
+pub mod structure;
+pub mod propositional;
+pub mod pretty;
+pub mod render;
+pub mod doc;
+pub mod deps;
+pub mod hash;
+pub mod check;
+pub mod batch;
+pub mod intern;
\ No newline at end of file
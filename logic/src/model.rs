@@ -0,0 +1,556 @@
+//! A finite model finder, for counter-models of conjectured theorems.
+//!
+//! The search tries domains `{e0, ..., en-1}` of increasing size. For each
+//! size, quantifiers are expanded over the domain and the formulas are
+//! encoded into clauses over the values of the symbols, which are solved by
+//! `sat::Solver`. As in `smtlib`, a constant applied through
+//! `Relation([Const(f), t1, ..., tn])` is a relation in formula positions
+//! and a function in term positions, which are the sides of `Eq` and the
+//! arguments of relations.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use formula::{Formula, ConstName, Expr};
+use knowledge_base::KnowledgeBase;
+use pretty::{self, Style};
+use sat::Solver;
+use structure::Structure;
+
+/// The value of a function on each tuple of arguments.
+pub type Table = Vec<(Vec<usize>, usize)>;
+
+/// A finite structure for the symbols of some formulas.
+#[derive(Clone)]
+pub struct Model {
+	pub size: usize,
+	/// Term constants with their values.
+	pub constants: Vec<(ConstName, usize)>,
+	/// Propositional constants with their truth values.
+	pub propositions: Vec<(ConstName, bool)>,
+	/// Functions with their arity and value on every tuple of arguments.
+	pub functions: Vec<(ConstName, usize, Table)>,
+	/// Relations with their arity and the tuples they hold of.
+	pub relations: Vec<(ConstName, usize, Vec<Vec<usize>>)>,
+}
+
+impl fmt::Display for Model {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let elems: Vec<String> = (0..self.size).map(element).collect();
+		writeln!(f, "Domain: {{{}}}", elems.join(", "))?;
+		for (c, v) in self.constants.iter() {
+			writeln!(f, "\t{} = {}", pretty::name(c, Style::Unicode), element(*v))?;
+		}
+		for (c, b) in self.propositions.iter() {
+			writeln!(f, "\t{} = {}", pretty::name(c, Style::Unicode), b)?;
+		}
+		for (c, _, table) in self.functions.iter() {
+			for (args, v) in table.iter() {
+				writeln!(f, "\t{}({}) = {}", pretty::name(c, Style::Unicode), tuple(args), element(*v))?;
+			}
+		}
+		for (c, _, holds) in self.relations.iter() {
+			if holds.is_empty() {
+				writeln!(f, "\t{} holds of nothing", pretty::name(c, Style::Unicode))?;
+			}
+			for args in holds.iter() {
+				writeln!(f, "\t{}({})", pretty::name(c, Style::Unicode), tuple(args))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Structure for Model {
+	type Element = usize;
+
+	fn domain(&self) -> Vec<usize> {
+		(0..self.size).collect()
+	}
+
+	fn constant(&self, c: &ConstName) -> Option<usize> {
+		self.constants.iter().find(|(n, _)| n == c).map(|(_, v)| *v)
+	}
+
+	fn function(&self, f: &ConstName, args: &[usize]) -> Option<usize> {
+		self.functions.iter()
+			.find(|(n, _, _)| n == f)
+			.and_then(|(_, _, table)| table.iter().find(|(a, _)| a[..] == *args))
+			.map(|(_, v)| *v)
+	}
+
+	fn relation(&self, r: &ConstName, args: &[usize]) -> Option<bool> {
+		if args.is_empty() {
+			return self.proposition(r);
+		}
+		self.relations.iter()
+			.find(|(n, a, _)| n == r && *a == args.len())
+			.map(|(_, _, holds)| holds.iter().any(|t| t[..] == *args))
+	}
+
+	fn proposition(&self, c: &ConstName) -> Option<bool> {
+		self.propositions.iter().find(|(n, _)| n == c).map(|(_, b)| *b)
+	}
+}
+
+fn element(i: usize) -> String {
+	format!("e{}", i)
+}
+
+fn tuple(args: &[usize]) -> String {
+	args.iter().map(|a| element(*a)).collect::<Vec<_>>().join(", ")
+}
+
+/// Searches for a model of `axioms` in which `goal` is false, with at most
+/// `max_size` elements. The axioms are given explicitly, as a knowledge
+/// base need not enumerate its theorems, and must be theorems of `kbase`.
+/// The constants `kbase` declares are part of the signature even when the
+/// formulas do not mention them.
+pub fn counter_model<K: KnowledgeBase>(kbase: &K, axioms: &[Formula], goal: &Formula, max_size: usize)
+-> Result<Option<Model>, String> {
+	if let Some(a) = axioms.iter().find(|a| !kbase.contains(a)) {
+		return Err(format!("Model: {} is not a theorem of the knowledge base", a.to_string()));
+	}
+	let mut formulas = axioms.to_vec();
+	formulas.push(Formula::Not(goal.clone().ptr()));
+
+	let mut symbols = vec!();
+	for f in formulas.iter() {
+		signature(f, true, &mut symbols)?;
+	}
+	for c in kbase.constants() {
+		if !symbols.iter().any(|s| s.name == c) {
+			symbols.push(Symbol { name: c, arity: 0, predicate: false });
+		}
+	}
+	search(&formulas, &symbols, max_size)
+}
+
+/// Searches for a model of the closed formulas with at most `max_size`
+/// elements. When there is none, the formulas may be inconsistent.
+pub fn find(formulas: &[Formula], max_size: usize) -> Result<Option<Model>, String> {
+	let mut symbols = vec!();
+	for f in formulas.iter() {
+		signature(f, true, &mut symbols)?;
+	}
+	search(formulas, &symbols, max_size)
+}
+
+fn search(formulas: &[Formula], symbols: &[Symbol], max_size: usize) -> Result<Option<Model>, String> {
+	for size in 1..=max_size {
+		let mut g = Grounding { size, symbols, vars: HashMap::new(), clauses: vec!() };
+		g.functional();
+		for f in formulas.iter() {
+			let p = g.ground(f, &mut vec!())?;
+			g.assert(&p);
+		}
+
+		let mut solver = Solver::new(g.vars.len());
+		for c in g.clauses.iter() {
+			solver.add_clause(c)?;
+		}
+		if let Some(values) = solver.solve() {
+			return Ok(Some(g.model(&values)));
+		}
+	}
+	Ok(None)
+}
+
+struct Symbol {
+	name: ConstName,
+	arity: usize,
+	predicate: bool,
+}
+
+/// Collects the symbols of `f`, checking each is used with a single arity
+/// and sort.
+fn signature(f: &Formula, predicate: bool, symbols: &mut Vec<Symbol>) -> Result<(), String> {
+	let mut add = |c: &ConstName, arity: usize, predicate: bool| -> Result<(), String> {
+		match symbols.iter().find(|s| s.name == *c) {
+			Some(s) if s.arity != arity || s.predicate != predicate =>
+				Err(format!("Model: Symbol {} is used with different arities or sorts", c.to_string())),
+			Some(_) => Ok(()),
+			None => {
+				symbols.push(Symbol { name: c.clone(), arity, predicate });
+				Ok(())
+			}
+		}
+	};
+
+	match f {
+		Formula::Const(c) => add(c, 0, predicate),
+		Formula::Relation(v) => {
+			match v.first() {
+				Some(Expr::Formula(Formula::Const(c))) => add(c, v.len() - 1, predicate)?,
+				_ => return Err(format!("Model: Cannot interpret relation {}", f.to_string())),
+			}
+			for e in v[1..].iter() {
+				match e {
+					Expr::Formula(t) => signature(t, false, symbols)?,
+					s => return Err(format!("Model: Cannot interpret sequence {}", s.to_string())),
+				}
+			}
+			Ok(())
+		}
+		_ if !predicate => match f {
+			Formula::Free(_) => Ok(()),
+			o => Err(format!("Model: Cannot use formula {} as a term", o.to_string())),
+		}
+		Formula::Eq(l, r) => {
+			signature(l, false, symbols)?;
+			signature(r, false, symbols)
+		}
+		Formula::IFF(l, r) | Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) => {
+			signature(l, true, symbols)?;
+			signature(r, true, symbols)
+		}
+		Formula::Not(e) | Formula::ForAll(_, e) | Formula::Exists(_, e) => signature(e, true, symbols),
+		Formula::ForAllSeq(_, v, _) =>
+			Err(format!("Model: Cannot interpret sequence quantifier over {}", v.to_string())),
+		Formula::Subst(_, v, _) =>
+			Err(format!("Model: Cannot interpret substitution of {}", v.to_string())),
+		Formula::Free(v) => Err(format!("Model: Cannot use variable {} as a formula", v.to_string())),
+		Formula::True | Formula::False => Ok(()),
+	}
+}
+
+/// A ground propositional formula over the solver's variables.
+#[derive(Clone)]
+enum Prop {
+	Const(bool),
+	Var(i64),
+	And(Vec<Prop>),
+	Or(Vec<Prop>),
+	Iff(Box<Prop>, Box<Prop>),
+}
+
+impl Prop {
+	fn not(self) -> Prop {
+		match self {
+			Prop::Const(b) => Prop::Const(!b),
+			Prop::Var(v) => Prop::Var(-v),
+			Prop::And(v) => Prop::Or(v.into_iter().map(|p| p.not()).collect()),
+			Prop::Or(v) => Prop::And(v.into_iter().map(|p| p.not()).collect()),
+			Prop::Iff(l, r) => Prop::Iff(Box::new(l.not()), r),
+		}
+	}
+
+	fn and(v: Vec<Prop>) -> Prop {
+		let mut out = vec!();
+		for p in v {
+			match p {
+				Prop::Const(true) => (),
+				Prop::Const(false) => return Prop::Const(false),
+				Prop::And(mut w) => out.append(&mut w),
+				p => out.push(p),
+			}
+		}
+		if out.len() == 1 { out.pop().unwrap() } else if out.is_empty() { Prop::Const(true) } else { Prop::And(out) }
+	}
+
+	fn or(v: Vec<Prop>) -> Prop {
+		Prop::and(v.into_iter().map(|p| p.not()).collect()).not()
+	}
+}
+
+struct Grounding<'a> {
+	size: usize,
+	symbols: &'a [Symbol],
+	/// The variable of each symbol applied to a tuple of elements, followed
+	/// by the value for functions.
+	vars: HashMap<(usize, Vec<usize>), i64>,
+	clauses: Vec<Vec<i64>>,
+}
+
+impl <'a> Grounding<'a> {
+	fn var(&mut self, symbol: usize, key: Vec<usize>) -> i64 {
+		let next = self.vars.len() as i64 + 1;
+		*self.vars.entry((symbol, key)).or_insert(next)
+	}
+
+	fn index(&self, c: &ConstName) -> usize {
+		self.symbols.iter().position(|s| s.name == *c).expect("Model: Unknown symbol")
+	}
+
+	fn tuples(&self, arity: usize) -> Vec<Vec<usize>> {
+		let mut out = vec!(vec!());
+		for _ in 0..arity {
+			out = out.into_iter()
+				.flat_map(|t| (0..self.size).map(move |d| {
+					let mut t = t.clone();
+					t.push(d);
+					t
+				}))
+				.collect();
+		}
+		out
+	}
+
+	/// Requires every function to have exactly one value on each tuple, and
+	/// the `i`-th term constant to be one of the first `i + 1` elements.
+	fn functional(&mut self) {
+		let mut constants = 0;
+		for (i, s) in self.symbols.iter().enumerate() {
+			if s.predicate {
+				continue;
+			}
+			let bound = if s.arity == 0 {
+				constants += 1;
+				constants.min(self.size)
+			} else {
+				self.size
+			};
+			for args in self.tuples(s.arity) {
+				let values: Vec<i64> = (0..self.size).map(|d| {
+					let mut key = args.clone();
+					key.push(d);
+					self.var(i, key)
+				}).collect();
+				self.clauses.push(values[..bound].to_vec());
+				for a in 0..values.len() {
+					if a >= bound {
+						self.clauses.push(vec!(-values[a]));
+					}
+					for b in a + 1..values.len() {
+						self.clauses.push(vec!(-values[a], -values[b]));
+					}
+				}
+			}
+		}
+	}
+
+	fn ground(&mut self, f: &Formula, env: &mut Vec<(ConstName, usize)>) -> Result<Prop, String> {
+		Ok(match f {
+			Formula::True => Prop::Const(true),
+			Formula::False => Prop::Const(false),
+			Formula::Not(e) => self.ground(e, env)?.not(),
+			Formula::And(l, r) => Prop::and(vec!(self.ground(l, env)?, self.ground(r, env)?)),
+			Formula::Or(l, r) => Prop::or(vec!(self.ground(l, env)?, self.ground(r, env)?)),
+			Formula::Implies(l, r) => Prop::or(vec!(self.ground(l, env)?.not(), self.ground(r, env)?)),
+			Formula::IFF(l, r) => match (self.ground(l, env)?, self.ground(r, env)?) {
+				(Prop::Const(a), p) | (p, Prop::Const(a)) => if a { p } else { p.not() },
+				(a, b) => Prop::Iff(Box::new(a), Box::new(b)),
+			},
+			Formula::ForAll(v, e) | Formula::Exists(v, e) => {
+				let mut cases = vec!();
+				for d in 0..self.size {
+					env.push((v.clone(), d));
+					let p = self.ground(e, env);
+					env.pop();
+					cases.push(p?);
+				}
+				if let Formula::ForAll(_, _) = f { Prop::and(cases) } else { Prop::or(cases) }
+			}
+			Formula::Eq(l, r) => {
+				if let Some(d) = value(l, env) {
+					self.denote(r, d, env)?
+				} else {
+					let mut cases = vec!();
+					for d in 0..self.size {
+						cases.push(Prop::and(vec!(self.denote(l, d, env)?, self.denote(r, d, env)?)));
+					}
+					Prop::or(cases)
+				}
+			}
+			Formula::Const(c) => {
+				let i = self.index(c);
+				Prop::Var(self.var(i, vec!()))
+			}
+			Formula::Relation(v) => self.application(v, None, env)?,
+			o => return Err(format!("Model: Cannot interpret {}", o.to_string())),
+		})
+	}
+
+	/// The condition for the term `t` to denote the element `d`.
+	fn denote(&mut self, t: &Formula, d: usize, env: &mut Vec<(ConstName, usize)>) -> Result<Prop, String> {
+		Ok(match t {
+			Formula::Free(v) => match value(t, env) {
+				Some(e) => Prop::Const(e == d),
+				None => return Err(format!("Model: Free variable {} is not bound", v.to_string())),
+			},
+			Formula::Const(c) => {
+				let i = self.index(c);
+				Prop::Var(self.var(i, vec!(d)))
+			}
+			Formula::Relation(v) => self.application(v, Some(d), env)?,
+			o => return Err(format!("Model: Cannot use formula {} as a term", o.to_string())),
+		})
+	}
+
+	/// The condition for a relation to hold, or for a function to have the
+	/// value `result`, over every possible value of its arguments.
+	fn application(&mut self, v: &[Expr], result: Option<usize>, env: &mut Vec<(ConstName, usize)>)
+	-> Result<Prop, String> {
+		let i = match v.first() {
+			Some(Expr::Formula(Formula::Const(c))) => self.index(c),
+			_ => return Err("Model: Cannot interpret relation head".to_string()),
+		};
+		let args: Vec<&Formula> = v[1..].iter().map(|e| match e {
+			Expr::Formula(f) => Ok(f),
+			s => Err(format!("Model: Cannot interpret sequence {}", s.to_string())),
+		}).collect::<Result<_, _>>()?;
+
+		let mut cases = vec!();
+		for tuple in self.tuples(args.len()) {
+			let mut conds = vec!();
+			for (a, d) in args.iter().zip(tuple.iter()) {
+				conds.push(match value(a, env) {
+					Some(e) => Prop::Const(e == *d),
+					None => self.denote(a, *d, env)?,
+				});
+			}
+			let mut key = tuple;
+			key.extend(result);
+			conds.push(Prop::Var(self.var(i, key)));
+			cases.push(Prop::and(conds));
+		}
+		Ok(Prop::or(cases))
+	}
+
+	/// Adds clauses requiring `p` to hold.
+	fn assert(&mut self, p: &Prop) {
+		match p {
+			Prop::Const(true) => (),
+			Prop::And(v) => for q in v.iter() {
+				self.assert(q);
+			}
+			p => {
+				let l = self.encode(p);
+				self.clauses.push(vec!(l));
+			}
+		}
+	}
+
+	/// Returns a literal equivalent to `p` under the clauses added.
+	fn encode(&mut self, p: &Prop) -> i64 {
+		let fresh = |g: &mut Grounding| {
+			let n = g.vars.len();
+			g.var(usize::MAX, vec!(n))
+		};
+		match p {
+			Prop::Var(v) => *v,
+			Prop::Const(b) => {
+				let v = fresh(self);
+				self.clauses.push(vec!(if *b { v } else { -v }));
+				v
+			}
+			Prop::And(ps) | Prop::Or(ps) => {
+				let and = matches!(p, Prop::And(_));
+				let ls: Vec<i64> = ps.iter().map(|q| self.encode(q)).collect();
+				let v = fresh(self);
+				let sign = if and { 1 } else { -1 };
+				let mut all = vec!(sign * v);
+				for l in ls.iter() {
+					self.clauses.push(vec!(-sign * v, sign * l));
+					all.push(-sign * l);
+				}
+				self.clauses.push(all);
+				v
+			}
+			Prop::Iff(a, b) => {
+				let (a, b) = (self.encode(a), self.encode(b));
+				let v = fresh(self);
+				self.clauses.extend(vec!(
+					vec!(-v, -a, b), vec!(-v, a, -b),
+					vec!(v, a, b), vec!(v, -a, -b)));
+				v
+			}
+		}
+	}
+
+	fn model(&self, values: &[bool]) -> Model {
+		let holds = |g: &Grounding, i: usize, key: Vec<usize>| {
+			g.vars.get(&(i, key)).is_some_and(|v| values[*v as usize - 1])
+		};
+
+		let mut m = Model { size: self.size, constants: vec!(), propositions: vec!(), functions: vec!(), relations: vec!() };
+		for (i, s) in self.symbols.iter().enumerate() {
+			let name = s.name.clone();
+			match (s.arity, s.predicate) {
+				(0, true) => m.propositions.push((name, holds(self, i, vec!()))),
+				(0, false) => {
+					let v = (0..self.size).find(|d| holds(self, i, vec!(*d))).unwrap_or(0);
+					m.constants.push((name, v));
+				}
+				(a, true) => {
+					let tuples = self.tuples(a).into_iter().filter(|t| holds(self, i, t.clone())).collect();
+					m.relations.push((name, a, tuples));
+				}
+				(a, false) => {
+					let table = self.tuples(a).into_iter().map(|t| {
+						let v = (0..self.size).find(|d| {
+							let mut key = t.clone();
+							key.push(*d);
+							holds(self, i, key)
+						}).unwrap_or(0);
+						(t, v)
+					}).collect();
+					m.functions.push((name, a, table));
+				}
+			}
+		}
+		m
+	}
+}
+
+/// The element a bound variable stands for.
+fn value(t: &Formula, env: &[(ConstName, usize)]) -> Option<usize> {
+	match t {
+		Formula::Free(v) => env.iter().rev().find(|(n, _)| n == v).map(|(_, d)| *d),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	/// Holds its facts without enumerating them.
+	struct Facts(Vec<Formula>, Vec<ConstName>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.contains(form)
+		}
+
+		fn has_const(&self, c: &ConstName) -> bool {
+			self.1.contains(c)
+		}
+
+		fn constants(&self) -> Vec<ConstName> {
+			self.1.clone()
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	#[test]
+	fn counter_model_uses_the_given_axioms_and_declared_constants() {
+		let p = Formula::Const(name("p"));
+		let kbase = Facts(vec!(p.clone()), vec!(name("c")));
+
+		assert!(counter_model(&kbase, std::slice::from_ref(&p), &p, 2).unwrap().is_none());
+		assert!(counter_model(&kbase, &[Formula::Not(p.clone().ptr())], &p, 2).is_err());
+
+		let q = Formula::Const(name("q"));
+		let model = counter_model(&kbase, std::slice::from_ref(&p), &q, 2).unwrap().unwrap();
+		assert_eq!(model.proposition(&name("p")), Some(true));
+		assert_eq!(model.proposition(&name("q")), Some(false));
+		assert!(model.constant(&name("c")).is_some());
+	}
+
+	#[test]
+	fn display_prints_names_as_formulas_do() {
+		let model = Model {
+			size: 1,
+			constants: vec!((ConstName::Int(1), 0)),
+			propositions: vec!((name("p"), true)),
+			functions: vec!(),
+			relations: vec!((name("R"), 1, vec!(vec!(0)))),
+		};
+		assert_eq!(model.to_string(), "Domain: {e0}\n\tv₁ = e0\n\tp = true\n\tR(e0)\n");
+	}
+}
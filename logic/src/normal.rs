@@ -0,0 +1,880 @@
+//! Normal forms for formulas: negation normal form, prenex normal form, and
+//! conjunctive and disjunctive normal form.
+//!
+//! Every transformation has a proof producing mode which also returns a
+//! `Deduction` of `IFF(original, normalized)`. The proof is built from small
+//! propositional lemmas, proven by `prover::tautology`, and congruence steps
+//! through the connectives and quantifiers. It introduces the bound variables
+//! of the formula as eigenvariables, so in that mode a bound variable may
+//! not be a declared constant, occur as a constant in the formula, or be
+//! bound again within its own scope.
+//!
+//! Prenex, conjunctive and disjunctive normal forms start from the negation
+//! normal form. For the latter two, only the matrix below the quantifier
+//! prefix is distributed. `Eq`, `Relation`, `Subst` and `ForAllSeq` formulas
+//! are left untouched as atoms.
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::{Deduction, Work};
+use knowledge_base::KnowledgeBase;
+use prover::tautology;
+use Ptr;
+
+/// Returns the negation normal form of the formula.
+pub fn nnf(f: &Formula) -> Formula {
+	normalize(f, |n, f| n.nnf(f))
+}
+
+/// Returns a prenex normal form of the formula. Bound variables are renamed
+/// apart where pulling their quantifier out would capture another variable.
+pub fn prenex(f: &Formula) -> Formula {
+	normalize(f, |n, f| n.prenex(f))
+}
+
+/// Returns a prenex conjunctive normal form of the formula.
+pub fn cnf(f: &Formula) -> Formula {
+	normalize(f, |n, f| n.cnf(f))
+}
+
+/// Returns a prenex disjunctive normal form of the formula.
+pub fn dnf(f: &Formula) -> Formula {
+	normalize(f, |n, f| n.dnf(f))
+}
+
+/// Renames bound variables apart, so that no two quantifiers bind the same
+/// name and no bound name is also free or a constant of the formula.
+pub fn rename(f: &Formula) -> Formula {
+	normalize(f, |n, f| n.rename(f))
+}
+
+/// Like `nnf`, also returning a deduction of `IFF(f, nnf(f))`, checked
+/// against `kbase`.
+pub fn nnf_proof<K: KnowledgeBase>(kbase: &K, f: &Formula)
+-> Result<(Formula, Deduction), String> {
+	normalize_proof(kbase, f, |n, f| n.nnf(f))
+}
+
+/// Like `prenex`, also returning a deduction of `IFF(f, prenex(f))`, checked
+/// against `kbase`.
+pub fn prenex_proof<K: KnowledgeBase>(kbase: &K, f: &Formula)
+-> Result<(Formula, Deduction), String> {
+	normalize_proof(kbase, f, |n, f| n.prenex(f))
+}
+
+/// Like `cnf`, also returning a deduction of `IFF(f, cnf(f))`, checked
+/// against `kbase`.
+pub fn cnf_proof<K: KnowledgeBase>(kbase: &K, f: &Formula)
+-> Result<(Formula, Deduction), String> {
+	normalize_proof(kbase, f, |n, f| n.cnf(f))
+}
+
+/// Like `dnf`, also returning a deduction of `IFF(f, dnf(f))`, checked
+/// against `kbase`.
+pub fn dnf_proof<K: KnowledgeBase>(kbase: &K, f: &Formula)
+-> Result<(Formula, Deduction), String> {
+	normalize_proof(kbase, f, |n, f| n.dnf(f))
+}
+
+fn normalize<F>(f: &Formula, run: F) -> Formula
+	where F: FnOnce(&Normalizer, &Form) -> Step
+{
+	let n = Normalizer { prove: false, declared: &|_| false };
+	match run(&n, &f.clone().ptr()) {
+		Ok(e) => (*e.to).clone(),
+		// Only the proofs of a step can fail.
+		Err(e) => unreachable!("{}", e),
+	}
+}
+
+fn normalize_proof<K, F>(kbase: &K, f: &Formula, run: F) -> Result<(Formula, Deduction), String>
+	where K: KnowledgeBase, F: FnOnce(&Normalizer, &Form) -> Step
+{
+	check_binders(kbase, f, f, &mut vec!())?;
+
+	let n = Normalizer { prove: true, declared: &|c| kbase.has_const(c) };
+	let f = f.clone().ptr();
+	let e = run(&n, &f)?;
+	let d = e.proof.unwrap_or_else(|| Deduction::IFFIntro(
+		(f.clone(), empty()),
+		(f.clone(), empty())));
+
+	Ok(((*e.to).clone(), d))
+}
+
+/// Rejects formulas whose bound variables cannot serve as eigenvariables.
+fn check_binders<K: KnowledgeBase>(
+	kbase: &K,
+	whole: &Formula,
+	f: &Formula,
+	scope: &mut Vec<ConstName>)
+-> Result<(), String> {
+	match f {
+		Formula::IFF(l, r) | Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) => {
+			check_binders(kbase, whole, l, scope)?;
+			check_binders(kbase, whole, r, scope)
+		}
+		Formula::Not(l) => check_binders(kbase, whole, l, scope),
+		Formula::ForAll(v, e) | Formula::Exists(v, e) => {
+			if kbase.has_const(v) || unground(whole, v) != *whole {
+				Err(format!("Normal: Bound variable {} is also a constant", v.to_string()))
+			} else if scope.contains(v) {
+				Err(format!("Normal: Bound variable {} is bound within its own scope", v.to_string()))
+			} else {
+				scope.push(v.clone());
+				let r = check_binders(kbase, whole, e, scope);
+				scope.pop();
+				r
+			}
+		}
+		_ => Ok(()),
+	}
+}
+
+/// Collects the names of the free variables and constants of a formula.
+fn names(f: &Formula, bound: &mut Vec<ConstName>, out: &mut Vec<ConstName>) {
+	match f {
+		Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+		| Formula::Or(l, r) | Formula::Implies(l, r) => {
+			names(l, bound, out);
+			names(r, bound, out);
+		}
+		Formula::Not(l) | Formula::ForAllSeq(_, _, l) => names(l, bound, out),
+		Formula::Relation(l) => for x in l.iter() {
+			if let Expr::Formula(e) = x {
+				names(e, bound, out);
+			}
+		},
+		Formula::ForAll(v, e) | Formula::Exists(v, e) => {
+			bound.push(v.clone());
+			names(e, bound, out);
+			bound.pop();
+		}
+		Formula::Subst(body, v, sub) => {
+			bound.push(v.clone());
+			names(body, bound, out);
+			bound.pop();
+			names(sub, bound, out);
+		}
+		Formula::Free(v) => if !bound.contains(v) && !out.contains(v) {
+			out.push(v.clone());
+		},
+		Formula::Const(v) => if !out.contains(v) {
+			out.push(v.clone());
+		},
+		Formula::True | Formula::False => (),
+	}
+}
+
+/// Renames bound variables so that no two quantifiers bind the same name,
+/// and no bound name is also free or a constant.
+fn rename_apart(f: &Formula, used: &mut Vec<ConstName>, fresh: &mut dyn FnMut() -> ConstName)
+-> Formula {
+	match f {
+		Formula::IFF(l, r) => Formula::IFF(
+			rename_apart(l, used, fresh).ptr(),
+			rename_apart(r, used, fresh).ptr()),
+		Formula::And(l, r) => Formula::And(
+			rename_apart(l, used, fresh).ptr(),
+			rename_apart(r, used, fresh).ptr()),
+		Formula::Or(l, r) => Formula::Or(
+			rename_apart(l, used, fresh).ptr(),
+			rename_apart(r, used, fresh).ptr()),
+		Formula::Implies(l, r) => Formula::Implies(
+			rename_apart(l, used, fresh).ptr(),
+			rename_apart(r, used, fresh).ptr()),
+		Formula::Not(l) => Formula::Not(rename_apart(l, used, fresh).ptr()),
+		Formula::ForAll(v, e) | Formula::Exists(v, e) => {
+			let (v, e) = if used.contains(v) {
+				let n = fresh();
+				(n.clone(), e.substitute(v, &Formula::Free(n)).ptr())
+			} else {
+				(v.clone(), e.clone())
+			};
+			used.push(v.clone());
+			let e = rename_apart(&e, used, fresh).ptr();
+			match f {
+				Formula::ForAll(_, _) => Formula::ForAll(v, e),
+				_ => Formula::Exists(v, e),
+			}
+		}
+		o => o.clone(),
+	}
+}
+
+/// Replaces the constant `c` with the variable of the same name; the inverse
+/// of instantiating a bound variable with itself as an eigenvariable.
+fn unground(f: &Formula, c: &ConstName) -> Formula {
+	let rec = |x: &Form| unground(x, c).ptr();
+	match f {
+		Formula::Eq(l, r) => Formula::Eq(rec(l), rec(r)),
+		Formula::IFF(l, r) => Formula::IFF(rec(l), rec(r)),
+		Formula::And(l, r) => Formula::And(rec(l), rec(r)),
+		Formula::Or(l, r) => Formula::Or(rec(l), rec(r)),
+		Formula::Implies(l, r) => Formula::Implies(rec(l), rec(r)),
+		Formula::Not(l) => Formula::Not(rec(l)),
+		Formula::Relation(l) => Formula::Relation(l.iter().map(|x| match x {
+			Expr::Formula(e) => Expr::Formula(unground(e, c)),
+			o => o.clone(),
+		}).collect()),
+		Formula::Subst(body, v, sub) => Formula::Subst(rec(body), v.clone(), rec(sub)),
+		Formula::ForAllSeq(a, v, e) => Formula::ForAllSeq(*a, v.clone(), rec(e)),
+		Formula::ForAll(v, e) => Formula::ForAll(v.clone(), rec(e)),
+		Formula::Exists(v, e) => Formula::Exists(v.clone(), rec(e)),
+		Formula::Const(v) if v == c => Formula::Free(v.clone()),
+		o => o.clone(),
+	}
+}
+
+fn empty() -> Work { Ptr::new(Deduction::EmptyStep) }
+
+fn seq(v: Vec<Deduction>) -> Work { Ptr::new(Deduction::Sequence(v)) }
+
+fn not(f: &Form) -> Form { Formula::Not(f.clone()).ptr() }
+
+fn iff(l: &Form, r: &Form) -> Form { Formula::IFF(l.clone(), r.clone()).ptr() }
+
+fn konst(c: &ConstName) -> Form { Formula::Const(c.clone()).ptr() }
+
+type Connective = fn(Form, Form) -> Formula;
+
+/// A step of a normalization: `from` is equivalent to `to`, and `proof`
+/// deduces `IFF(from, to)` when proofs are requested and the two differ.
+struct Eqv {
+	from: Form,
+	to: Form,
+	proof: Option<Deduction>,
+}
+
+type Step = Result<Eqv, String>;
+
+struct Normalizer<'a> {
+	prove: bool,
+	declared: &'a dyn Fn(&ConstName) -> bool,
+}
+
+impl <'a> Normalizer<'a> {
+	fn same(&self, f: &Form) -> Step {
+		Ok(Eqv { from: f.clone(), to: f.clone(), proof: None })
+	}
+
+	/// A propositional equivalence, proven schematically in `atoms`.
+	fn lemma(&self, from: &Form, to: Form, atoms: &[Form]) -> Step {
+		let proof = if self.prove {
+			let d = tautology::prove_with_atoms(&[], iff(from, &to), atoms)
+				.ok_or_else(|| format!("Normal: {} is not a tautology", iff(from, &to).to_string()))?;
+			Some(d)
+		} else {
+			None
+		};
+		Ok(Eqv { from: from.clone(), to, proof })
+	}
+
+	/// An equivalence justified by a fixed deduction.
+	fn rule<F>(&self, from: &Form, to: Form, proof: F) -> Step
+		where F: FnOnce() -> Deduction
+	{
+		let proof = if self.prove { Some(proof()) } else { None };
+		Ok(Eqv { from: from.clone(), to, proof })
+	}
+
+	fn trans(&self, e1: Step, e2: Step) -> Step {
+		let (e1, e2) = (e1?, e2?);
+		if e1.from == e1.to {
+			return Ok(e2);
+		}
+		if e2.from == e2.to {
+			return Ok(e1);
+		}
+
+		let proof = match (e1.proof, e2.proof) {
+			(Some(p1), Some(p2)) => {
+				let (a, b, c) = (&e1.from, &e1.to, &e2.to);
+				Some(Deduction::Sequence(vec!(p1, p2, Deduction::IFFIntro(
+					(a.clone(), seq(vec!(
+						Deduction::IFFExtract(a.clone(), b.clone()),
+						Deduction::IFFExtract(b.clone(), c.clone())))),
+					(c.clone(), seq(vec!(
+						Deduction::IFFExtract(b.clone(), c.clone()),
+						Deduction::IFFExtract(a.clone(), b.clone()))))))))
+			}
+			_ => None,
+		};
+
+		Ok(Eqv { from: e1.from, to: e2.to, proof })
+	}
+
+	/// Rebuilds the connective `f` from the normalized immediate subformulas.
+	fn cong(&self, f: &Form, parts: Vec<Step>) -> Step {
+		let parts = parts.into_iter().collect::<Result<Vec<Eqv>, String>>()?;
+		if parts.iter().all(|e| e.from == e.to) {
+			return self.same(f);
+		}
+
+		let p: Vec<Form> = parts.iter().map(|e| e.to.clone()).collect();
+		let to = match &**f {
+			Formula::IFF(_, _) => Formula::IFF(p[0].clone(), p[1].clone()),
+			Formula::And(_, _) => Formula::And(p[0].clone(), p[1].clone()),
+			Formula::Or(_, _) => Formula::Or(p[0].clone(), p[1].clone()),
+			Formula::Implies(_, _) => Formula::Implies(p[0].clone(), p[1].clone()),
+			Formula::Not(_) => Formula::Not(p[0].clone()),
+			_ => unreachable!(),
+		}.ptr();
+
+		let proof = if self.prove {
+			let mut atoms = vec!();
+			let mut hyps = vec!();
+			let mut steps = vec!();
+			for e in parts {
+				atoms.push(e.from.clone());
+				atoms.push(e.to.clone());
+				if let Some(d) = e.proof {
+					hyps.push(iff(&e.from, &e.to));
+					steps.push(d);
+				}
+			}
+			steps.push(tautology::prove_with_atoms(&hyps, iff(f, &to), &atoms)
+				.ok_or_else(|| format!("Normal: Congruence for {} is not a tautology", f.to_string()))?);
+			Some(Deduction::Sequence(steps))
+		} else {
+			None
+		};
+
+		Ok(Eqv { from: f.clone(), to, proof })
+	}
+
+	/// Normalizes the body of the quantified formula `f` with `body`. When
+	/// proving, the body is normalized with the bound variable instantiated
+	/// by itself, which is how the quantifier rules refer to it.
+	fn quant<F>(&self, f: &Form, body: F) -> Step
+		where F: Fn(&Self, &Form) -> Step
+	{
+		let (v, e) = match &**f {
+			Formula::ForAll(v, e) | Formula::Exists(v, e) => (v, e),
+			_ => unreachable!(),
+		};
+		let rebind = |b: Form| match &**f {
+			Formula::ForAll(_, _) => Formula::ForAll(v.clone(), b).ptr(),
+			_ => Formula::Exists(v.clone(), b).ptr(),
+		};
+
+		if !self.prove {
+			let inner = body(self, e)?;
+			return Ok(Eqv { from: f.clone(), to: rebind(inner.to), proof: None });
+		}
+
+		let inner = body(self, &e.substitute(v, &Formula::Const(v.clone())).ptr())?;
+		if inner.from == inner.to {
+			return self.same(f);
+		}
+
+		let b = unground(&inner.to, v).ptr();
+		let to = rebind(b.clone());
+		let proof = quantified(f, v, &b, inner.proof.clone(), inner.proof);
+		Ok(Eqv { from: f.clone(), to, proof: Some(proof) })
+	}
+
+	fn nnf(&self, f: &Form) -> Step {
+		match &**f {
+			Formula::And(a, b) | Formula::Or(a, b) =>
+				self.cong(f, vec!(self.nnf(a), self.nnf(b))),
+			Formula::Implies(a, b) => {
+				let g = Formula::Or(not(a), b.clone()).ptr();
+				self.trans(self.lemma(f, g.clone(), &[a.clone(), b.clone()]), self.nnf(&g))
+			}
+			Formula::IFF(a, b) => {
+				let g = Formula::And(
+					Formula::Or(not(a), b.clone()).ptr(),
+					Formula::Or(a.clone(), not(b)).ptr()).ptr();
+				self.trans(self.lemma(f, g.clone(), &[a.clone(), b.clone()]), self.nnf(&g))
+			}
+			Formula::ForAll(_, _) | Formula::Exists(_, _) => self.quant(f, |n, b| n.nnf(b)),
+			Formula::Not(g) => self.nnf_negated(f, g),
+			_ => self.same(f),
+		}
+	}
+
+	fn nnf_negated(&self, f: &Form, g: &Form) -> Step {
+		let (to, atoms) = match &**g {
+			Formula::Not(a) => (a.clone(), vec!(a.clone())),
+			Formula::And(a, b) =>
+				(Formula::Or(not(a), not(b)).ptr(), vec!(a.clone(), b.clone())),
+			Formula::Or(a, b) =>
+				(Formula::And(not(a), not(b)).ptr(), vec!(a.clone(), b.clone())),
+			Formula::Implies(a, b) =>
+				(Formula::And(a.clone(), not(b)).ptr(), vec!(a.clone(), b.clone())),
+			Formula::IFF(a, b) => (Formula::Or(
+				Formula::And(a.clone(), not(b)).ptr(),
+				Formula::And(not(a), b.clone()).ptr()).ptr(), vec!(a.clone(), b.clone())),
+			Formula::True => (Formula::False.ptr(), vec!()),
+			Formula::False => (Formula::True.ptr(), vec!()),
+			Formula::ForAll(v, a) => {
+				let to = Formula::Exists(v.clone(), not(a)).ptr();
+				let step = self.rule(f, to.clone(), || not_forall(v, a));
+				return self.trans(step, self.nnf(&to));
+			}
+			Formula::Exists(v, a) => {
+				let to = Formula::ForAll(v.clone(), not(a)).ptr();
+				let step = self.rule(f, to.clone(), || not_exists(v, a));
+				return self.trans(step, self.nnf(&to));
+			}
+			_ => return self.same(f),
+		};
+
+		self.trans(self.lemma(f, to.clone(), &atoms), self.nnf(&to))
+	}
+
+	fn prenex(&self, f: &Form) -> Step {
+		let e1 = self.nnf(f)?;
+		let e2 = self.rename(&e1.to)?;
+		let e3 = self.pnf(&e2.to);
+		self.trans(self.trans(Ok(e1), Ok(e2)), e3)
+	}
+
+	fn rename(&self, f: &Form) -> Step {
+		let mut used = vec!();
+		names(f, &mut vec!(), &mut used);
+		let mut next = f.max_int();
+		let declared = self.declared;
+		let mut fresh = || loop {
+			next += 1;
+			let c = ConstName::Int(next);
+			if !declared(&c) {
+				return c;
+			}
+		};
+
+		let to = rename_apart(f, &mut used, &mut fresh).ptr();
+		if self.prove {
+			self.alpha(f, &to)
+		} else {
+			Ok(Eqv { from: f.clone(), to, proof: None })
+		}
+	}
+
+	/// Proves two formulas equal up to the names of bound variables
+	/// equivalent.
+	fn alpha(&self, s: &Form, t: &Form) -> Step {
+		if s == t {
+			return self.same(s);
+		}
+
+		match (&**s, &**t) {
+			(Formula::IFF(a, b), Formula::IFF(c, d))
+			| (Formula::And(a, b), Formula::And(c, d))
+			| (Formula::Or(a, b), Formula::Or(c, d))
+			| (Formula::Implies(a, b), Formula::Implies(c, d)) =>
+				self.cong(s, vec!(self.alpha(a, c), self.alpha(b, d))),
+			(Formula::Not(a), Formula::Not(c)) => self.cong(s, vec!(self.alpha(a, c))),
+			(Formula::ForAll(x, a), Formula::ForAll(y, b))
+			| (Formula::Exists(x, a), Formula::Exists(y, b)) => {
+				let at = |c: &ConstName| (
+					a.substitute(x, &Formula::Const(c.clone())).ptr(),
+					b.substitute(y, &Formula::Const(c.clone())).ptr());
+				let (ay, by) = at(y);
+				let (ax, bx) = at(x);
+				let w1 = self.alpha(&ay, &by)?.proof;
+				let w2 = self.alpha(&ax, &bx)?.proof;
+				let proof = quantified(s, y, b, w1, w2);
+				Ok(Eqv { from: s.clone(), to: t.clone(), proof: Some(proof) })
+			}
+			_ => Err(format!("Normal: {} and {} are not alpha equivalent",
+				s.to_string(), t.to_string())),
+		}
+	}
+
+	/// Pulls the quantifiers of a renamed apart negation normal form out.
+	fn pnf(&self, f: &Form) -> Step {
+		match &**f {
+			Formula::ForAll(_, _) | Formula::Exists(_, _) => self.quant(f, |n, b| n.pnf(b)),
+			Formula::And(a, b) | Formula::Or(a, b) => {
+				let e = self.cong(f, vec!(self.pnf(a), self.pnf(b)))?;
+				let p = self.pull(&e.to);
+				self.trans(Ok(e), p)
+			}
+			_ => self.same(f),
+		}
+	}
+
+	fn pull(&self, f: &Form) -> Step {
+		let (l, r) = match &**f {
+			Formula::And(l, r) | Formula::Or(l, r) => (l, r),
+			_ => return self.same(f),
+		};
+		let op = |a: Form, b: Form| match &**f {
+			Formula::And(_, _) => Formula::And(a, b).ptr(),
+			_ => Formula::Or(a, b).ptr(),
+		};
+
+		let (q, left, other) = match (&**l, &**r) {
+			(Formula::ForAll(_, _), _) | (Formula::Exists(_, _), _) => (l, true, r),
+			(_, Formula::ForAll(_, _)) | (_, Formula::Exists(_, _)) => (r, false, l),
+			_ => return self.same(f),
+		};
+		let (v, a) = match &**q {
+			Formula::ForAll(v, a) | Formula::Exists(v, a) => (v, a),
+			_ => unreachable!(),
+		};
+		let body = if left { op(a.clone(), other.clone()) } else { op(other.clone(), a.clone()) };
+		let to = match &**q {
+			Formula::ForAll(_, _) => Formula::ForAll(v.clone(), body),
+			_ => Formula::Exists(v.clone(), body),
+		}.ptr();
+
+		let step = self.rule(f, to.clone(), || pull_quantifier(f, q, left, other));
+		self.trans(step, self.quant(&to, |n, b| n.pull(b)))
+	}
+
+	fn cnf(&self, f: &Form) -> Step {
+		let e = self.prenex(f)?;
+		let m = self.matrix(&e.to, true);
+		self.trans(Ok(e), m)
+	}
+
+	fn dnf(&self, f: &Form) -> Step {
+		let e = self.prenex(f)?;
+		let m = self.matrix(&e.to, false);
+		self.trans(Ok(e), m)
+	}
+
+	/// Distributes the matrix below a quantifier prefix, into a conjunction
+	/// of disjunctions if `conj`, otherwise a disjunction of conjunctions.
+	fn matrix(&self, f: &Form, conj: bool) -> Step {
+		match &**f {
+			Formula::ForAll(_, _) | Formula::Exists(_, _) =>
+				self.quant(f, |n, b| n.matrix(b, conj)),
+			_ => self.distribute(f, conj),
+		}
+	}
+
+	fn distribute(&self, f: &Form, conj: bool) -> Step {
+		match (&**f, conj) {
+			(Formula::And(a, b), true) | (Formula::Or(a, b), false) =>
+				self.cong(f, vec!(self.distribute(a, conj), self.distribute(b, conj))),
+			(Formula::Or(a, b), true) | (Formula::And(a, b), false) => {
+				let e = self.cong(f, vec!(self.distribute(a, conj), self.distribute(b, conj)))?;
+				let d = self.distribute_over(&e.to, conj);
+				self.trans(Ok(e), d)
+			}
+			_ => self.same(f),
+		}
+	}
+
+	/// Distributes the inner connective of `f` over its outer connective,
+	/// where both sides of `f` are already distributed.
+	fn distribute_over(&self, f: &Form, conj: bool) -> Step {
+		let (outer, inner): (Connective, Connective) = if conj {
+			(Formula::And, Formula::Or)
+		} else {
+			(Formula::Or, Formula::And)
+		};
+		let split = |g: &Form| match (&**g, conj) {
+			(Formula::And(a, b), true) | (Formula::Or(a, b), false) => Some((a.clone(), b.clone())),
+			_ => None,
+		};
+		let (l, r) = match (&**f, conj) {
+			(Formula::Or(l, r), true) | (Formula::And(l, r), false) => (l, r),
+			_ => return self.same(f),
+		};
+
+		let (g, atoms) = if let Some((a, b)) = split(l) {
+			(outer(inner(a.clone(), r.clone()).ptr(), inner(b.clone(), r.clone()).ptr()).ptr(),
+				vec!(a, b, r.clone()))
+		} else if let Some((a, b)) = split(r) {
+			(outer(inner(l.clone(), a.clone()).ptr(), inner(l.clone(), b.clone()).ptr()).ptr(),
+				vec!(l.clone(), a, b))
+		} else {
+			return self.same(f);
+		};
+
+		let step = self.lemma(f, g.clone(), &atoms);
+		let parts = match &*g {
+			Formula::And(a, b) | Formula::Or(a, b) =>
+				vec!(self.distribute_over(a, conj), self.distribute_over(b, conj)),
+			_ => unreachable!(),
+		};
+		self.trans(step, self.cong(&g, parts))
+	}
+}
+
+/// Deduces `IFF(f, Q(y, b))` for `f = Q(x, a)`. `w1` must deduce the
+/// equivalence of `a` and `b` with both variables instantiated by `y`, and
+/// `w2` with both instantiated by `x`; either is `None` when the instances
+/// are identical.
+fn quantified(f: &Form, y: &ConstName, b: &Form, w1: Option<Deduction>, w2: Option<Deduction>)
+-> Deduction {
+	let (x, a) = match &**f {
+		Formula::ForAll(x, a) | Formula::Exists(x, a) => (x, a),
+		_ => unreachable!(),
+	};
+	let at = |c: &ConstName| (
+		a.substitute(x, &Formula::Const(c.clone())).ptr(),
+		b.substitute(y, &Formula::Const(c.clone())).ptr());
+	let via = |w: Option<Deduction>, l: Form, r: Form| match w {
+		Some(w) => vec!(w, Deduction::IFFExtract(l, r)),
+		None => vec!(),
+	};
+	let (ay, by) = at(y);
+	let (ax, bx) = at(x);
+
+	match &**f {
+		Formula::ForAll(_, _) => {
+			let g = Formula::ForAll(y.clone(), b.clone()).ptr();
+			let mut s1 = vec!(Deduction::ForAllExtract((x.clone(), a.clone()), konst(y)));
+			s1.append(&mut via(w1, ay, by));
+			let mut s2 = vec!(Deduction::ForAllExtract((y.clone(), b.clone()), konst(x)));
+			s2.append(&mut via(w2, ax, bx));
+
+			Deduction::IFFIntro(
+				(f.clone(), Ptr::new(Deduction::ForAllIntro((y.clone(), b.clone()), seq(s1)))),
+				(g, Ptr::new(Deduction::ForAllIntro((x.clone(), a.clone()), seq(s2)))))
+		}
+		_ => {
+			let g = Formula::Exists(y.clone(), b.clone()).ptr();
+			let mut s1 = vec!(Deduction::ExistsExtract((x.clone(), a.clone()), y.clone()));
+			s1.append(&mut via(w1, ay, by));
+			s1.push(Deduction::ExistsIntro((y.clone(), b.clone()), konst(y)));
+			let mut s2 = vec!(Deduction::ExistsExtract((y.clone(), b.clone()), x.clone()));
+			s2.append(&mut via(w2, ax, bx));
+			s2.push(Deduction::ExistsIntro((x.clone(), a.clone()), konst(x)));
+
+			Deduction::IFFIntro((f.clone(), seq(s1)), (g, seq(s2)))
+		}
+	}
+}
+
+/// Deduces `IFF(Not(ForAll(x, a)), Exists(x, Not(a)))`.
+fn not_forall(x: &ConstName, a: &Form) -> Deduction {
+	let all = Formula::ForAll(x.clone(), a.clone()).ptr();
+	let ex = Formula::Exists(x.clone(), not(a)).ptr();
+	let ax = a.substitute(x, &Formula::Const(x.clone())).ptr();
+
+	Deduction::IFFIntro(
+		(not(&all), Ptr::new(Deduction::Contradiction(ex.clone(), seq(vec!(
+			Deduction::ForAllIntro((x.clone(), a.clone()),
+				Ptr::new(Deduction::Contradiction(ax.clone(), seq(vec!(
+					Deduction::ExistsIntro((x.clone(), not(a)), konst(x)),
+					Deduction::NotExtract(ex.clone())))))),
+			Deduction::NotExtract(all.clone())))))),
+		(ex, Ptr::new(Deduction::NotIntro(all, seq(vec!(
+			Deduction::ExistsExtract((x.clone(), not(a)), x.clone()),
+			Deduction::ForAllExtract((x.clone(), a.clone()), konst(x)),
+			Deduction::NotExtract(ax)))))))
+}
+
+/// Deduces `IFF(Not(Exists(x, a)), ForAll(x, Not(a)))`.
+fn not_exists(x: &ConstName, a: &Form) -> Deduction {
+	let ex = Formula::Exists(x.clone(), a.clone()).ptr();
+	let all = Formula::ForAll(x.clone(), not(a)).ptr();
+	let ax = a.substitute(x, &Formula::Const(x.clone())).ptr();
+
+	Deduction::IFFIntro(
+		(not(&ex), Ptr::new(Deduction::ForAllIntro((x.clone(), not(a)),
+			Ptr::new(Deduction::NotIntro(ax.clone(), seq(vec!(
+				Deduction::ExistsIntro((x.clone(), a.clone()), konst(x)),
+				Deduction::NotExtract(ex.clone())))))))),
+		(all, Ptr::new(Deduction::NotIntro(ex, seq(vec!(
+			Deduction::ExistsExtract((x.clone(), a.clone()), x.clone()),
+			Deduction::ForAllExtract((x.clone(), not(a)), konst(x)),
+			Deduction::NotExtract(ax)))))))
+}
+
+/// Deduces `IFF(f, Q(x, op(a, other)))` for `f = op(Q(x, a), other)`, or
+/// with the operands of `op` swapped if not `left`. The variable `x` must
+/// not be free in `other`.
+fn pull_quantifier(f: &Form, q: &Form, left: bool, other: &Form) -> Deduction {
+	let (x, a) = match &**q {
+		Formula::ForAll(x, a) | Formula::Exists(x, a) => (x, a),
+		_ => unreachable!(),
+	};
+	let ax = a.substitute(x, &Formula::Const(x.clone())).ptr();
+	let pair = |p: Form| if left { (p, other.clone()) } else { (other.clone(), p) };
+	let conj = matches!(**f, Formula::And(_, _));
+	let op = |p: Form| {
+		let (l, r) = pair(p);
+		if conj { Formula::And(l, r).ptr() } else { Formula::Or(l, r).ptr() }
+	};
+	// OrExtract takes its cases in the order of the disjunction.
+	let cases = |p: (Form, Work), o: (Form, Work), goal: Form| if left {
+		Deduction::OrExtract(p, o, goal)
+	} else {
+		Deduction::OrExtract(o, p, goal)
+	};
+	let body = op(a.clone());
+	let g = match &**q {
+		Formula::ForAll(_, _) => Formula::ForAll(x.clone(), body.clone()),
+		_ => Formula::Exists(x.clone(), body.clone()),
+	}.ptr();
+	let and_extract = |p: Form| { let (l, r) = pair(p); Deduction::AndExtract(l, r) };
+	let and_intro = |p: Form| { let (l, r) = pair(p); Deduction::AndIntro(l, r) };
+	let or_intro = |p: Form| { let (l, r) = pair(p); Deduction::OrIntro(l, r) };
+
+	let (w1, w2) = match (&**q, conj) {
+		(Formula::ForAll(_, _), true) => (
+			Ptr::new(Deduction::ForAllIntro((x.clone(), body.clone()), seq(vec!(
+				and_extract(q.clone()),
+				Deduction::ForAllExtract((x.clone(), a.clone()), konst(x)),
+				and_intro(ax.clone()))))),
+			seq(vec!(
+				Deduction::ForAllIntro((x.clone(), a.clone()), seq(vec!(
+					Deduction::ForAllExtract((x.clone(), body.clone()), konst(x)),
+					and_extract(ax.clone())))),
+				Deduction::ForAllExtract((x.clone(), body.clone()), konst(x)),
+				and_extract(ax.clone()),
+				and_intro(q.clone())))),
+		(_, true) => (
+			seq(vec!(
+				and_extract(q.clone()),
+				Deduction::ExistsExtract((x.clone(), a.clone()), x.clone()),
+				and_intro(ax.clone()),
+				Deduction::ExistsIntro((x.clone(), body.clone()), konst(x)))),
+			seq(vec!(
+				Deduction::ExistsExtract((x.clone(), body.clone()), x.clone()),
+				and_extract(ax.clone()),
+				Deduction::ExistsIntro((x.clone(), a.clone()), konst(x)),
+				and_intro(q.clone())))),
+		(Formula::ForAll(_, _), false) => (
+			Ptr::new(Deduction::ForAllIntro((x.clone(), body.clone()), Ptr::new(cases(
+				(q.clone(), seq(vec!(
+					Deduction::ForAllExtract((x.clone(), a.clone()), konst(x)),
+					or_intro(ax.clone())))),
+				(other.clone(), Ptr::new(or_intro(ax.clone()))),
+				op(ax.clone()))))),
+			Ptr::new(Deduction::Contradiction(f.clone(), seq(vec!(
+				Deduction::NotIntro(other.clone(), seq(vec!(
+					or_intro(q.clone()),
+					Deduction::NotExtract(f.clone())))),
+				Deduction::ForAllIntro((x.clone(), a.clone()), seq(vec!(
+					Deduction::ForAllExtract((x.clone(), body.clone()), konst(x)),
+					cases(
+						(ax.clone(), empty()),
+						(other.clone(), seq(vec!(
+							Deduction::NotExtract(other.clone()),
+							Deduction::Contradiction(ax.clone(), empty())))),
+						ax.clone())))),
+				or_intro(q.clone()),
+				Deduction::NotExtract(f.clone())))))),
+		(_, false) => (
+			Ptr::new(cases(
+				(q.clone(), seq(vec!(
+					Deduction::ExistsExtract((x.clone(), a.clone()), x.clone()),
+					or_intro(ax.clone()),
+					Deduction::ExistsIntro((x.clone(), body.clone()), konst(x))))),
+				(other.clone(), seq(vec!(
+					or_intro(ax.clone()),
+					Deduction::ExistsIntro((x.clone(), body.clone()), konst(x))))),
+				g.clone())),
+			seq(vec!(
+				Deduction::ExistsExtract((x.clone(), body.clone()), x.clone()),
+				cases(
+					(ax.clone(), seq(vec!(
+						Deduction::ExistsIntro((x.clone(), a.clone()), konst(x)),
+						or_intro(q.clone())))),
+					(other.clone(), Ptr::new(or_intro(q.clone()))),
+					f.clone())))),
+	};
+
+	Deduction::IFFIntro((f.clone(), w1), (g, w2))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Declared(Vec<ConstName>);
+
+	impl KnowledgeBase for Declared {
+		fn contains(&self, _form: &Formula) -> bool {
+			false
+		}
+
+		fn has_const(&self, c: &ConstName) -> bool {
+			self.0.contains(c)
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn atom(s: &str) -> Form {
+		konst(&name(s))
+	}
+
+	fn app(p: &str, x: &ConstName) -> Form {
+		Formula::Relation(vec!(
+			Expr::Formula(Formula::Const(name(p))),
+			Expr::Formula(Formula::Free(x.clone())))).ptr()
+	}
+
+	fn and(l: &Form, r: &Form) -> Form { Formula::And(l.clone(), r.clone()).ptr() }
+	fn or(l: &Form, r: &Form) -> Form { Formula::Or(l.clone(), r.clone()).ptr() }
+
+	/// Checks that the proof mode agrees with `run` and that its deduction
+	/// is accepted by the kernel.
+	fn proven<F, G>(f: &Form, run: F, prove: G) -> Formula
+		where
+			F: Fn(&Formula) -> Formula,
+			G: Fn(&Declared, &Formula) -> Result<(Formula, Deduction), String>
+	{
+		let g = run(f);
+		let (h, d) = prove(&Declared(vec!()), f).unwrap();
+		assert!(g == h);
+		assert!(d.deduced(Declared(vec!()), &Formula::IFF(f.clone(), g.clone().ptr())));
+		g
+	}
+
+	#[test]
+	fn nnf_pushes_negations_to_atoms() {
+		let (p, q, r) = (atom("p"), atom("q"), atom("r"));
+		let f = not(&and(&p, &Formula::Implies(q.clone(), r.clone()).ptr()));
+		let g = proven(&f, nnf, nnf_proof);
+		assert!(g == *or(&not(&p), &and(&q, &not(&r))));
+
+		let x = name("x");
+		let f = not(&Formula::ForAll(x.clone(), app("P", &x)).ptr());
+		let g = proven(&f, nnf, nnf_proof);
+		assert!(g == Formula::Exists(x.clone(), not(&app("P", &x))));
+	}
+
+	#[test]
+	fn prenex_renames_before_pulling_quantifiers() {
+		let x = name("x");
+		let f = and(
+			&Formula::ForAll(x.clone(), app("P", &x)).ptr(),
+			&Formula::Exists(x.clone(), app("Q", &x)).ptr());
+		let g = proven(&f, prenex, prenex_proof);
+
+		let y = ConstName::Int(1);
+		assert!(g == Formula::ForAll(x.clone(), Formula::Exists(y.clone(),
+			and(&app("P", &x), &app("Q", &y))).ptr()));
+	}
+
+	#[test]
+	fn cnf_and_dnf_distribute() {
+		let (p, q, r) = (atom("p"), atom("q"), atom("r"));
+		let f = or(&and(&p, &q), &r);
+		let g = proven(&f, cnf, cnf_proof);
+		assert!(g == *and(&or(&p, &r), &or(&q, &r)));
+
+		let f = and(&or(&p, &q), &r);
+		let g = proven(&f, dnf, dnf_proof);
+		assert!(g == *or(&and(&p, &r), &and(&q, &r)));
+	}
+
+	#[test]
+	fn cnf_distributes_below_the_prefix() {
+		let x = name("x");
+		let (p, q) = (atom("p"), atom("q"));
+		let f = Formula::Exists(x.clone(), or(&and(&p, &q), &not(&app("P", &x)))).ptr();
+		let g = proven(&f, cnf, cnf_proof);
+		assert!(g == Formula::Exists(x.clone(),
+			and(&or(&p, &not(&app("P", &x))), &or(&q, &not(&app("P", &x))))));
+	}
+
+	#[test]
+	fn declared_bound_variable_is_rejected() {
+		let x = name("x");
+		let f = Formula::ForAll(x.clone(), not(&not(&app("P", &x))));
+		assert!(nnf_proof(&Declared(vec!(x)), &f).is_err());
+	}
+}
@@ -0,0 +1,466 @@
+//! Infix printing of formulas as Unicode, ASCII or LaTeX.
+//!
+//! Connectives bind from tightest to loosest as `¬`, `=`, `∧`, `∨`, `→`,
+//! `↔`, and quantifiers extend as far right as possible. `∧` and `∨` group to
+//! the left, as the `and!` and `or!` macros build them, and `→` to the
+//! right, so only the parentheses needed to read a formula back are
+//! printed. A constant applied through `Relation([Const(f), t1, ..., tn])`
+//! is printed `f(t1, ..., tn)`, `Subst(φ, x, t)` is printed `φ[x := t]`
+//! and a sequence variable `x` is printed `x₁…xₙ`, its tail `x₁…xₙ₋₁`
+//! and its head `x₁`. `ConstName::Int(i)` is printed as `v` with the
+//! subscript `i`; a string name which would print the same, or which starts
+//! with `"`, is printed quoted.
+
+use formula::{Formula, ConstName, Expr, Seq};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Style {
+	Unicode,
+	Ascii,
+	Latex,
+}
+
+#[derive(Clone, Copy)]
+pub struct Options {
+	pub style: Style,
+	/// The width to break lines at, or `None` to print on one line.
+	pub width: Option<usize>,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options { style: Style::Unicode, width: None }
+	}
+}
+
+impl Options {
+	pub fn new(style: Style) -> Options {
+		Options { style, width: None }
+	}
+
+	pub fn width(self, width: usize) -> Options {
+		Options { width: Some(width), ..self }
+	}
+}
+
+pub fn formula(f: &Formula, opts: Options) -> String {
+	let p = Printer { style: opts.style };
+	render(&p.formula(f, 0, true), opts.width.unwrap_or(usize::MAX))
+}
+
+pub fn expr(e: &Expr, opts: Options) -> String {
+	let p = Printer { style: opts.style };
+	render(&p.expr(e), opts.width.unwrap_or(usize::MAX))
+}
+
+pub fn name(c: &ConstName, style: Style) -> String {
+	Printer { style }.name(c)
+}
+
+/// The formula in Unicode on one line.
+pub fn unicode(f: &Formula) -> String {
+	formula(f, Options::new(Style::Unicode))
+}
+
+/// The formula in ASCII on one line.
+pub fn ascii(f: &Formula) -> String {
+	formula(f, Options::new(Style::Ascii))
+}
+
+/// The formula as LaTeX math on one line.
+pub fn latex(f: &Formula) -> String {
+	formula(f, Options::new(Style::Latex))
+}
+
+/// A layout of text with optional line breaks, in the manner of Wadler's
+/// "A prettier printer". The breaks of a group are all taken or all left
+/// out.
+enum Doc {
+	Text(String),
+	/// A space, or a line break when its group does not fit.
+	Line,
+	Concat(Vec<Doc>),
+	Nest(usize, Box<Doc>),
+	Group(Box<Doc>),
+}
+
+fn text<S: Into<String>>(s: S) -> Doc {
+	Doc::Text(s.into())
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+	let mut out = String::new();
+	let mut col = 0;
+	let mut stack: Vec<(usize, bool, &Doc)> = vec!((0, false, doc));
+	while let Some((indent, flat, d)) = stack.pop() {
+		match d {
+			Doc::Text(s) => {
+				out.push_str(s);
+				col += s.chars().count();
+			}
+			Doc::Line => if flat {
+				out.push(' ');
+				col += 1;
+			} else {
+				out.push('\n');
+				out.push_str(&" ".repeat(indent));
+				col = indent;
+			}
+			Doc::Concat(v) => stack.extend(v.iter().rev().map(|x| (indent, flat, x))),
+			Doc::Nest(i, x) => stack.push((indent + i, flat, x)),
+			Doc::Group(x) => {
+				let flat = flat || fits(width.saturating_sub(col), x, &stack);
+				stack.push((indent, flat, x));
+			}
+		}
+	}
+	out
+}
+
+/// Whether `next` printed flat, and what follows it up to the next line
+/// break, takes at most `width` characters.
+fn fits(width: usize, next: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+	let mut left = width;
+	let mut work: Vec<(bool, &Doc)> = vec!((true, next));
+	let mut rest = rest.iter().rev();
+	loop {
+		let (flat, d) = match work.pop() {
+			Some(x) => x,
+			None => match rest.next() {
+				Some((_, flat, d)) => (*flat, *d),
+				None => return true,
+			}
+		};
+		match d {
+			Doc::Text(s) => match left.checked_sub(s.chars().count()) {
+				Some(l) => left = l,
+				None => return false,
+			}
+			Doc::Line => if !flat {
+				return true;
+			} else if left == 0 {
+				return false;
+			} else {
+				left -= 1;
+			}
+			Doc::Concat(v) => work.extend(v.iter().rev().map(|x| (flat, x))),
+			Doc::Nest(_, x) | Doc::Group(x) => work.push((flat, x)),
+		}
+	}
+}
+
+/// Precedences of the connectives. Quantifiers are below every connective
+/// and atoms above. `=` is above the connectives but below atoms, so a
+/// negated or substituted equation is parenthesized.
+const IFF: u8 = 1;
+const IMPLIES: u8 = 2;
+const OR: u8 = 3;
+const AND: u8 = 4;
+const EQ: u8 = 5;
+const ATOM: u8 = 6;
+
+fn precedence(f: &Formula) -> u8 {
+	match f {
+		Formula::And(_, _) => AND,
+		Formula::Or(_, _) => OR,
+		Formula::Implies(_, _) => IMPLIES,
+		Formula::IFF(_, _) => IFF,
+		Formula::Eq(_, _) => EQ,
+		Formula::ForAll(_, _) | Formula::Exists(_, _) | Formula::ForAllSeq(_, _, _) => 0,
+		_ => ATOM,
+	}
+}
+
+fn operands_of(f: &Formula) -> Option<(&Formula, &Formula)> {
+	match f {
+		Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) | Formula::IFF(l, r) => Some((l, r)),
+		_ => None,
+	}
+}
+
+struct Printer {
+	style: Style,
+}
+
+impl Printer {
+	fn symbol(&self, unicode: &'static str, ascii: &'static str, latex: &'static str) -> &'static str {
+		match self.style {
+			Style::Unicode => unicode,
+			Style::Ascii => ascii,
+			Style::Latex => latex,
+		}
+	}
+
+	/// Prints `f` where it must bind at least as tightly as `prec`. `open` is
+	/// whether nothing follows it up to the end of the enclosing parentheses,
+	/// so a quantifier may extend to the right.
+	fn formula(&self, f: &Formula, prec: u8, open: bool) -> Doc {
+		let own = precedence(f);
+		let parens = if own == 0 { !open } else { own < prec };
+		// Parentheses end at the end of `f`, so its right side is open.
+		let open = open || parens;
+
+		let doc = match f {
+			Formula::True => text(self.symbol("⊤", "true", "\\top")),
+			Formula::False => text(self.symbol("⊥", "false", "\\bot")),
+			Formula::Not(l) => Doc::Concat(vec!(
+				text(self.symbol("¬", "~", "\\neg ")),
+				self.formula(l, ATOM, open),
+			)),
+			Formula::And(_, _) => self.binary(f, self.symbol("∧", "&", "\\land"), (AND, AND + 1), open),
+			Formula::Or(_, _) => self.binary(f, self.symbol("∨", "|", "\\lor"), (OR, OR + 1), open),
+			Formula::Implies(_, _) => self.binary(f, self.symbol("→", "->", "\\to"), (IMPLIES + 1, IMPLIES), open),
+			Formula::IFF(_, _) => self.binary(f, self.symbol("↔", "<->", "\\leftrightarrow"), (IFF + 1, IFF + 1), open),
+			Formula::Eq(l, r) => Doc::Concat(vec!(self.term(l), text(" = "), self.term(r))),
+			Formula::ForAll(_, _) | Formula::Exists(_, _) | Formula::ForAllSeq(_, _, _) => self.quantifier(f),
+			Formula::Subst(body, v, t) => Doc::Concat(vec!(
+				self.formula(body, ATOM, false),
+				text(format!("[{} := ", self.name(v))),
+				self.term(t),
+				text("]"),
+			)),
+			Formula::Relation(v) => self.application(v),
+			Formula::Free(c) | Formula::Const(c) => text(self.name(c)),
+		};
+
+		if parens {
+			Doc::Concat(vec!(text("("), Doc::Nest(1, Box::new(doc)), text(")")))
+		} else {
+			doc
+		}
+	}
+
+	/// Prints a chain of the same connective as one group, so its lines
+	/// break together. `sides` are the precedences the left and right
+	/// operands must have; the chain follows the side which may hold the
+	/// same connective without parentheses.
+	fn binary(&self, f: &Formula, op: &str, sides: (u8, u8), open: bool) -> Doc {
+		let same = |g: &Formula| std::mem::discriminant(g) == std::mem::discriminant(f);
+		let mut operands = vec!();
+		let mut g = f;
+		if sides.0 < sides.1 {
+			while let Some((l, r)) = operands_of(g).filter(|_| same(g)) {
+				operands.push((r, sides.1));
+				g = l;
+			}
+			operands.push((g, sides.0));
+			operands.reverse();
+		} else if sides.1 < sides.0 {
+			while let Some((l, r)) = operands_of(g).filter(|_| same(g)) {
+				operands.push((l, sides.0));
+				g = r;
+			}
+			operands.push((g, sides.1));
+		} else if let Some((l, r)) = operands_of(f) {
+			operands = vec!((l, sides.0), (r, sides.1));
+		}
+
+		let last = operands.len() - 1;
+		let mut docs = vec!();
+		for (i, (g, prec)) in operands.into_iter().enumerate() {
+			let doc = self.formula(g, prec, i == last && open);
+			if i == 0 {
+				docs.push(doc);
+			} else {
+				docs.push(text(format!(" {}", op)));
+				docs.push(Doc::Nest(2, Box::new(Doc::Concat(vec!(Doc::Line, doc)))));
+			}
+		}
+		Doc::Group(Box::new(Doc::Concat(docs)))
+	}
+
+	/// Prints a run of quantifiers, merging variables bound by the same one.
+	fn quantifier(&self, f: &Formula) -> Doc {
+		let exists = matches!(f, Formula::Exists(_, _));
+		let q = if exists {
+			self.symbol("∃", "exists ", "\\exists ")
+		} else {
+			self.symbol("∀", "forall ", "\\forall ")
+		};
+		let mut vars = vec!();
+		let mut body = f;
+		loop {
+			let (v, e) = match body {
+				Formula::ForAll(v, e) if !exists => (self.name(v), e),
+				Formula::ForAllSeq(_, v, e) if !exists => (self.sequence(&self.name(v), 0), e),
+				Formula::Exists(v, e) if exists => (self.name(v), e),
+				_ => break,
+			};
+			vars.push(v);
+			body = e;
+		}
+		let vars = vars.join(self.symbol(" ", " ", "\\, "));
+		let prefix = text(format!("{}{}{}", q, vars, self.symbol(".", ".", ".\\,")));
+		if precedence(body) == 0 {
+			// A run of different quantifiers stays on one line.
+			return Doc::Concat(vec!(prefix, text(" "), self.formula(body, 0, true)));
+		}
+		Doc::Group(Box::new(Doc::Concat(vec!(
+			prefix,
+			Doc::Nest(2, Box::new(Doc::Concat(vec!(Doc::Line, self.formula(body, 0, true))))),
+		))))
+	}
+
+	/// Prints a term, which is atomic unless it is a formula.
+	fn term(&self, f: &Formula) -> Doc {
+		self.formula(f, ATOM, false)
+	}
+
+	fn application(&self, v: &[Expr]) -> Doc {
+		let head = match v.first() {
+			Some(Expr::Formula(f)) => self.formula(f, ATOM, false),
+			Some(e) => self.expr(e),
+			None => return text("()"),
+		};
+		if v.len() == 1 {
+			return head;
+		}
+
+		let mut args = vec!(head, text("("));
+		for (i, e) in v[1..].iter().enumerate() {
+			if i > 0 {
+				args.push(text(","));
+				args.push(Doc::Line);
+			}
+			args.push(self.expr(e));
+		}
+		args.push(text(")"));
+		Doc::Group(Box::new(Doc::Nest(2, Box::new(Doc::Concat(args)))))
+	}
+
+	fn expr(&self, e: &Expr) -> Doc {
+		match e {
+			Expr::Formula(f) => self.term(f),
+			Expr::Seq(s) => {
+				let (v, tails) = self.seq(s);
+				text(self.sequence(&v, tails))
+			}
+			Expr::Head(s) => text(self.subscript(&self.seq(s).0, "1")),
+		}
+	}
+
+	/// The name of a sequence variable and the number of tails taken.
+	fn seq(&self, s: &Seq) -> (String, usize) {
+		match s {
+			Seq::Free(_, v) => (self.name(v), 0),
+			Seq::Tail(p) => {
+				let (v, n) = self.seq(p);
+				(v, n + 1)
+			}
+		}
+	}
+
+	/// The sequence `v₁…vₙ` with `tails` elements removed from the end.
+	fn sequence(&self, v: &str, tails: usize) -> String {
+		let last = if tails == 0 { "n".to_string() } else { format!("n-{}", tails) };
+		let dots = self.symbol("…", "...", "\\ldots ");
+		format!("{}{}{}", self.subscript(v, "1"), dots, self.subscript(v, &last))
+	}
+
+	fn subscript(&self, v: &str, sub: &str) -> String {
+		match self.style {
+			Style::Unicode => {
+				let sub: String = sub.chars().map(|c| match c {
+					'0'..='9' => std::char::from_u32(0x2080 + c as u32 - '0' as u32).unwrap(),
+					'n' => 'ₙ',
+					'-' => '₋',
+					o => o,
+				}).collect();
+				format!("{}{}", v, sub)
+			}
+			Style::Ascii => format!("{}_{}", v, sub),
+			Style::Latex => if sub.chars().count() == 1 {
+				format!("{}_{}", v, sub)
+			} else {
+				format!("{}_{{{}}}", v, sub)
+			}
+		}
+	}
+
+	fn name(&self, c: &ConstName) -> String {
+		match c {
+			ConstName::Int(i) => self.subscript("v", &i.to_string()),
+			ConstName::String(s) => match self.style {
+				Style::Latex => latex_name(s),
+				_ if s.starts_with('"') || self.numbered(s) => format!("{:?}", s),
+				_ => s.to_string(),
+			}
+		}
+	}
+
+	/// Whether the string name prints the same as a `ConstName::Int`.
+	fn numbered(&self, s: &str) -> bool {
+		let digits: String = match (self.style, s.strip_prefix('v')) {
+			(Style::Unicode, Some(rest)) => rest.chars().map(|c| match c {
+				'₀'..='₉' => std::char::from_u32(c as u32 - 0x2080 + '0' as u32).unwrap(),
+				o => o,
+			}).collect(),
+			(Style::Ascii, Some(rest)) => match rest.strip_prefix('_') {
+				Some(d) => d.to_string(),
+				None => return false,
+			},
+			_ => return false,
+		};
+		match digits.parse::<u64>() {
+			Ok(i) => self.name(&ConstName::Int(i)) == s,
+			Err(_) => false,
+		}
+	}
+}
+
+/// A name as LaTeX math: single letters as they are, longer names upright.
+fn latex_name(s: &str) -> String {
+	let mut chars = s.chars();
+	if let (Some(c), None) = (chars.next(), chars.next()) {
+		if c.is_ascii_alphabetic() {
+			return s.to_string();
+		}
+	}
+	let mut out = String::new();
+	for c in s.chars() {
+		match c {
+			'_' | '#' | '$' | '%' | '&' | '{' | '}' => {
+				out.push('\\');
+				out.push(c);
+			}
+			'\\' => out.push_str("\\textbackslash{}"),
+			'^' => out.push_str("\\hat{}"),
+			'~' => out.push_str("\\sim{}"),
+			c => out.push(c),
+		}
+	}
+	format!("\\mathrm{{{}}}", out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	fn atom(s: &str) -> Ptr<Formula> {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	#[test]
+	fn equations_bind_between_negation_and_conjunction() {
+		let (a, b) = (atom("a"), atom("b"));
+		let eq = Formula::Eq(a.clone(), b.clone()).ptr();
+		assert_eq!(unicode(&Formula::Not(eq.clone())), "¬(a = b)");
+		assert_eq!(unicode(&Formula::Eq(Formula::Not(a.clone()).ptr(), b.clone())), "¬a = b");
+		assert_eq!(unicode(&Formula::And(eq.clone(), eq.clone())), "a = b ∧ a = b");
+		assert_eq!(unicode(&Formula::Eq(eq.clone(), b.clone())), "(a = b) = b");
+	}
+
+	#[test]
+	fn string_names_do_not_clash_with_numbered_names() {
+		let s = |s: &str| ConstName::String(Ptr::new(s.to_string()));
+		assert_eq!(name(&ConstName::Int(1), Style::Unicode), "v₁");
+		assert_eq!(name(&s("v₁"), Style::Unicode), "\"v₁\"");
+		assert_eq!(name(&s("\"v₁\""), Style::Unicode), "\"\\\"v₁\\\"\"");
+		assert_eq!(name(&s("v₀₁"), Style::Unicode), "v₀₁");
+		assert_eq!(name(&s("v_1"), Style::Unicode), "v_1");
+		assert_eq!(name(&ConstName::Int(12), Style::Ascii), "v_12");
+		assert_eq!(name(&s("v_12"), Style::Ascii), "\"v_12\"");
+		assert_eq!(name(&s("v"), Style::Ascii), "v");
+	}
+}
@@ -0,0 +1,384 @@
+//! Truth tables and binary decision diagrams for the propositional fragment
+//! of `Formula`.
+//!
+//! Formulas are read as in `prover::tautology`: `True`, `False`, `And`,
+//! `Or`, `Not`, `Implies` and `IFF` are connectives and every other formula
+//! is an atom compared structurally. Truth tables have a row for each of the
+//! `2^n` assignments to `n` atoms, so larger formulas should be compared
+//! with `Bdd` instead.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use formula::{Formula, Form};
+use pretty;
+
+/// The atoms of the formula, in order of first occurrence.
+pub fn atoms(f: &Formula) -> Vec<Form> {
+	let mut out = vec!();
+	collect_atoms(f, &mut out);
+	out
+}
+
+fn collect_atoms(f: &Formula, out: &mut Vec<Form>) {
+	match f {
+		Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) | Formula::IFF(l, r) => {
+			collect_atoms(l, out);
+			collect_atoms(r, out);
+		}
+		Formula::Not(l) => collect_atoms(l, out),
+		Formula::True | Formula::False => (),
+		a => if !out.iter().any(|b| **b == *a) {
+			out.push(a.clone().ptr());
+		}
+	}
+}
+
+/// The truth value of the formula when each atom of `atoms` has the value
+/// at the same index of `values`. Atoms not listed are false.
+pub fn eval(f: &Formula, atoms: &[Form], values: &[bool]) -> bool {
+	match f {
+		Formula::True => true,
+		Formula::False => false,
+		Formula::Not(l) => !eval(l, atoms, values),
+		Formula::And(l, r) => eval(l, atoms, values) && eval(r, atoms, values),
+		Formula::Or(l, r) => eval(l, atoms, values) || eval(r, atoms, values),
+		Formula::Implies(l, r) => !eval(l, atoms, values) || eval(r, atoms, values),
+		Formula::IFF(l, r) => eval(l, atoms, values) == eval(r, atoms, values),
+		a => atoms.iter().position(|b| **b == *a).is_some_and(|i| values[i]),
+	}
+}
+
+/// The truth table of some formulas over their atoms.
+pub struct TruthTable {
+	pub atoms: Vec<Form>,
+	pub formulas: Vec<Form>,
+	/// For each assignment to the atoms, the values of the formulas. Rows
+	/// start with every atom true and count down.
+	pub rows: Vec<(Vec<bool>, Vec<bool>)>,
+}
+
+impl TruthTable {
+	pub fn new(formulas: &[Form]) -> TruthTable {
+		let mut atoms = vec!();
+		for f in formulas.iter() {
+			collect_atoms(f, &mut atoms);
+		}
+
+		let n = atoms.len();
+		let rows = (0..1u64 << n).map(|i| {
+			let values: Vec<bool> = (0..n).map(|a| i >> (n - 1 - a) & 1 == 0).collect();
+			let results = formulas.iter().map(|f| eval(f, &atoms, &values)).collect();
+			(values, results)
+		}).collect();
+
+		TruthTable { atoms, formulas: formulas.to_vec(), rows }
+	}
+
+	/// Whether the formula at the given index is true on every row.
+	pub fn valid(&self, i: usize) -> bool {
+		self.rows.iter().all(|(_, r)| r[i])
+	}
+}
+
+impl fmt::Display for TruthTable {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let headers: Vec<String> = self.atoms.iter().chain(self.formulas.iter())
+			.map(|h| pretty::unicode(h))
+			.collect();
+		let cells = |h: &[String]| h.iter().map(|s| format!(" {} ", s)).collect::<Vec<_>>().join("|");
+
+		writeln!(f, "{}", cells(&headers).trim_end())?;
+		let rule: Vec<String> = headers.iter().map(|h| "-".repeat(h.chars().count() + 2)).collect();
+		writeln!(f, "{}", rule.join("+"))?;
+		for (values, results) in self.rows.iter() {
+			let row: Vec<String> = values.iter().chain(results.iter()).zip(headers.iter())
+				.map(|(b, h)| {
+					let width = h.chars().count();
+					format!("{:<w$}", if *b { "T" } else { "F" }, w = width)
+				})
+				.collect();
+			writeln!(f, "{}", cells(&row).trim_end())?;
+		}
+		Ok(())
+	}
+}
+
+/// Whether the formulas have the same truth value under every assignment.
+pub fn equivalent(a: &Formula, b: &Formula) -> bool {
+	let mut bdd = Bdd::new(&[]);
+	let (x, y) = (bdd.build(a), bdd.build(b));
+	x == y
+}
+
+pub fn is_tautology(f: &Formula) -> bool {
+	let mut bdd = Bdd::new(&[]);
+	bdd.build(f) == Node::TRUE
+}
+
+pub fn is_satisfiable(f: &Formula) -> bool {
+	let mut bdd = Bdd::new(&[]);
+	bdd.build(f) != Node::FALSE
+}
+
+/// A node of a `Bdd`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Node(usize);
+
+impl Node {
+	pub const FALSE: Node = Node(0);
+	pub const TRUE: Node = Node(1);
+}
+
+/// Reduced ordered binary decision diagrams sharing their nodes. Two
+/// formulas built in the same `Bdd` are equivalent exactly when they have
+/// the same node.
+pub struct Bdd {
+	/// The variable order.
+	pub atoms: Vec<Form>,
+	/// The variable and low and high children of each node. The first two
+	/// entries stand for the terminals.
+	nodes: Vec<(usize, Node, Node)>,
+	unique: HashMap<(usize, Node, Node), Node>,
+	computed: HashMap<(Node, Node, Node), Node>,
+}
+
+impl Bdd {
+	/// A diagram ordering its variables as in `atoms`, followed by any other
+	/// atoms in order of first use.
+	pub fn new(atoms: &[Form]) -> Bdd {
+		Bdd {
+			atoms: atoms.to_vec(),
+			nodes: vec!((usize::MAX, Node::FALSE, Node::FALSE), (usize::MAX, Node::TRUE, Node::TRUE)),
+			unique: HashMap::new(),
+			computed: HashMap::new(),
+		}
+	}
+
+	pub fn build(&mut self, f: &Formula) -> Node {
+		match f {
+			Formula::True => Node::TRUE,
+			Formula::False => Node::FALSE,
+			Formula::Not(l) => {
+				let a = self.build(l);
+				self.ite(a, Node::FALSE, Node::TRUE)
+			}
+			Formula::And(l, r) => {
+				let (a, b) = (self.build(l), self.build(r));
+				self.ite(a, b, Node::FALSE)
+			}
+			Formula::Or(l, r) => {
+				let (a, b) = (self.build(l), self.build(r));
+				self.ite(a, Node::TRUE, b)
+			}
+			Formula::Implies(l, r) => {
+				let (a, b) = (self.build(l), self.build(r));
+				self.ite(a, b, Node::TRUE)
+			}
+			Formula::IFF(l, r) => {
+				let (a, b) = (self.build(l), self.build(r));
+				let nb = self.ite(b, Node::FALSE, Node::TRUE);
+				self.ite(a, b, nb)
+			}
+			a => {
+				let v = match self.atoms.iter().position(|b| **b == *a) {
+					Some(v) => v,
+					None => {
+						self.atoms.push(a.clone().ptr());
+						self.atoms.len() - 1
+					}
+				};
+				self.make(v, Node::FALSE, Node::TRUE)
+			}
+		}
+	}
+
+	fn make(&mut self, var: usize, low: Node, high: Node) -> Node {
+		if low == high {
+			return low;
+		}
+		if let Some(n) = self.unique.get(&(var, low, high)) {
+			return *n;
+		}
+		let n = Node(self.nodes.len());
+		self.nodes.push((var, low, high));
+		self.unique.insert((var, low, high), n);
+		n
+	}
+
+	fn var(&self, n: Node) -> usize {
+		self.nodes[n.0].0
+	}
+
+	/// The cofactors of `n` for the variable `v`.
+	fn cofactors(&self, n: Node, v: usize) -> (Node, Node) {
+		let (var, low, high) = self.nodes[n.0];
+		if var == v { (low, high) } else { (n, n) }
+	}
+
+	/// If-then-else, from which every connective is built.
+	pub fn ite(&mut self, f: Node, g: Node, h: Node) -> Node {
+		if f == Node::TRUE {
+			return g;
+		}
+		if f == Node::FALSE {
+			return h;
+		}
+		if g == h || (g == Node::TRUE && h == Node::FALSE) {
+			return if g == h { g } else { f };
+		}
+		if let Some(n) = self.computed.get(&(f, g, h)) {
+			return *n;
+		}
+
+		let v = [f, g, h].iter().map(|n| self.var(*n)).min().unwrap();
+		let (f0, f1) = self.cofactors(f, v);
+		let (g0, g1) = self.cofactors(g, v);
+		let (h0, h1) = self.cofactors(h, v);
+		let low = self.ite(f0, g0, h0);
+		let high = self.ite(f1, g1, h1);
+		let n = self.make(v, low, high);
+		self.computed.insert((f, g, h), n);
+		n
+	}
+
+	/// The number of nodes reachable from `n`, terminals included.
+	pub fn size(&self, n: Node) -> usize {
+		let mut seen = vec!();
+		let mut stack = vec!(n);
+		while let Some(m) = stack.pop() {
+			if seen.contains(&m) {
+				continue;
+			}
+			seen.push(m);
+			if m.0 > 1 {
+				let (_, low, high) = self.nodes[m.0];
+				stack.push(low);
+				stack.push(high);
+			}
+		}
+		seen.len()
+	}
+
+	/// The number of assignments to all the atoms of the diagram making `n`
+	/// true.
+	pub fn sat_count(&self, n: Node) -> u128 {
+		let level = |m: Node| if m.0 > 1 { self.var(m) } else { self.atoms.len() };
+		let mut memo: HashMap<Node, u128> = HashMap::new();
+		fn count(b: &Bdd, m: Node, memo: &mut HashMap<Node, u128>, level: &dyn Fn(Node) -> usize) -> u128 {
+			if m.0 <= 1 {
+				return m.0 as u128;
+			}
+			if let Some(c) = memo.get(&m) {
+				return *c;
+			}
+			let (v, low, high) = b.nodes[m.0];
+			let c = (count(b, low, memo, level) << (level(low) - v - 1))
+				+ (count(b, high, memo, level) << (level(high) - v - 1));
+			memo.insert(m, c);
+			c
+		}
+		count(self, n, &mut memo, &level) << level(n)
+	}
+
+	/// Some assignment making `n` true, giving values to the atoms on one
+	/// path of the diagram only.
+	pub fn any_sat(&self, n: Node) -> Option<Vec<(Form, bool)>> {
+		if n == Node::FALSE {
+			return None;
+		}
+		let mut out = vec!();
+		let mut m = n;
+		while m.0 > 1 {
+			let (v, low, high) = self.nodes[m.0];
+			if low != Node::FALSE {
+				out.push((self.atoms[v].clone(), false));
+				m = low;
+			} else {
+				out.push((self.atoms[v].clone(), true));
+				m = high;
+			}
+		}
+		Some(out)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use formula::ConstName;
+	use Ptr;
+
+	fn atom(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn and(l: &Form, r: &Form) -> Form { Formula::And(l.clone(), r.clone()).ptr() }
+	fn or(l: &Form, r: &Form) -> Form { Formula::Or(l.clone(), r.clone()).ptr() }
+	fn implies(l: &Form, r: &Form) -> Form { Formula::Implies(l.clone(), r.clone()).ptr() }
+	fn iff(l: &Form, r: &Form) -> Form { Formula::IFF(l.clone(), r.clone()).ptr() }
+	fn not(f: &Form) -> Form { Formula::Not(f.clone()).ptr() }
+
+	fn samples() -> Vec<Form> {
+		let (p, q, r) = (atom("p"), atom("q"), atom("r"));
+		vec!(
+			implies(&p, &q),
+			or(&not(&p), &q),
+			iff(&p, &not(&q)),
+			and(&or(&p, &q), &r),
+			or(&and(&p, &r), &and(&q, &r)),
+			implies(&and(&implies(&p, &q), &implies(&q, &r)), &implies(&p, &r)),
+			and(&p, &not(&p)),
+			Formula::True.ptr(),
+			iff(&iff(&p, &q), &r),
+			iff(&p, &iff(&q, &r)))
+	}
+
+	#[test]
+	fn truth_tables_and_bdds_agree() {
+		let formulas = samples();
+		let table = TruthTable::new(&formulas);
+		assert!(table.atoms.len() == 3 && table.rows.len() == 8);
+		assert!(table.rows[0].0 == vec!(true, true, true));
+
+		let mut bdd = Bdd::new(&table.atoms);
+		let nodes: Vec<Node> = formulas.iter().map(|f| bdd.build(f)).collect();
+		for (i, f) in formulas.iter().enumerate() {
+			let count = table.rows.iter().filter(|(_, r)| r[i]).count();
+			assert_eq!(bdd.sat_count(nodes[i]), count as u128);
+			assert_eq!(is_tautology(f), table.valid(i));
+			assert_eq!(is_satisfiable(f), count > 0);
+			for j in 0..formulas.len() {
+				let same = table.rows.iter().all(|(_, r)| r[i] == r[j]);
+				assert_eq!(nodes[i] == nodes[j], same);
+				assert_eq!(equivalent(f, &formulas[j]), same);
+			}
+		}
+	}
+
+	#[test]
+	fn any_sat_satisfies() {
+		let mut bdd = Bdd::new(&[]);
+		for f in samples() {
+			let n = bdd.build(&f);
+			match bdd.any_sat(n) {
+				Some(assignment) => {
+					let (atoms, values): (Vec<Form>, Vec<bool>) = assignment.into_iter().unzip();
+					assert!(eval(&f, &atoms, &values));
+				}
+				None => assert!(!is_satisfiable(&f)),
+			}
+		}
+	}
+
+	#[test]
+	fn bdds_are_reduced() {
+		let (p, q) = (atom("p"), atom("q"));
+		let mut bdd = Bdd::new(&[p.clone(), q.clone()]);
+		let n = bdd.build(&and(&p, &q));
+		assert_eq!(bdd.size(n), 4);
+		let m = bdd.build(&or(&and(&p, &q), &and(&p, &not(&q))));
+		assert!(m == bdd.build(&p));
+		assert_eq!(bdd.size(m), 3);
+	}
+}
@@ -0,0 +1,430 @@
+//! Congruence closure over ground equations.
+//!
+//! Terms are compared structurally. `Relation` formulas whose arguments are
+//! all formulas, as well as the propositional connectives and `Eq`, are
+//! function applications over their subformulas; quantified formulas,
+//! `Subst` and relations over sequences are opaque. Equations come from the
+//! `Eq` formulas of a context, so the closure decides exactly the equalities
+//! following from them by reflexivity, symmetry, transitivity and
+//! congruence. Every equality it decides is explained by `EqualityIntro`
+//! and `Substitution` steps, re-checked by the kernel.
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::Deduction;
+use knowledge_base::KnowledgeBase;
+
+/// Returns the equations among the formulas of the context.
+pub fn equations<K: KnowledgeBase>(kbase: &K) -> Vec<(Form, Form)> {
+	kbase.theorems().into_iter()
+		.filter_map(|f| match f {
+			Formula::Eq(l, r) => Some((l, r)),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Searches for a proof of `Eq(l, r)` from the equations of `kbase`. On
+/// success the deduction is checked by `Deduction::deduced` against `kbase`.
+pub fn prove<K: KnowledgeBase>(kbase: &K, l: &Form, r: &Form) -> Option<Deduction> {
+	Closure::new(&equations(kbase)).prove(l, r)
+}
+
+/// The shape of a term, up to its arguments.
+#[derive(Clone, PartialEq)]
+enum Shape {
+	Leaf,
+	Relation(usize),
+	Eq,
+	Iff,
+	And,
+	Or,
+	Implies,
+	Not,
+}
+
+struct Node {
+	term: Form,
+	shape: Shape,
+	args: Vec<usize>,
+}
+
+/// Why two terms were merged.
+#[derive(Clone, Copy)]
+enum Reason {
+	/// The equation with the given index.
+	Given(usize),
+	/// Both terms apply the same shape to equal arguments.
+	Congruence,
+}
+
+/// The equivalence classes of terms under a set of equations.
+pub struct Closure {
+	equations: Vec<(Form, Form)>,
+	nodes: Vec<Node>,
+	parent: Vec<usize>,
+	/// The merges made, forming a spanning forest of each class.
+	edges: Vec<(usize, usize, Reason)>,
+}
+
+impl Closure {
+	pub fn new(equations: &[(Form, Form)]) -> Closure {
+		let mut c = Closure {
+			equations: equations.to_vec(),
+			nodes: vec!(),
+			parent: vec!(),
+			edges: vec!(),
+		};
+		for (i, (l, r)) in equations.iter().enumerate() {
+			let a = c.node(l);
+			let b = c.node(r);
+			c.merge(a, b, Reason::Given(i));
+		}
+		c.close();
+		c
+	}
+
+	/// Whether `l` and `r` are equal under the equations.
+	pub fn equal(&mut self, l: &Form, r: &Form) -> bool {
+		let a = self.add(l);
+		let b = self.add(r);
+		self.find(a) == self.find(b)
+	}
+
+	/// Returns a deduction of `Eq(l, r)`, in any context containing the
+	/// equations, if they are equal.
+	pub fn prove(&mut self, l: &Form, r: &Form) -> Option<Deduction> {
+		if !self.equal(l, r) {
+			return None;
+		}
+
+		let a = self.add(l);
+		let b = self.add(r);
+		let fresh = self.nodes.iter().map(|n| n.term.max_int()).max().unwrap_or(0) + 1;
+		let mut proof = Proof { placeholder: ConstName::Int(fresh), steps: vec!(), known: vec!() };
+		self.explain(a, b, &mut proof);
+		Some(Deduction::Sequence(proof.steps))
+	}
+
+	/// Adds a term and its subterms, closing the classes again if needed.
+	fn add(&mut self, t: &Form) -> usize {
+		let before = self.nodes.len();
+		let n = self.node(t);
+		if self.nodes.len() != before {
+			self.close();
+		}
+		n
+	}
+
+	fn node(&mut self, t: &Form) -> usize {
+		if let Some(i) = self.nodes.iter().position(|n| n.term == *t) {
+			return i;
+		}
+
+		let (shape, args) = split(t);
+		let args = args.iter().map(|a| self.node(a)).collect();
+		self.nodes.push(Node { term: t.clone(), shape, args });
+		self.parent.push(self.parent.len());
+		self.nodes.len() - 1
+	}
+
+	fn find(&self, mut i: usize) -> usize {
+		while self.parent[i] != i {
+			i = self.parent[i];
+		}
+		i
+	}
+
+	fn merge(&mut self, a: usize, b: usize, reason: Reason) -> bool {
+		let (ra, rb) = (self.find(a), self.find(b));
+		if ra == rb {
+			return false;
+		}
+		self.parent[ra] = rb;
+		self.edges.push((a, b, reason));
+		true
+	}
+
+	/// Merges congruent terms until no more are.
+	fn close(&mut self) {
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for a in 0..self.nodes.len() {
+				for b in a + 1..self.nodes.len() {
+					if self.congruent(a, b) && self.merge(a, b, Reason::Congruence) {
+						changed = true;
+					}
+				}
+			}
+		}
+	}
+
+	fn congruent(&self, a: usize, b: usize) -> bool {
+		let (x, y) = (&self.nodes[a], &self.nodes[b]);
+		x.shape != Shape::Leaf
+			&& x.shape == y.shape
+			&& x.args.len() == y.args.len()
+			&& x.args.iter().zip(y.args.iter()).all(|(i, j)| self.find(*i) == self.find(*j))
+	}
+
+	/// The merges connecting `a` to `b`, which must be in the same class.
+	fn path(&self, a: usize, b: usize) -> Vec<(usize, usize, Reason)> {
+		let mut previous: Vec<Option<(usize, Reason)>> = vec!(None; self.nodes.len());
+		let mut seen = vec!(false; self.nodes.len());
+		let mut queue = vec!(a);
+		seen[a] = true;
+
+		while let Some(n) = queue.pop() {
+			if n == b {
+				break;
+			}
+			for (x, y, reason) in self.edges.iter() {
+				let next = if *x == n { *y } else if *y == n { *x } else { continue };
+				if !seen[next] {
+					seen[next] = true;
+					previous[next] = Some((n, *reason));
+					queue.push(next);
+				}
+			}
+		}
+
+		let mut path = vec!();
+		let mut n = b;
+		while let Some((p, reason)) = previous[n] {
+			path.push((p, n, reason));
+			n = p;
+		}
+		path.reverse();
+		path
+	}
+
+	/// Adds steps deducing `Eq(a, b)` to the proof.
+	fn explain(&self, a: usize, b: usize, proof: &mut Proof) {
+		let (l, r) = (&self.nodes[a].term, &self.nodes[b].term);
+		if proof.knows(l, r) {
+			return;
+		}
+		if a == b {
+			proof.push(l, r, Deduction::EqualityIntro(l.clone()));
+			return;
+		}
+
+		for (x, y, reason) in self.path(a, b) {
+			match reason {
+				Reason::Given(i) => {
+					let (el, er) = &self.equations[i];
+					proof.given(el, er);
+					if *el != self.nodes[x].term {
+						proof.symmetry(el, er);
+					}
+				}
+				Reason::Congruence => self.congruence(x, y, proof),
+			}
+			if x != a {
+				proof.transitivity(&self.nodes[a].term, &self.nodes[x].term, &self.nodes[y].term);
+			}
+		}
+	}
+
+	/// Adds steps deducing `Eq(a, b)` for congruent terms, one argument at a
+	/// time.
+	fn congruence(&self, a: usize, b: usize, proof: &mut Proof) {
+		let (x, y) = (&self.nodes[a], &self.nodes[b]);
+		if proof.knows(&x.term, &y.term) {
+			return;
+		}
+		for (i, j) in x.args.iter().zip(y.args.iter()) {
+			self.explain(*i, *j, proof);
+		}
+
+		let args: Vec<Form> = x.args.iter().map(|i| self.nodes[*i].term.clone()).collect();
+		let mut current = args.clone();
+		proof.push(&x.term, &x.term, Deduction::EqualityIntro(x.term.clone()));
+		for (n, j) in y.args.iter().enumerate() {
+			let target = &self.nodes[*j].term;
+			if current[n] == *target {
+				continue;
+			}
+			let mut hole = current.clone();
+			hole[n] = Formula::Free(proof.placeholder.clone()).ptr();
+			let context = Formula::Eq(x.term.clone(), join(&x.term, &hole).ptr());
+			proof.steps.push(Deduction::Substitution(
+				args[n].clone(),
+				target.clone(),
+				proof.placeholder.clone(),
+				context.ptr()));
+			current[n] = target.clone();
+		}
+		proof.known.push((x.term.clone(), y.term.clone()));
+	}
+}
+
+/// The steps of an explanation, with the equations they deduce.
+struct Proof {
+	placeholder: ConstName,
+	steps: Vec<Deduction>,
+	known: Vec<(Form, Form)>,
+}
+
+impl Proof {
+	fn knows(&self, l: &Form, r: &Form) -> bool {
+		self.known.iter().any(|(a, b)| a == l && b == r)
+	}
+
+	fn push(&mut self, l: &Form, r: &Form, step: Deduction) {
+		self.steps.push(step);
+		self.known.push((l.clone(), r.clone()));
+	}
+
+	fn given(&mut self, l: &Form, r: &Form) {
+		self.known.push((l.clone(), r.clone()));
+	}
+
+	fn hole(&self) -> Form {
+		Formula::Free(self.placeholder.clone()).ptr()
+	}
+
+	/// Deduces `Eq(r, l)` from `Eq(l, r)`.
+	fn symmetry(&mut self, l: &Form, r: &Form) {
+		if self.knows(r, l) {
+			return;
+		}
+		self.steps.push(Deduction::EqualityIntro(l.clone()));
+		let context = Formula::Eq(self.hole(), l.clone()).ptr();
+		self.push(r, l, Deduction::Substitution(l.clone(), r.clone(), self.placeholder.clone(), context));
+	}
+
+	/// Deduces `Eq(a, c)` from `Eq(a, b)` and `Eq(b, c)`.
+	fn transitivity(&mut self, a: &Form, b: &Form, c: &Form) {
+		if self.knows(a, c) {
+			return;
+		}
+		let context = Formula::Eq(a.clone(), self.hole()).ptr();
+		self.push(a, c, Deduction::Substitution(b.clone(), c.clone(), self.placeholder.clone(), context));
+	}
+}
+
+/// Splits a term into its shape and arguments.
+fn split(t: &Form) -> (Shape, Vec<Form>) {
+	match &**t {
+		Formula::Relation(v) => {
+			let args: Option<Vec<Form>> = v.iter().map(|e| match e {
+				Expr::Formula(f) => Some(f.clone().ptr()),
+				_ => None,
+			}).collect();
+			match args {
+				Some(args) => (Shape::Relation(args.len()), args),
+				None => (Shape::Leaf, vec!()),
+			}
+		}
+		Formula::Eq(l, r) => (Shape::Eq, vec!(l.clone(), r.clone())),
+		Formula::IFF(l, r) => (Shape::Iff, vec!(l.clone(), r.clone())),
+		Formula::And(l, r) => (Shape::And, vec!(l.clone(), r.clone())),
+		Formula::Or(l, r) => (Shape::Or, vec!(l.clone(), r.clone())),
+		Formula::Implies(l, r) => (Shape::Implies, vec!(l.clone(), r.clone())),
+		Formula::Not(l) => (Shape::Not, vec!(l.clone())),
+		_ => (Shape::Leaf, vec!()),
+	}
+}
+
+/// Rebuilds a term of the same shape as `t` over new arguments.
+fn join(t: &Form, args: &[Form]) -> Formula {
+	match &**t {
+		Formula::Relation(_) =>
+			Formula::Relation(args.iter().map(|a| Expr::Formula((**a).clone())).collect()),
+		Formula::Eq(_, _) => Formula::Eq(args[0].clone(), args[1].clone()),
+		Formula::IFF(_, _) => Formula::IFF(args[0].clone(), args[1].clone()),
+		Formula::And(_, _) => Formula::And(args[0].clone(), args[1].clone()),
+		Formula::Or(_, _) => Formula::Or(args[0].clone(), args[1].clone()),
+		Formula::Implies(_, _) => Formula::Implies(args[0].clone(), args[1].clone()),
+		Formula::Not(_) => Formula::Not(args[0].clone()),
+		o => o.clone(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	struct Facts(Vec<Formula>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.contains(form)
+		}
+
+		fn has_const(&self, _c: &ConstName) -> bool {
+			false
+		}
+
+		fn theorems(&self) -> Vec<Formula> {
+			self.0.clone()
+		}
+	}
+
+	fn konst(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn app(f: &str, args: &[&Form]) -> Form {
+		let mut v = vec!(Expr::Formula((*konst(f)).clone()));
+		v.extend(args.iter().map(|a| Expr::Formula((***a).clone())));
+		Formula::Relation(v).ptr()
+	}
+
+	fn eq(l: &Form, r: &Form) -> Formula { Formula::Eq(l.clone(), r.clone()) }
+
+	/// Proves `Eq(l, r)` from the equations and checks the proof.
+	fn proven(equations: Vec<Formula>, l: &Form, r: &Form) -> bool {
+		let kbase = Facts(equations);
+		match prove(&kbase, l, r) {
+			Some(d) => {
+				assert!(d.deduced(kbase, &eq(l, r)));
+				true
+			}
+			None => false,
+		}
+	}
+
+	#[test]
+	fn equality_is_reflexive_symmetric_and_transitive() {
+		let (a, b, c) = (konst("a"), konst("b"), konst("c"));
+		assert!(proven(vec!(), &a, &a));
+		assert!(proven(vec!(eq(&a, &b)), &b, &a));
+		assert!(proven(vec!(eq(&a, &b), eq(&c, &b)), &a, &c));
+		assert!(!proven(vec!(eq(&a, &b)), &a, &c));
+	}
+
+	#[test]
+	fn equal_arguments_give_equal_applications() {
+		let (a, b, c) = (konst("a"), konst("b"), konst("c"));
+		assert!(proven(vec!(eq(&a, &b)), &app("f", &[&a, &c]), &app("f", &[&b, &c])));
+		assert!(proven(vec!(eq(&a, &b)),
+			&app("g", &[&app("f", &[&a])]),
+			&app("g", &[&app("f", &[&b])])));
+		assert!(!proven(vec!(eq(&a, &b)), &app("f", &[&a]), &app("g", &[&b])));
+	}
+
+	#[test]
+	fn cycles_of_applications_collapse() {
+		let a = konst("a");
+		let mut f = vec!(a.clone());
+		for i in 0..5 {
+			let next = app("f", &[&f[i]]);
+			f.push(next);
+		}
+		assert!(proven(vec!(eq(&f[3], &a), eq(&f[5], &a)), &f[1], &a));
+		assert!(!proven(vec!(eq(&f[3], &a)), &f[1], &a));
+	}
+
+	#[test]
+	fn connectives_are_congruent() {
+		let (a, b) = (konst("a"), konst("b"));
+		let not = |f: &Form| Formula::Not(f.clone()).ptr();
+		assert!(proven(vec!(eq(&a, &b)), &not(&a), &not(&b)));
+		let q = Formula::ForAll(ConstName::Int(1), a.clone()).ptr();
+		let r = Formula::ForAll(ConstName::Int(1), b.clone()).ptr();
+		assert!(!proven(vec!(eq(&a, &b)), &q, &r));
+	}
+}
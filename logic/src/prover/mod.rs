@@ -0,0 +1,36 @@
+//! Automated proof search. Provers in this module never decide a theorem on
+//! their own authority: they only construct `Deduction`s, which are then
+//! checked by the kernel like any hand written proof.
+
+use formula::Form;
+use deduction::Deduction;
+
+pub mod tautology;
+pub mod tableau;
+pub mod congruence;
+
+/// A tableau branch which could not be closed.
+#[derive(Clone)]
+pub struct OpenBranch {
+	/// The formulas known on the branch when the search stopped.
+	pub formulas: Vec<Form>,
+	/// Whether every rule was exhausted on the branch. A saturated branch
+	/// describes a counter-model; otherwise the search ran out of resources.
+	pub saturated: bool,
+}
+
+/// Either a deduction of `False` from a branch, or the branches left open.
+pub type Refutation = Result<Deduction, Vec<OpenBranch>>;
+
+/// Combines the refutations of two sibling branches, collecting the open
+/// branches of both when either stays open.
+pub fn both<F>(left: Refutation, right: Refutation, join: F) -> Refutation 
+	where F: FnOnce(Deduction, Deduction) -> Deduction 
+{
+	match (left, right) {
+		(Ok(l), Ok(r)) => Ok(join(l, r)),
+		(Err(l), Ok(_)) => Err(l),
+		(Ok(_), Err(r)) => Err(r),
+		(Err(mut l), Err(mut r)) => { l.append(&mut r); Err(l) }
+	}
+}
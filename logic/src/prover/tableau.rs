@@ -0,0 +1,525 @@
+//! A bounded, ground analytic tableau for first-order goals.
+//!
+//! The propositional rules are those of `prover::tautology`. On top of them
+//! `Exists` and negated `ForAll` formulas are instantiated once with a fresh
+//! constant, `ForAll` and negated `Exists` formulas are instantiated with the
+//! ground terms of the branch, and `Eq` literals rewrite the other literals
+//! of the branch. The last two rules can be applied forever, so each branch
+//! is given a fixed number of them; branches still open when it runs out are
+//! reported back instead of a deduction.
+
+use std::cell::Cell;
+use std::fmt;
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::Deduction;
+use deduction::Work;
+use knowledge_base::KnowledgeBase;
+use prover::{OpenBranch, Refutation};
+use prover::tautology;
+use Ptr;
+
+/// The report of an unsuccessful search.
+pub struct GaveUp {
+	pub open: Vec<OpenBranch>,
+}
+
+impl GaveUp {
+	/// Whether some open branch is saturated, in which case no bound would
+	/// have been enough to prove the goal. A branch holding a formula the
+	/// tableau cannot expand, such as a `ForAllSeq`, a `Subst` or a negated
+	/// `ForAll` whose bound name is declared in the knowledge base, is never
+	/// saturated.
+	pub fn refuted(&self) -> bool {
+		self.open.iter().any(|b| b.saturated)
+	}
+}
+
+impl fmt::Display for GaveUp {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Gave up with {} open branch(es):", self.open.len())?;
+		for (i, b) in self.open.iter().enumerate() {
+			let state = if b.saturated { "saturated" } else { "not saturated" };
+			writeln!(f, "Branch {} ({}):", i, state)?;
+			for form in b.formulas.iter() {
+				writeln!(f, "\t{}", form.to_string())?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Searches for a proof of `goal` from `premises`, all of which must be
+/// contained in `kbase`. `bound` limits the number of quantifier
+/// instantiations and equality rewrites made on any single branch. On
+/// success the deduction is checked by `Deduction::deduced` against `kbase`.
+pub fn prove<K: KnowledgeBase>(kbase: &K, premises: &[Form], goal: Form, bound: usize)
+-> Result<Deduction, GaveUp> {
+	let fresh = premises.iter()
+		.chain(Some(&goal))
+		.map(|f| f.max_int())
+		.max()
+		.unwrap_or(0);
+	let search = Search { kbase, fresh: Cell::new(fresh) };
+
+	let mut branch = Branch {
+		known: vec!(),
+		expanded: vec!(),
+		terms: vec!(),
+		instances: vec!(),
+		budget: bound,
+		rewrite_next: false,
+	};
+	for p in premises.iter() {
+		branch.assume(p.clone());
+	}
+	branch.assume(Formula::Not(goal.clone()).ptr());
+
+	match branch.refute(&search) {
+		Ok(w) => Ok(Deduction::Contradiction(goal, Ptr::new(w))),
+		Err(open) => Err(GaveUp { open }),
+	}
+}
+
+fn seq(v: Vec<Deduction>) -> Work { Ptr::new(Deduction::Sequence(v)) }
+
+struct Search<'a, K: 'a> {
+	kbase: &'a K,
+	fresh: Cell<u64>,
+}
+
+impl <'a, K: KnowledgeBase> Search<'a, K> {
+	/// A constant neither declared in the knowledge base nor used anywhere
+	/// in the search so far.
+	fn fresh_const(&self) -> ConstName {
+		loop {
+			let n = self.fresh.get() + 1;
+			self.fresh.set(n);
+			let c = ConstName::Int(n);
+			if !self.kbase.has_const(&c) {
+				return c;
+			}
+		}
+	}
+}
+
+/// A rule which may be applied any number of times on a branch.
+enum Step {
+	/// A general formula and the term it is instantiated with.
+	Instance(Form, Form),
+	/// An equation, the literal abstracted over the rewritten side, the
+	/// variable it was abstracted over, and whether the equation is used
+	/// right to left.
+	Rewrite(Form, Form, ConstName, bool),
+}
+
+#[derive(Clone)]
+struct Branch {
+	known: Vec<Form>,
+	expanded: Vec<Form>,
+	terms: Vec<Form>,
+	instances: Vec<(Form, Form)>,
+	budget: usize,
+	rewrite_next: bool,
+}
+
+impl Branch {
+	fn assume(&mut self, f: Form) {
+		if !self.known.contains(&f) {
+			ground_terms(&f, &mut self.terms);
+			self.known.push(f);
+		}
+	}
+
+	fn with(&self, fs: Vec<Form>) -> Branch {
+		let mut b = self.clone();
+		for f in fs {
+			b.assume(f);
+		}
+		b
+	}
+
+	fn refute<K: KnowledgeBase>(mut self, s: &Search<K>) -> Refutation {
+		if let Some(d) = tautology::closure(&self.known) {
+			return Ok(d);
+		}
+		if let Some(d) = self.eq_closure() {
+			return Ok(d);
+		}
+
+		let next = self.known.iter()
+			.filter(|f| !self.expanded.contains(f))
+			.find(|f| tautology::is_linear(f) || self.is_witness(f, s))
+			.or_else(|| self.known.iter()
+				.filter(|f| !self.expanded.contains(f))
+				.find(|f| tautology::is_branching(f)))
+			.cloned();
+
+		if let Some(f) = next {
+			self.expanded.push(f.clone());
+			if self.is_witness(&f, s) {
+				return self.witness(&f, s);
+			}
+			let b = self.clone();
+			return tautology::expand(&f, |fs| b.with(fs).refute(s)).unwrap();
+		}
+
+		match self.next_step(s) {
+			None => {
+				let saturated = !self.blocked(s);
+				Err(vec!(OpenBranch { formulas: self.known, saturated }))
+			}
+			Some(_) if self.budget == 0 =>
+				Err(vec!(OpenBranch { formulas: self.known, saturated: false })),
+			Some(step) => {
+				self.budget -= 1;
+				self.rewrite_next = !self.rewrite_next;
+				self.apply(step, s)
+			}
+		}
+	}
+
+	/// Closes the branch on a literal `Not(Eq(t, t))`.
+	fn eq_closure(&self) -> Option<Deduction> {
+		self.known.iter().filter_map(|f| match &**f {
+			Formula::Not(g) => match &**g {
+				Formula::Eq(l, r) if l == r => Some(Deduction::Sequence(vec!(
+					Deduction::EqualityIntro(l.clone()),
+					Deduction::NotExtract(g.clone())))),
+				_ => None,
+			},
+			_ => None,
+		}).next()
+	}
+
+	/// Formulas instantiated once with a fresh constant.
+	fn is_witness<K: KnowledgeBase>(&self, f: &Formula, s: &Search<K>) -> bool {
+		match f {
+			Formula::Exists(_, _) => true,
+			Formula::Not(g) => match &**g {
+				// ForAllIntro uses the bound name as its eigenvariable.
+				Formula::ForAll(v, _) => !s.kbase.has_const(v),
+				_ => false,
+			},
+			_ => false,
+		}
+	}
+
+	/// Whether the branch holds a formula no rule expands, such as a
+	/// `ForAllSeq`, a `Subst` or a negated `ForAll` that `is_witness` leaves
+	/// alone, so it may be open only for lack of a rule.
+	fn blocked<K: KnowledgeBase>(&self, s: &Search<K>) -> bool {
+		self.known.iter().any(|f| !(is_atomic(f) || tautology::is_linear(f) || tautology::is_branching(f)
+			|| is_general(f) || self.is_witness(f, s)))
+	}
+
+	fn witness<K: KnowledgeBase>(&self, f: &Form, s: &Search<K>) -> Refutation {
+		let c = s.fresh_const();
+		let mut b = self.clone();
+		b.terms.push(Formula::Const(c.clone()).ptr());
+
+		match &**f {
+			Formula::Exists(v, body) => {
+				let inst = body.substitute(v, &Formula::Const(c.clone())).ptr();
+				b.with(vec!(inst)).refute(s).map(|rest| Deduction::Sequence(vec!(
+					Deduction::ExistsExtract((v.clone(), body.clone()), c),
+					rest)))
+			}
+			Formula::Not(g) => match &**g {
+				Formula::ForAll(v, body) => {
+					let neg = Formula::Not(body.clone()).ptr();
+					let ex = Formula::Exists(v.clone(), neg.clone()).ptr();
+					let local = body.substitute(v, &Formula::Const(v.clone())).ptr();
+					let inst = neg.substitute(v, &Formula::Const(c.clone())).ptr();
+
+					b.with(vec!(inst)).refute(s).map(|rest| Deduction::Sequence(vec!(
+						Deduction::Contradiction(ex.clone(), seq(vec!(
+							Deduction::ForAllIntro((v.clone(), body.clone()),
+								Ptr::new(Deduction::Contradiction(local, seq(vec!(
+									Deduction::ExistsIntro(
+										(v.clone(), neg.clone()),
+										Formula::Const(v.clone()).ptr()),
+									Deduction::NotExtract(ex.clone())))))),
+							Deduction::NotExtract(g.clone())))),
+						Deduction::ExistsExtract((v.clone(), neg), c),
+						rest)))
+				}
+				_ => unreachable!(),
+			},
+			_ => unreachable!(),
+		}
+	}
+
+	/// Picks the next quantifier instance or equality rewrite, alternating
+	/// between the two kinds so neither can starve the other.
+	fn next_step<K: KnowledgeBase>(&mut self, s: &Search<K>) -> Option<Step> {
+		let gammas: Vec<Form> = self.known.iter()
+			.filter(|f| is_general(f))
+			.cloned()
+			.collect();
+		if !gammas.is_empty() && self.terms.is_empty() {
+			self.terms.push(Formula::Const(s.fresh_const()).ptr());
+		}
+
+		let mut instance = None;
+		'terms: for t in self.terms.iter() {
+			for g in gammas.iter() {
+				let pair = (g.clone(), t.clone());
+				if !self.instances.contains(&pair) {
+					instance = Some(Step::Instance(pair.0, pair.1));
+					break 'terms;
+				}
+			}
+		}
+
+		let rewrite = self.next_rewrite(s);
+
+		if self.rewrite_next {
+			rewrite.or(instance)
+		} else {
+			instance.or(rewrite)
+		}
+	}
+
+	fn next_rewrite<K: KnowledgeBase>(&self, s: &Search<K>) -> Option<Step> {
+		// Every integer name in use is below the fresh counter, so the next
+		// one cannot occur in any literal.
+		let placeholder = ConstName::Int(s.fresh.get() + 1);
+
+		for e in self.known.iter() {
+			let (l, r) = match &**e {
+				Formula::Eq(l, r) if l != r => (l, r),
+				_ => continue,
+			};
+
+			for (from, to, flipped) in [(l, r, false), (r, l, true)] {
+				for lit in self.known.iter().filter(|f| is_literal(f)) {
+					let ctx = match abstract_term(lit, from, &placeholder) {
+						Some(ctx) => ctx,
+						None => continue,
+					};
+					let result = ctx.substitute(&placeholder, to);
+					if !self.known.iter().any(|k| **k == result) {
+						return Some(Step::Rewrite(e.clone(), ctx.ptr(), placeholder, flipped));
+					}
+				}
+			}
+		}
+
+		None
+	}
+
+	fn apply<K: KnowledgeBase>(mut self, step: Step, s: &Search<K>) -> Refutation {
+		match step {
+			Step::Instance(g, t) => {
+				self.instances.push((g.clone(), t.clone()));
+				let (d, inst) = instantiate(&g, &t);
+				self.with(vec!(inst)).refute(s).map(|rest| Deduction::Sequence(vec!(d, rest)))
+			}
+			Step::Rewrite(e, ctx, placeholder, flipped) => {
+				let (l, r) = match &*e {
+					Formula::Eq(l, r) => (l.clone(), r.clone()),
+					_ => unreachable!(),
+				};
+
+				let mut steps = vec!();
+				let mut new = vec!();
+				let (from, to) = if flipped {
+					let sym = Formula::Eq(r.clone(), l.clone()).ptr();
+					steps.push(Deduction::EqualityIntro(l.clone()));
+					steps.push(Deduction::Substitution(l.clone(), r.clone(), placeholder.clone(),
+						Formula::Eq(Formula::Free(placeholder.clone()).ptr(), l.clone()).ptr()));
+					new.push(sym);
+					(r, l)
+				} else {
+					(l, r)
+				};
+
+				new.push(ctx.substitute(&placeholder, &to).ptr());
+				steps.push(Deduction::Substitution(from, to, placeholder, ctx));
+
+				self.with(new).refute(s).map(|rest| {
+					steps.push(rest);
+					Deduction::Sequence(steps)
+				})
+			}
+		}
+	}
+}
+
+/// Formulas instantiated with every ground term of the branch.
+/// Atoms and negated atoms, which only take part in closing the branch.
+fn is_atomic(f: &Formula) -> bool {
+	let atom = |f: &Formula| matches!(f,
+		Formula::True | Formula::False | Formula::Eq(_, _) | Formula::Relation(_) | Formula::Const(_));
+	match f {
+		Formula::Not(g) => atom(g),
+		g => atom(g),
+	}
+}
+
+fn is_general(f: &Formula) -> bool {
+	match f {
+		Formula::ForAll(_, _) => true,
+		Formula::Not(g) => matches!(**g, Formula::Exists(_, _)),
+		_ => false,
+	}
+}
+
+/// Returns the deduction instantiating a general formula with `t`, and the
+/// instance it concludes.
+fn instantiate(g: &Form, t: &Form) -> (Deduction, Form) {
+	match &**g {
+		Formula::ForAll(v, body) => (
+			Deduction::ForAllExtract((v.clone(), body.clone()), t.clone()),
+			body.substitute(v, t).ptr()),
+		Formula::Not(e) => match &**e {
+			Formula::Exists(v, body) => {
+				let inst = body.substitute(v, t).ptr();
+				(Deduction::NotIntro(inst.clone(), seq(vec!(
+					Deduction::ExistsIntro((v.clone(), body.clone()), t.clone()),
+					Deduction::NotExtract(e.clone())))),
+				Formula::Not(inst).ptr())
+			}
+			_ => unreachable!(),
+		},
+		_ => unreachable!(),
+	}
+}
+
+fn is_literal(f: &Formula) -> bool {
+	match f {
+		Formula::Eq(_, _) | Formula::Relation(_) => true,
+		Formula::Not(g) => matches!(**g, Formula::Eq(_, _) | Formula::Relation(_)),
+		_ => false,
+	}
+}
+
+/// Replaces every occurrence of the term `t` in the literal `f` with the
+/// variable `c`, or returns `None` if `t` does not occur. Quantified
+/// subformulas are left untouched.
+fn abstract_term(f: &Formula, t: &Formula, c: &ConstName) -> Option<Formula> {
+	if f == t {
+		return Some(Formula::Free(c.clone()));
+	}
+
+	match f {
+		Formula::Eq(l, r) => {
+			let l2 = abstract_term(l, t, c);
+			let r2 = abstract_term(r, t, c);
+			if l2.is_none() && r2.is_none() {
+				None
+			} else {
+				Some(Formula::Eq(
+					l2.map(|x| x.ptr()).unwrap_or_else(|| l.clone()),
+					r2.map(|x| x.ptr()).unwrap_or_else(|| r.clone())))
+			}
+		}
+		Formula::Not(g) => abstract_term(g, t, c).map(|x| Formula::Not(x.ptr())),
+		Formula::Relation(v) => {
+			let mut found = false;
+			let args = v.iter().map(|e| match e {
+				Expr::Formula(x) => match abstract_term(x, t, c) {
+					Some(y) => { found = true; Expr::Formula(y) }
+					None => e.clone(),
+				},
+				_ => e.clone(),
+			}).collect();
+			if found { Some(Formula::Relation(args)) } else { None }
+		}
+		_ => None,
+	}
+}
+
+/// Collects the ground terms occurring as arguments of relations or as the
+/// sides of equations in `f`.
+fn ground_terms(f: &Formula, out: &mut Vec<Form>) {
+	let add = |t: &Formula, out: &mut Vec<Form>| {
+		if is_ground(t) && !out.iter().any(|o| **o == *t) {
+			out.push(t.clone().ptr());
+		}
+		ground_terms(t, out);
+	};
+
+	match f {
+		Formula::Eq(l, r) => { add(l, out); add(r, out); }
+		Formula::Relation(v) => for e in v.iter().skip(1) {
+			if let Expr::Formula(x) = e {
+				add(x, out);
+			}
+		},
+		Formula::IFF(l, r) | Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) => {
+			ground_terms(l, out);
+			ground_terms(r, out);
+		}
+		Formula::Not(g) => ground_terms(g, out),
+		_ => (),
+	}
+}
+
+fn is_ground(f: &Formula) -> bool {
+	match f {
+		Formula::Const(_) => true,
+		Formula::Relation(v) => v.iter().all(|e| match e {
+			Expr::Formula(x) => is_ground(x),
+			_ => false,
+		}),
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use formula::Seq;
+
+	struct Declared(Vec<ConstName>);
+
+	impl KnowledgeBase for Declared {
+		fn contains(&self, _form: &Formula) -> bool {
+			false
+		}
+
+		fn has_const(&self, c: &ConstName) -> bool {
+			self.0.contains(c)
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	#[test]
+	fn declared_bound_name_is_not_a_counter_model() {
+		let x = name("x");
+		let px = Formula::Relation(vec!(
+			Expr::Formula(Formula::Const(name("P"))),
+			Expr::Formula(Formula::Free(x.clone())))).ptr();
+		let goal = Formula::ForAll(x.clone(), Formula::Or(px.clone(), Formula::Not(px).ptr()).ptr()).ptr();
+
+		match prove(&Declared(vec!(x, name("P"))), &[], goal, 5) {
+			Ok(_) => (),
+			Err(g) => assert!(!g.refuted()),
+		}
+	}
+
+	fn app(f: &str, arg: Expr) -> Form {
+		Formula::Relation(vec!(Expr::Formula(Formula::Const(name(f))), arg)).ptr()
+	}
+
+	#[test]
+	fn unexpanded_formulas_are_not_counter_models() {
+		let (xs, x, c) = (name("xs"), name("x"), Formula::Const(name("c")));
+		let goal = app("P", Expr::Formula(c.clone()));
+		let kbase = Declared(vec!(name("P"), name("c")));
+
+		let seq = Formula::ForAllSeq(1, xs.clone(), app("P", Expr::Seq(Seq::Free(1, xs.clone())))).ptr();
+		let subst = Formula::Subst(app("P", Expr::Formula(Formula::Free(x.clone()))), x, c.ptr()).ptr();
+		for premise in [seq.clone(), Formula::Not(Formula::Not(seq).ptr()).ptr(), subst] {
+			match prove(&kbase, &[premise], goal.clone(), 5) {
+				Ok(_) => (),
+				Err(g) => assert!(!g.refuted()),
+			}
+		}
+	}
+}
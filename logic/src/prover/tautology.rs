@@ -0,0 +1,266 @@
+//! A decision procedure for the propositional fragment of `Formula`.
+//!
+//! `True`, `False`, `And`, `Or`, `Not`, `Implies` and `IFF` are treated as
+//! connectives, every other formula is an atom compared structurally. The
+//! search is a signed tableau on the hypotheses and the negated goal, where
+//! every tableau rule is emitted as the natural deduction steps justifying
+//! it, so a closed tableau is a `Deduction` of `False` under `Not(goal)`.
+
+use formula::Formula;
+use formula::Form;
+use deduction::Deduction;
+use deduction::Work;
+use prover::{both, OpenBranch, Refutation};
+use Ptr;
+
+/// Searches for a proof of `goal` from `hyps`. On success the returned
+/// deduction is checked by `Deduction::deduced` against any knowledge base
+/// containing every formula of `hyps`. Returns `None` when the goal is not
+/// a propositional consequence of the hypotheses.
+pub fn prove(hyps: &[Form], goal: Form) -> Option<Deduction> {
+	prove_with_atoms(hyps, goal, &[])
+}
+
+/// Like `prove`, but the formulas of `atoms` are never decomposed, even if
+/// they are built from connectives. Useful for proving schematic lemmas
+/// about large subformulas without searching through their structure.
+pub fn prove_with_atoms(hyps: &[Form], goal: Form, atoms: &[Form]) -> Option<Deduction> {
+	let mut branch = Branch::new(atoms.to_vec());
+	for h in hyps.iter() {
+		branch.assume(h.clone());
+	}
+	branch.assume(Formula::Not(goal.clone()).ptr());
+
+	branch.refute().ok().map(|w| Deduction::Contradiction(goal, Ptr::new(w)))
+}
+
+/// Searches for a proof of `goal` from no hypotheses at all.
+pub fn prove_tautology(goal: Form) -> Option<Deduction> {
+	prove(&[], goal)
+}
+
+/// Returns whether the formula is a propositional tautology.
+pub fn is_tautology(goal: &Formula) -> bool {
+	prove_tautology(goal.clone().ptr()).is_some()
+}
+
+fn not(f: &Form) -> Form { Formula::Not(f.clone()).ptr() }
+
+fn seq(v: Vec<Deduction>) -> Work { Ptr::new(Deduction::Sequence(v)) }
+
+fn empty() -> Work { Ptr::new(Deduction::EmptyStep) }
+
+/// One open branch of the tableau: the formulas known in the current proof
+/// context and those which have already been decomposed.
+#[derive(Clone)]
+struct Branch {
+	known: Vec<Form>,
+	expanded: Vec<Form>,
+	atoms: Vec<Form>,
+}
+
+impl Branch {
+	fn new(atoms: Vec<Form>) -> Branch {
+		Branch { known: vec!(), expanded: vec!(), atoms }
+	}
+
+	/// Whether `f` is one of the opaque atoms or the negation of one.
+	fn is_atom(&self, f: &Formula) -> bool {
+		match f {
+			Formula::Not(g) if self.atoms.contains(g) => true,
+			_ => self.atoms.iter().any(|a| **a == *f),
+		}
+	}
+
+	fn assume(&mut self, f: Form) {
+		if !self.known.contains(&f) {
+			self.known.push(f);
+		}
+	}
+
+	fn with(&self, fs: Vec<Form>) -> Branch {
+		let mut b = self.clone();
+		for f in fs {
+			b.assume(f);
+		}
+		b
+	}
+
+	/// Derives `False` from the known formulas, or returns the branch itself
+	/// if it stays open once every formula has been decomposed.
+	fn refute(mut self) -> Refutation {
+		if let Some(d) = closure(&self.known) {
+			return Ok(d);
+		}
+
+		let open: Vec<&Form> = self.known.iter()
+			.filter(|f| !self.expanded.contains(f) && !self.is_atom(f))
+			.collect();
+		let next = open.iter()
+			.find(|f| is_linear(f))
+			.or_else(|| open.iter().find(|f| is_branching(f)))
+			.map(|f| (*f).clone());
+
+		match next {
+			Some(f) => {
+				self.expanded.push(f.clone());
+				let b = self.clone();
+				expand(&f, |fs| b.with(fs).refute()).unwrap()
+			}
+			None => Err(vec!(OpenBranch { formulas: self.known, saturated: true }))
+		}
+	}
+}
+
+/// Returns the step deriving `False` immediately, if the known formulas are
+/// contradictory on their face.
+pub fn closure(known: &[Form]) -> Option<Deduction> {
+	if known.iter().any(|f| **f == Formula::False) {
+		return Some(Deduction::EmptyStep);
+	}
+
+	for f in known.iter() {
+		if let Formula::Not(g) = &**f {
+			if **g == Formula::True {
+				return Some(Deduction::Sequence(vec!(
+					Deduction::TrueIntro,
+					Deduction::NotExtract(g.clone()))));
+			}
+			if known.contains(g) {
+				return Some(Deduction::NotExtract(g.clone()));
+			}
+		}
+	}
+
+	None
+}
+
+/// Formulas whose tableau rule does not split the branch.
+pub fn is_linear(f: &Formula) -> bool {
+	match f {
+		Formula::And(_, _) => true,
+		Formula::Not(g) => matches!(**g,
+			Formula::Not(_) | Formula::Or(_, _) | Formula::Implies(_, _)),
+		_ => false,
+	}
+}
+
+/// Formulas whose tableau rule splits the branch in two.
+pub fn is_branching(f: &Formula) -> bool {
+	match f {
+		Formula::Or(_, _) | Formula::Implies(_, _) | Formula::IFF(_, _) => true,
+		Formula::Not(g) => matches!(**g, Formula::And(_, _) | Formula::IFF(_, _)),
+		_ => false,
+	}
+}
+
+/// Applies the propositional tableau rule for `f`, a formula known in the
+/// current context. `refute` is called with the formulas added to each
+/// resulting branch and must derive `False` in that extended context.
+/// Returns `None` if `f` is an atom.
+pub fn expand<F>(f: &Form, mut refute: F) -> Option<Refutation>
+	where F: FnMut(Vec<Form>) -> Refutation
+{
+	let r = match &**f {
+		Formula::And(a, b) => refute(vec!(a.clone(), b.clone())).map(|rest|
+			Deduction::Sequence(vec!(
+				Deduction::AndExtract(a.clone(), b.clone()),
+				rest))),
+		Formula::Or(a, b) => {
+			let left = refute(vec!(a.clone()));
+			let right = refute(vec!(b.clone()));
+			both(left, right, |l, r| Deduction::OrExtract(
+				(a.clone(), Ptr::new(l)),
+				(b.clone(), Ptr::new(r)),
+				Formula::False.ptr()))
+		}
+		Formula::Implies(a, b) => {
+			let left = refute(vec!(a.clone(), b.clone()));
+			let right = refute(vec!(not(a)));
+			both(left, right, |l, r| Deduction::Sequence(vec!(
+				Deduction::NotIntro(a.clone(), seq(vec!(
+					Deduction::ImplyExtract(a.clone(), b.clone()),
+					l))),
+				r)))
+		}
+		Formula::IFF(a, b) => {
+			let left = refute(vec!(a.clone(), b.clone()));
+			let right = refute(vec!(not(a), not(b)));
+			both(left, right, |l, r| Deduction::Sequence(vec!(
+				Deduction::NotIntro(a.clone(), seq(vec!(
+					Deduction::IFFExtract(a.clone(), b.clone()),
+					l))),
+				Deduction::NotIntro(b.clone(), seq(vec!(
+					Deduction::IFFExtract(a.clone(), b.clone()),
+					Deduction::NotExtract(a.clone())))),
+				r)))
+		}
+		Formula::Not(g) => return expand_negated(g, refute),
+		_ => return None,
+	};
+
+	Some(r)
+}
+
+fn expand_negated<F>(g: &Form, mut refute: F) -> Option<Refutation>
+	where F: FnMut(Vec<Form>) -> Refutation
+{
+	let r = match &**g {
+		Formula::Not(a) => refute(vec!(a.clone())).map(|rest|
+			Deduction::Sequence(vec!(
+				Deduction::Contradiction(a.clone(),
+					Ptr::new(Deduction::NotExtract(not(a)))),
+				rest))),
+		Formula::Or(a, b) => refute(vec!(not(a), not(b))).map(|rest| {
+			let refute_or = |x: &Form| Deduction::NotIntro(x.clone(), seq(vec!(
+				Deduction::OrIntro(a.clone(), b.clone()),
+				Deduction::NotExtract(g.clone()))));
+			Deduction::Sequence(vec!(refute_or(a), refute_or(b), rest))
+		}),
+		Formula::Implies(a, b) => refute(vec!(a.clone(), not(b))).map(|rest|
+			Deduction::Sequence(vec!(
+				Deduction::Contradiction(a.clone(), seq(vec!(
+					Deduction::ImplyIntro(a.clone(), b.clone(), seq(vec!(
+						Deduction::NotExtract(a.clone()),
+						Deduction::Contradiction(b.clone(), empty())))),
+					Deduction::NotExtract(g.clone())))),
+				Deduction::NotIntro(b.clone(), seq(vec!(
+					Deduction::ImplyIntro(a.clone(), b.clone(), empty()),
+					Deduction::NotExtract(g.clone())))),
+				rest))),
+		Formula::And(a, b) => {
+			let left = refute(vec!(a.clone(), not(b)));
+			let right = refute(vec!(not(a)));
+			both(left, right, |l, r| Deduction::Sequence(vec!(
+				Deduction::NotIntro(a.clone(), seq(vec!(
+					Deduction::NotIntro(b.clone(), seq(vec!(
+						Deduction::AndIntro(a.clone(), b.clone()),
+						Deduction::NotExtract(g.clone())))),
+					l))),
+				r)))
+		}
+		Formula::IFF(a, b) => {
+			let left = refute(vec!(a.clone(), not(b)));
+			let right = refute(vec!(not(a), b.clone()));
+			both(left, right, |l, r| Deduction::Sequence(vec!(
+				Deduction::NotIntro(a.clone(), seq(vec!(
+					Deduction::NotIntro(b.clone(), seq(vec!(
+						Deduction::IFFIntro((a.clone(), empty()), (b.clone(), empty())),
+						Deduction::NotExtract(g.clone())))),
+					l))),
+				Deduction::Contradiction(b.clone(), seq(vec!(
+					Deduction::IFFIntro(
+						(a.clone(), seq(vec!(
+							Deduction::NotExtract(a.clone()),
+							Deduction::Contradiction(b.clone(), empty())))),
+						(b.clone(), seq(vec!(
+							Deduction::NotExtract(b.clone()),
+							Deduction::Contradiction(a.clone(), empty()))))),
+					Deduction::NotExtract(g.clone())))),
+				r)))
+		}
+		_ => return None,
+	};
+
+	Some(r)
+}
@@ -0,0 +1,631 @@
+//! Rendering of deductions as Fitch-style listings and `bussproofs` trees.
+//!
+//! A `Deduction` records which rules were applied but not which earlier
+//! lines they used, so the premises of each step are recovered from what
+//! the rule needs, as the checker looks for them. Premises which are
+//! neither given nor derived come from the knowledge base the deduction
+//! was checked against, and are cited without a line number. Chains of
+//! `Calc` links are rendered as one step per link. A box whose conclusion
+//! is a line outside it reiterates that line, so that steps always cite the
+//! box itself.
+
+use std::fmt::Write;
+
+use deduction::{Deduction, CalcRel};
+use formula::{Formula, ConstName};
+use pretty::{self, Style};
+use Ptr;
+
+/// Renders the deduction as a numbered Fitch-style listing, with the
+/// premises first and a nested box for each subproof. `Style::Latex` is
+/// rendered as `Style::Ascii`.
+pub fn fitch(d: &Deduction, premises: &[Formula], style: Style) -> String {
+	let style = if style == Style::Latex { Style::Ascii } else { style };
+	let steps = Walker::new(premises).steps(d);
+
+	let mut f = Fitch { style, rows: vec!(), scopes: vec!(vec!()), next: 1 };
+	for p in premises.iter() {
+		f.line(0, p, Rule::Premise.label(style).to_string());
+	}
+	if !premises.is_empty() {
+		f.rows.push(Row::Bar(0));
+	}
+	f.steps(&steps, 0);
+	f.finish()
+}
+
+/// Renders the derivation of `goal` as a LaTeX `bussproofs` tree, with the
+/// premises as leaves and discharged assumptions numbered.
+pub fn bussproofs(d: &Deduction, premises: &[Formula], goal: &Formula) -> Result<String, String> {
+	let steps = Walker::new(premises).steps(d);
+
+	let mut t = Trees { scopes: vec!(vec!()), next: 1 };
+	for p in premises.iter() {
+		t.scopes[0].push((p.clone(), Ptr::new(Tree::Leaf(p.clone(), None))));
+	}
+	t.steps(&steps);
+	let tree = match t.lookup(goal) {
+		Some(tree) => tree,
+		None => return Err(format!("Render: Did not deduce {}", pretty::unicode(goal))),
+	};
+
+	let mut out = String::from("\\begin{prooftree}\n");
+	tree.write(&mut out);
+	out.push_str("\\end{prooftree}\n");
+	Ok(out)
+}
+
+/// What a deduction takes from the context it is checked in.
+pub struct Uses {
+	/// The formulas it uses without deriving them.
+	pub formulas: Vec<Formula>,
+	/// The theorems it names through `ForAllBy`.
+	pub theorems: Vec<ConstName>,
+}
+
+/// Finds what the deduction uses from its context. `context` is searched
+/// for the generalizations `ForAllMatch` instantiates.
+pub fn uses(d: &Deduction, context: &[Formula]) -> Uses {
+	let steps = Walker::new(context).steps(d);
+	let mut u = Uses { formulas: vec!(), theorems: vec!() };
+	u.steps(&steps, &mut vec!(vec!()));
+	u
+}
+
+impl Uses {
+	fn cite(&mut self, f: &Formula, scopes: &[Vec<Formula>]) {
+		if !scopes.iter().any(|s| s.contains(f)) && !self.formulas.contains(f) {
+			self.formulas.push(f.clone());
+		}
+	}
+
+	fn steps(&mut self, steps: &[Step], scopes: &mut Vec<Vec<Formula>>) {
+		for s in steps.iter() {
+			s.premises.iter().for_each(|p| self.cite(p, scopes));
+			if let (Rule::AllE, Some(c)) = (s.rule, &s.note) {
+				if !self.theorems.contains(c) {
+					self.theorems.push(c.clone());
+				}
+			}
+			for frame in s.frames.iter() {
+				scopes.push(frame.assumption.iter().cloned().collect());
+				self.steps(&frame.steps, scopes);
+				self.cite(&frame.conclusion, scopes);
+				scopes.pop();
+			}
+			scopes.last_mut().unwrap().push(s.conclusion.clone());
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Rule {
+	Premise,
+	Assumption,
+	AndI,
+	AndE,
+	OrI,
+	OrE,
+	ImpI,
+	ImpE,
+	NotI,
+	NotE,
+	IffI,
+	IffE,
+	EqI,
+	EqE,
+	AllI,
+	AllE,
+	ExI,
+	ExE,
+	TrueI,
+	Raa,
+	Subst,
+	Def,
+	Calc,
+	Reit,
+}
+
+impl Rule {
+	fn label(self, style: Style) -> &'static str {
+		let labels = match self {
+			Rule::Premise => ("premise", "premise", "\\mathrm{premise}"),
+			Rule::Assumption => ("assumption", "assumption", "\\mathrm{assumption}"),
+			Rule::AndI => ("∧I", "&I", "\\land I"),
+			Rule::AndE => ("∧E", "&E", "\\land E"),
+			Rule::OrI => ("∨I", "|I", "\\lor I"),
+			Rule::OrE => ("∨E", "|E", "\\lor E"),
+			Rule::ImpI => ("→I", "->I", "\\to I"),
+			Rule::ImpE => ("→E", "->E", "\\to E"),
+			Rule::NotI => ("¬I", "~I", "\\neg I"),
+			Rule::NotE => ("¬E", "~E", "\\neg E"),
+			Rule::IffI => ("↔I", "<->I", "\\leftrightarrow I"),
+			Rule::IffE => ("↔E", "<->E", "\\leftrightarrow E"),
+			Rule::EqI => ("=I", "=I", "=I"),
+			Rule::EqE => ("=E", "=E", "=E"),
+			Rule::AllI => ("∀I", "AI", "\\forall I"),
+			Rule::AllE => ("∀E", "AE", "\\forall E"),
+			Rule::ExI => ("∃I", "EI", "\\exists I"),
+			Rule::ExE => ("∃E", "EE", "\\exists E"),
+			Rule::TrueI => ("⊤I", "TI", "\\top I"),
+			Rule::Raa => ("RAA", "RAA", "\\mathrm{RAA}"),
+			Rule::Subst => ("sub", "sub", "\\mathrm{sub}"),
+			Rule::Def => ("def", "def", "\\mathrm{def}"),
+			Rule::Calc => ("calc", "calc", "\\mathrm{calc}"),
+			Rule::Reit => ("R", "R", "\\mathrm{R}"),
+		};
+		match style {
+			Style::Unicode => labels.0,
+			Style::Ascii => labels.1,
+			Style::Latex => labels.2,
+		}
+	}
+}
+
+/// A step concluding `conclusion` from earlier formulas and subproofs.
+struct Step {
+	conclusion: Formula,
+	rule: Rule,
+	/// A constant the rule introduces or the theorem it uses.
+	note: Option<ConstName>,
+	premises: Vec<Formula>,
+	frames: Vec<Frame>,
+}
+
+/// A subproof deducing `conclusion` from an assumption, or for a fresh
+/// constant.
+struct Frame {
+	assumption: Option<Formula>,
+	fresh: Option<ConstName>,
+	steps: Vec<Step>,
+	conclusion: Formula,
+}
+
+/// Recovers the steps of a deduction, keeping track of what is derived in
+/// each scope.
+struct Walker {
+	scopes: Vec<Vec<Formula>>,
+	/// The formulas named with `Name` in each scope.
+	names: Vec<Vec<(ConstName, Formula)>>,
+}
+
+impl Walker {
+	fn new(premises: &[Formula]) -> Walker {
+		Walker { scopes: vec!(premises.to_vec()), names: vec!(vec!()) }
+	}
+
+	fn named(&self, c: &ConstName) -> Option<Formula> {
+		self.names.iter().rev()
+			.flat_map(|s| s.iter().rev())
+			.find(|(n, _)| n == c)
+			.map(|(_, f)| f.clone())
+	}
+
+	fn steps(&mut self, d: &Deduction) -> Vec<Step> {
+		let mut out = vec!();
+		self.walk(d, &mut out);
+		out
+	}
+
+	fn known(&self, f: &Formula) -> bool {
+		self.scopes.iter().any(|s| s.contains(f))
+	}
+
+	fn push(&mut self, out: &mut Vec<Step>, step: Step) {
+		self.scopes.last_mut().unwrap().push(step.conclusion.clone());
+		out.push(step);
+	}
+
+	fn step(&mut self, out: &mut Vec<Step>, conclusion: Formula, rule: Rule, premises: Vec<Formula>) {
+		self.push(out, Step { conclusion, rule, note: None, premises, frames: vec!() });
+	}
+
+	fn frame(&mut self, assumption: Option<Formula>, fresh: Option<ConstName>, d: &Deduction, conclusion: Formula)
+	-> Frame {
+		self.scopes.push(assumption.iter().cloned().collect());
+		self.names.push(vec!());
+		let steps = self.steps(d);
+		self.names.pop();
+		self.scopes.pop();
+		Frame { assumption, fresh, steps, conclusion }
+	}
+
+	fn walk(&mut self, d: &Deduction, out: &mut Vec<Step>) {
+		match d {
+			Deduction::EmptyStep => (),
+			Deduction::Sequence(v) => v.iter().for_each(|d| self.walk(d, out)),
+			Deduction::AndIntro(a, b) =>
+				self.step(out, Formula::And(a.clone(), b.clone()), Rule::AndI, vec!((**a).clone(), (**b).clone())),
+			Deduction::AndExtract(a, b) => {
+				let and = Formula::And(a.clone(), b.clone());
+				self.step(out, (**a).clone(), Rule::AndE, vec!(and.clone()));
+				self.step(out, (**b).clone(), Rule::AndE, vec!(and));
+			}
+			Deduction::OrIntro(a, b) => {
+				let from = if self.known(a) || !self.known(b) { a } else { b };
+				self.step(out, Formula::Or(a.clone(), b.clone()), Rule::OrI, vec!((**from).clone()));
+			}
+			Deduction::OrExtract((a, w1), (b, w2), c) => {
+				let frames = vec!(
+					self.frame(Some((**a).clone()), None, w1, (**c).clone()),
+					self.frame(Some((**b).clone()), None, w2, (**c).clone()),
+				);
+				let premises = vec!(Formula::Or(a.clone(), b.clone()));
+				self.push(out, Step { conclusion: (**c).clone(), rule: Rule::OrE, note: None, premises, frames });
+			}
+			Deduction::ImplyIntro(a, b, w) => {
+				let frames = vec!(self.frame(Some((**a).clone()), None, w, (**b).clone()));
+				let conclusion = Formula::Implies(a.clone(), b.clone());
+				self.push(out, Step { conclusion, rule: Rule::ImpI, note: None, premises: vec!(), frames });
+			}
+			Deduction::ImplyExtract(a, b) =>
+				self.step(out, (**b).clone(), Rule::ImpE, vec!(Formula::Implies(a.clone(), b.clone()), (**a).clone())),
+			Deduction::NotIntro(a, w) => {
+				let frames = vec!(self.frame(Some((**a).clone()), None, w, Formula::False));
+				let conclusion = Formula::Not(a.clone());
+				self.push(out, Step { conclusion, rule: Rule::NotI, note: None, premises: vec!(), frames });
+			}
+			Deduction::NotExtract(a) =>
+				self.step(out, Formula::False, Rule::NotE, vec!((**a).clone(), Formula::Not(a.clone()))),
+			Deduction::Contradiction(a, w) => {
+				let frames = vec!(self.frame(Some(Formula::Not(a.clone())), None, w, Formula::False));
+				self.push(out, Step { conclusion: (**a).clone(), rule: Rule::Raa, note: None, premises: vec!(), frames });
+			}
+			Deduction::IFFIntro((a, w1), (b, w2)) => {
+				let frames = vec!(
+					self.frame(Some((**a).clone()), None, w1, (**b).clone()),
+					self.frame(Some((**b).clone()), None, w2, (**a).clone()),
+				);
+				let conclusion = Formula::IFF(a.clone(), b.clone());
+				self.push(out, Step { conclusion, rule: Rule::IffI, note: None, premises: vec!(), frames });
+			}
+			Deduction::IFFExtract(a, b) => {
+				let iff = Formula::IFF(a.clone(), b.clone());
+				let (from, to) = if self.known(a) || !self.known(b) { (a, b) } else { (b, a) };
+				self.step(out, (**to).clone(), Rule::IffE, vec!(iff, (**from).clone()));
+			}
+			Deduction::EqualityIntro(t) => self.step(out, Formula::Eq(t.clone(), t.clone()), Rule::EqI, vec!()),
+			Deduction::Substitution(l, r, c, sub) => {
+				let premises = vec!(Formula::Eq(l.clone(), r.clone()), sub.substitute(c, l));
+				self.step(out, sub.substitute(c, r), Rule::EqE, premises);
+			}
+			Deduction::SubstReduce(body, c, t) => {
+				let premises = vec!(Formula::Subst(body.clone(), c.clone(), t.clone()));
+				self.step(out, body.substitute(c, t), Rule::Subst, premises);
+			}
+			Deduction::ForAllExtract((v, body), t) => {
+				let premises = vec!(Formula::ForAll(v.clone(), body.clone()));
+				self.step(out, body.substitute(v, t), Rule::AllE, premises);
+			}
+			Deduction::ForAllSeqExtract((ar, v, body), ts) => {
+				let premises = vec!(Formula::ForAllSeq(*ar, v.clone(), body.clone()));
+				self.step(out, body.substitute_seq(*ar, v, ts), Rule::AllE, premises);
+			}
+			Deduction::ForAllMatch(thm) => {
+				let general = self.scopes.iter().rev()
+					.flat_map(|s| s.iter().rev())
+					.find(|f| Deduction::instantiate(f, thm).is_some())
+					.cloned();
+				self.step(out, (**thm).clone(), Rule::AllE, general.into_iter().collect());
+			}
+			Deduction::ForAllBy(c, thm) => match self.named(c) {
+				Some(general) => self.step(out, (**thm).clone(), Rule::AllE, vec!(general)),
+				None => self.push(out, Step {
+					conclusion: (**thm).clone(), rule: Rule::AllE, note: Some(c.clone()), premises: vec!(), frames: vec!(),
+				}),
+			},
+			Deduction::ForAllIntro((v, body), w) => {
+				let instance = body.substitute(v, &Formula::Const(v.clone()));
+				let frames = vec!(self.frame(None, Some(v.clone()), w, instance));
+				let conclusion = Formula::ForAll(v.clone(), body.clone());
+				self.push(out, Step { conclusion, rule: Rule::AllI, note: None, premises: vec!(), frames });
+			}
+			Deduction::ExistsIntro((v, body), t) =>
+				self.step(out, Formula::Exists(v.clone(), body.clone()), Rule::ExI, vec!(body.substitute(v, t))),
+			Deduction::ExistsExtract((v, body), c) => self.push(out, Step {
+				conclusion: body.substitute(v, &Formula::Const(c.clone())),
+				rule: Rule::ExE,
+				note: Some(c.clone()),
+				premises: vec!(Formula::Exists(v.clone(), body.clone())),
+				frames: vec!(),
+			}),
+			Deduction::Let(c, vars, t) => {
+				let def = if vars.is_empty() {
+					Formula::Eq(Formula::Const(c.clone()).ptr(), t.clone())
+				} else {
+					let vs = [std::slice::from_ref(c), vars.as_slice()].concat();
+					let eq = Formula::Eq(Formula::const_to_relation(&vs).ptr(), t.clone());
+					vars.iter().fold(eq, |acc, x| Formula::ForAll(x.clone(), acc.ptr()))
+				};
+				self.push(out, Step { conclusion: def, rule: Rule::Def, note: Some(c.clone()), premises: vec!(), frames: vec!() });
+			}
+			Deduction::TrueIntro => self.step(out, Formula::True, Rule::TrueI, vec!()),
+			Deduction::Calc(start, links) => {
+				let mut rel = CalcRel::Eq;
+				let mut chain = Formula::Eq(start.clone(), start.clone());
+				let mut prev = start.clone();
+				if links.is_empty() {
+					self.step(out, chain, Rule::EqI, vec!());
+					return;
+				}
+				for (i, (r, next, w)) in links.iter().enumerate() {
+					self.walk(w, out);
+					let link = r.relate(prev.clone(), next.clone());
+					rel = rel.compose(*r);
+					if i > 0 {
+						let composed = rel.relate(start.clone(), next.clone());
+						self.step(out, composed.clone(), Rule::Calc, vec!(chain, link.clone()));
+						chain = composed;
+					} else {
+						chain = link;
+					}
+					prev = next.clone();
+				}
+			}
+			Deduction::Name(c, f) => self.names.last_mut().unwrap().push((c.clone(), (**f).clone())),
+		}
+	}
+}
+
+enum Row {
+	Line(usize, usize, String, String),
+	/// The rule under the premises or the assumption of a box.
+	Bar(usize),
+	/// The constant a `∀I` box is about.
+	Fresh(usize, String),
+}
+
+struct Fitch {
+	style: Style,
+	rows: Vec<Row>,
+	/// The numbered lines visible in each open box.
+	scopes: Vec<Vec<(Formula, usize)>>,
+	next: usize,
+}
+
+impl Fitch {
+	fn line(&mut self, depth: usize, f: &Formula, justification: String) -> usize {
+		let n = self.next;
+		self.next += 1;
+		let text = pretty::formula(f, pretty::Options::new(self.style));
+		self.rows.push(Row::Line(n, depth, text, justification));
+		self.scopes.last_mut().unwrap().push((f.clone(), n));
+		n
+	}
+
+	fn lookup(&self, f: &Formula) -> Option<usize> {
+		self.scopes.iter().rev()
+			.flat_map(|s| s.iter().rev())
+			.find(|(g, _)| g == f)
+			.map(|(_, n)| *n)
+	}
+
+	fn steps(&mut self, steps: &[Step], depth: usize) {
+		for s in steps.iter() {
+			let mut refs: Vec<String> = s.premises.iter()
+				.filter_map(|p| self.lookup(p))
+				.map(|n| n.to_string())
+				.collect();
+			for frame in s.frames.iter() {
+				refs.push(self.frame(frame, depth + 1));
+			}
+
+			let mut justification = s.rule.label(self.style).to_string();
+			if !refs.is_empty() {
+				justification = format!("{} {}", justification, refs.join(", "));
+			}
+			if let Some(c) = &s.note {
+				justification = format!("{} ({})", justification, pretty::name(c, self.style));
+			}
+			self.line(depth, &s.conclusion, justification);
+		}
+	}
+
+	/// Renders a box and returns the range of lines it spans.
+	fn frame(&mut self, frame: &Frame, depth: usize) -> String {
+		let first = self.next;
+		self.scopes.push(vec!());
+		if let Some(c) = &frame.fresh {
+			self.rows.push(Row::Fresh(depth, pretty::name(c, self.style)));
+		}
+		if let Some(a) = &frame.assumption {
+			self.line(depth, a, Rule::Assumption.label(self.style).to_string());
+		}
+		self.rows.push(Row::Bar(depth));
+		self.steps(&frame.steps, depth);
+		let inside = self.scopes.last().unwrap().iter().any(|(g, _)| *g == frame.conclusion);
+		if let (false, Some(n)) = (inside, self.lookup(&frame.conclusion)) {
+			let justification = format!("{} {}", Rule::Reit.label(self.style), n);
+			self.line(depth, &frame.conclusion, justification);
+		}
+		self.scopes.pop();
+
+		let dash = if self.style == Style::Unicode { "–" } else { "-" };
+		let last = self.next - 1;
+		if last > first {
+			format!("{}{}{}", first, dash, last)
+		} else if last == first {
+			first.to_string()
+		} else {
+			String::new()
+		}
+	}
+
+	fn finish(&self) -> String {
+		let (bar, rule) = match self.style {
+			Style::Unicode => ("│ ", "├───"),
+			_ => ("| ", "|---"),
+		};
+		let number = self.next.saturating_sub(1).to_string().len();
+		let margin = |depth: usize| format!("{} ", " ".repeat(number)) + &bar.repeat(depth);
+
+		let left: Vec<String> = self.rows.iter().map(|r| match r {
+			Row::Line(n, depth, text, _) => format!("{:>w$} {}{}", n, bar.repeat(depth + 1), text, w = number),
+			Row::Bar(depth) => format!("{}{}", margin(*depth), rule),
+			Row::Fresh(depth, c) => format!("{}{}{}", margin(*depth), bar, c),
+		}).collect();
+		let width = left.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+		let mut out = String::new();
+		for (l, r) in left.iter().zip(self.rows.iter()) {
+			match r {
+				Row::Line(_, _, _, j) => {
+					let pad = width - l.chars().count();
+					let _ = writeln!(out, "{}{}   {}", l, " ".repeat(pad), j);
+				}
+				_ => {
+					let _ = writeln!(out, "{}", l);
+				}
+			}
+		}
+		out
+	}
+}
+
+enum Tree {
+	/// A premise, or an assumption with the number of the rule discharging it.
+	Leaf(Formula, Option<usize>),
+	Node(Formula, String, Vec<Ptr<Tree>>),
+}
+
+impl Tree {
+	fn write(&self, out: &mut String) {
+		let math = |f: &Formula| pretty::latex(f);
+		match self {
+			Tree::Leaf(f, None) => {
+				let _ = writeln!(out, "\\AxiomC{{${}$}}", math(f));
+			}
+			Tree::Leaf(f, Some(n)) => {
+				let _ = writeln!(out, "\\AxiomC{{$[{}]^{{{}}}$}}", math(f), n);
+			}
+			Tree::Node(f, label, children) => {
+				if children.is_empty() {
+					out.push_str("\\AxiomC{}\n");
+				}
+				children.iter().for_each(|c| c.write(out));
+				let _ = writeln!(out, "\\RightLabel{{${}$}}", label);
+				let inference = match children.len() {
+					0 | 1 => "Unary",
+					2 => "Binary",
+					_ => "Trinary",
+				};
+				let _ = writeln!(out, "\\{}InfC{{${}$}}", inference, math(f));
+			}
+		}
+	}
+}
+
+/// The trees deducing the formulas visible in each open subproof.
+struct Trees {
+	scopes: Vec<Vec<(Formula, Ptr<Tree>)>>,
+	next: usize,
+}
+
+impl Trees {
+	fn lookup(&self, f: &Formula) -> Option<Ptr<Tree>> {
+		self.scopes.iter().rev()
+			.flat_map(|s| s.iter().rev())
+			.find(|(g, _)| g == f)
+			.map(|(_, t)| t.clone())
+	}
+
+	fn tree(&self, f: &Formula) -> Ptr<Tree> {
+		self.lookup(f).unwrap_or_else(|| Ptr::new(Tree::Leaf(f.clone(), None)))
+	}
+
+	fn steps(&mut self, steps: &[Step]) {
+		for s in steps.iter() {
+			let mut children: Vec<Ptr<Tree>> = s.premises.iter().map(|p| self.tree(p)).collect();
+			let mut discharged = vec!();
+			for frame in s.frames.iter() {
+				self.scopes.push(vec!());
+				if let Some(a) = &frame.assumption {
+					let n = self.next;
+					self.next += 1;
+					discharged.push(n.to_string());
+					self.scopes.last_mut().unwrap().push((a.clone(), Ptr::new(Tree::Leaf(a.clone(), Some(n)))));
+				}
+				self.steps(&frame.steps);
+				children.push(self.tree(&frame.conclusion));
+				self.scopes.pop();
+			}
+
+			let mut label = s.rule.label(Style::Latex).to_string();
+			if !discharged.is_empty() {
+				label = format!("{}^{{{}}}", label, discharged.join(","));
+			}
+			if let Some(c) = &s.note {
+				label = format!("{}\\ ({})", label, pretty::name(c, Style::Latex));
+			}
+			let node = Tree::Node(s.conclusion.clone(), label, children);
+			self.scopes.last_mut().unwrap().push((s.conclusion.clone(), Ptr::new(node)));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use formula::Form;
+
+	fn atom(s: &str) -> Form {
+		Formula::Const(ConstName::String(Ptr::new(s.to_string()))).ptr()
+	}
+
+	fn not(f: &Form) -> Form { Formula::Not(f.clone()).ptr() }
+
+	#[test]
+	fn fitch_listing_in_ascii() {
+		let (p, q) = (atom("p"), atom("q"));
+		let d = Deduction::Sequence(vec!(
+			Deduction::AndExtract(p.clone(), q.clone()),
+			Deduction::AndIntro(q.clone(), p.clone())));
+		assert_eq!(fitch(&d, &[Formula::And(p, q)], Style::Ascii), concat!(
+			"1 | p & q   premise\n",
+			"  |---\n",
+			"2 | p       &E 1\n",
+			"3 | q       &E 1\n",
+			"4 | q & p   &I 3, 2\n"));
+	}
+
+	#[test]
+	fn box_concluding_an_outer_line_reiterates_it() {
+		let (p, q) = (atom("p"), atom("q"));
+		let d = Deduction::ImplyIntro(p, q.clone(), Ptr::new(Deduction::EmptyStep));
+		assert_eq!(fitch(&d, &[(*q).clone()], Style::Unicode), concat!(
+			"1 │ q       premise\n",
+			"  ├───\n",
+			"2 │ │ p     assumption\n",
+			"  │ ├───\n",
+			"3 │ │ q     R 1\n",
+			"4 │ p → q   →I 2–3\n"));
+	}
+
+	#[test]
+	fn raa_cites_its_box() {
+		let p = atom("p");
+		let d = Deduction::Contradiction(p.clone(), Ptr::new(Deduction::NotExtract(p.clone())));
+		assert_eq!(fitch(&d, &[(*p).clone(), (*not(&p)).clone()], Style::Unicode), concat!(
+			"1 │ p      premise\n",
+			"2 │ ¬p     premise\n",
+			"  ├───\n",
+			"3 │ │ ¬p   assumption\n",
+			"  │ ├───\n",
+			"4 │ │ ⊥    ¬E 1, 3\n",
+			"5 │ p      RAA 3–4\n"));
+	}
+
+	#[test]
+	fn bussproofs_numbers_discharged_assumptions() {
+		let p = atom("p");
+		let d = Deduction::ImplyIntro(p.clone(), p.clone(), Ptr::new(Deduction::EmptyStep));
+		let goal = Formula::Implies(p.clone(), p);
+		assert_eq!(bussproofs(&d, &[], &goal).unwrap(), concat!(
+			"\\begin{prooftree}\n",
+			"\\AxiomC{$[p]^{1}$}\n",
+			"\\RightLabel{$\\to I^{1}$}\n",
+			"\\UnaryInfC{$p \\to p$}\n",
+			"\\end{prooftree}\n"));
+		assert!(bussproofs(&d, &[], &Formula::Not(goal.ptr())).is_err());
+	}
+}
@@ -0,0 +1,372 @@
+//! Term rewriting with equations and equivalences as rewrite rules.
+//!
+//! A rule is a theorem `ForAll(x1, ... ForAll(xn, Eq(l, r)))`, or the same
+//! with `IFF`, used from left to right with the quantified variables as
+//! pattern variables. `Eq` rules rewrite any subformula and are justified by
+//! a `Substitution` step. `IFF` rules rewrite subformulas below
+//! propositional connectives only, by lifting the instance to the whole
+//! formula with `prover::tautology` and applying `IFFExtract`.
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::Deduction;
+use knowledge_base::KnowledgeBase;
+use prover::tautology;
+use unify::{self, Vars};
+
+/// An oriented equation or equivalence.
+#[derive(Clone)]
+pub struct Rule {
+	/// The theorem the rule comes from.
+	pub theorem: Form,
+	pub vars: Vec<ConstName>,
+	pub lhs: Form,
+	pub rhs: Form,
+	/// Whether the rule is an equivalence rather than an equation.
+	pub iff: bool,
+}
+
+impl Rule {
+	/// Reads a rule from a theorem. Returns `None` if the theorem is not a
+	/// quantified `Eq` or `IFF`, if its left side is a bare variable, or if
+	/// its right side has variables the left side does not bind.
+	pub fn new(theorem: &Form) -> Option<Rule> {
+		let mut vars = vec!();
+		let mut body = theorem;
+		while let Formula::ForAll(v, e) = &**body {
+			vars.push(v.clone());
+			body = e;
+		}
+
+		let (lhs, rhs, iff) = match &**body {
+			Formula::Eq(l, r) => (l, r, false),
+			Formula::IFF(l, r) => (l, r, true),
+			_ => return None,
+		};
+		if let Formula::Free(v) = &**lhs {
+			if vars.contains(v) {
+				return None;
+			}
+		}
+		if vars.iter().any(|v| occurs(rhs, v) && !occurs(lhs, v)) {
+			return None;
+		}
+
+		Some(Rule { theorem: theorem.clone(), vars, lhs: lhs.clone(), rhs: rhs.clone(), iff })
+	}
+
+	/// Rewrites `f` at its root, returning the result and the steps deducing
+	/// the instance `Eq(f, result)` or `IFF(f, result)`.
+	fn apply(&self, f: &Form) -> Option<(Form, Vec<Deduction>)> {
+		let sub = unify::matching(&self.lhs, f, &Vars::Only(self.vars.clone(), vec!()))?;
+		let to = sub.apply(&self.rhs).ptr();
+		let instance = if self.iff {
+			Formula::IFF(f.clone(), to.clone())
+		} else {
+			Formula::Eq(f.clone(), to.clone())
+		};
+
+		let steps = if self.vars.is_empty() {
+			vec!()
+		} else {
+			vec!(Deduction::instantiate(&self.theorem, &instance)?)
+		};
+		Some((to, steps))
+	}
+}
+
+/// The order in which redexes are rewritten.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+	/// Rewrite the leftmost redex with no redex below it first.
+	Innermost,
+	/// Rewrite the leftmost redex with no redex above it first.
+	Outermost,
+}
+
+/// The result of rewriting.
+pub struct Rewritten {
+	pub formula: Form,
+	/// Deduces `formula` from the original formula, or for `rewrite_term`
+	/// the equation between the original term and `formula`.
+	pub proof: Deduction,
+	/// The number of rewrite steps made.
+	pub steps: usize,
+	/// Whether no rule applies to `formula`, rather than the search having
+	/// reached its step limit.
+	pub normal: bool,
+}
+
+/// A set of rules with a strategy and a bound on the number of steps.
+pub struct Rewriter {
+	pub rules: Vec<Rule>,
+	pub strategy: Strategy,
+	pub limit: usize,
+}
+
+impl Rewriter {
+	pub fn new(rules: Vec<Rule>, strategy: Strategy, limit: usize) -> Rewriter {
+		Rewriter { rules, strategy, limit }
+	}
+
+	/// Uses every theorem of `kbase` which reads as a rule.
+	pub fn from_kbase<K: KnowledgeBase>(kbase: &K, strategy: Strategy, limit: usize) -> Rewriter {
+		let rules = kbase.theorems().into_iter()
+			.filter_map(|f| Rule::new(&f.ptr()))
+			.collect();
+		Rewriter::new(rules, strategy, limit)
+	}
+
+	/// Rewrites the formula `f`. The proof deduces the result in any context
+	/// containing `f` and the theorems of the rules.
+	pub fn rewrite(&self, f: &Form) -> Result<Rewritten, String> {
+		self.run(f, false)
+	}
+
+	/// Rewrites the term `t` with the `Eq` rules only. The proof deduces
+	/// `Eq(t, result)` in any context containing the theorems of the rules.
+	pub fn rewrite_term(&self, t: &Form) -> Result<Rewritten, String> {
+		self.run(t, true)
+	}
+
+	fn run(&self, f: &Form, term: bool) -> Result<Rewritten, String> {
+		let placeholder = ConstName::Int(self.rules.iter()
+			.map(|r| r.theorem.max_int())
+			.fold(f.max_int(), |a, b| a.max(b)) + 1);
+		let hole = Formula::Free(placeholder.clone()).ptr();
+
+		let mut current = f.clone();
+		let mut proof = if term { vec!(Deduction::EqualityIntro(f.clone())) } else { vec!() };
+		let mut steps = 0;
+		while steps < self.limit {
+			let (path, rule, to, mut instance) = match self.redex(&current, term) {
+				Some(r) => r,
+				None => return Ok(Rewritten { formula: current, proof: Deduction::Sequence(proof), steps, normal: true }),
+			};
+			let from = at(&current, &path);
+			let next = replace(&current, &path, &to);
+			proof.append(&mut instance);
+
+			if rule.iff {
+				if !path.is_empty() {
+					let lemma = tautology::prove_with_atoms(
+						&[Formula::IFF(from.clone(), to.clone()).ptr()],
+						Formula::IFF(current.clone(), next.clone()).ptr(),
+						&[from, to])
+						.ok_or_else(|| format!("Rewrite: Congruence for {} is not a tautology",
+							current.to_string()))?;
+					proof.push(lemma);
+				}
+				proof.push(Deduction::IFFExtract(current.clone(), next.clone()));
+			} else {
+				let context = replace(&current, &path, &hole);
+				let context = if term { Formula::Eq(f.clone(), context).ptr() } else { context };
+				proof.push(Deduction::Substitution(from, to, placeholder.clone(), context));
+			}
+
+			current = next;
+			steps += 1;
+		}
+
+		let normal = self.redex(&current, term).is_none();
+		Ok(Rewritten { formula: current, proof: Deduction::Sequence(proof), steps, normal })
+	}
+
+	/// Finds the next redex according to the strategy.
+	fn redex(&self, f: &Form, term: bool) -> Option<(Vec<usize>, &Rule, Form, Vec<Deduction>)> {
+		let mut path = vec!();
+		self.search(f, !term, term, &mut path)
+	}
+
+	/// `prop` tells whether `f` is reached through propositional
+	/// connectives only, so `IFF` rules may rewrite it.
+	fn search(&self, f: &Form, prop: bool, term: bool, path: &mut Vec<usize>)
+	-> Option<(Vec<usize>, &Rule, Form, Vec<Deduction>)> {
+		let here = |path: &Vec<usize>| self.rules.iter()
+			.filter(|r| if r.iff { prop && !term } else { true })
+			.find_map(|r| r.apply(f).map(|(to, d)| (path.clone(), r, to, d)));
+
+		if self.strategy == Strategy::Outermost {
+			if let Some(r) = here(path) {
+				return Some(r);
+			}
+		}
+
+		let inner = matches!(**f,
+			Formula::IFF(_, _) | Formula::And(_, _) | Formula::Or(_, _)
+			| Formula::Implies(_, _) | Formula::Not(_));
+		for (i, c) in children(f).iter().enumerate() {
+			path.push(i);
+			let r = self.search(c, prop && inner, term, path);
+			path.pop();
+			if r.is_some() {
+				return r;
+			}
+		}
+
+		if self.strategy == Strategy::Innermost {
+			return here(path);
+		}
+		None
+	}
+}
+
+/// Whether the variable occurs free in the formula.
+fn occurs(f: &Formula, v: &ConstName) -> bool {
+	f.substitute(v, &Formula::True) != *f
+}
+
+/// The immediate subformulas of `f` which may be rewritten.
+fn children(f: &Formula) -> Vec<Form> {
+	match f {
+		Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+		| Formula::Or(l, r) | Formula::Implies(l, r) => vec!(l.clone(), r.clone()),
+		Formula::Not(l) => vec!(l.clone()),
+		Formula::Relation(v) => v.iter().filter_map(|e| match e {
+			Expr::Formula(e) => Some(e.clone().ptr()),
+			_ => None,
+		}).collect(),
+		Formula::ForAll(_, e) | Formula::Exists(_, e) | Formula::ForAllSeq(_, _, e) => vec!(e.clone()),
+		Formula::Subst(body, _, sub) => vec!(body.clone(), sub.clone()),
+		Formula::Free(_) | Formula::Const(_) | Formula::True | Formula::False => vec!(),
+	}
+}
+
+/// Rebuilds `f` with new immediate subformulas, in the order of `children`.
+fn rebuild(f: &Formula, c: Vec<Form>) -> Formula {
+	match f {
+		Formula::Eq(_, _) => Formula::Eq(c[0].clone(), c[1].clone()),
+		Formula::IFF(_, _) => Formula::IFF(c[0].clone(), c[1].clone()),
+		Formula::And(_, _) => Formula::And(c[0].clone(), c[1].clone()),
+		Formula::Or(_, _) => Formula::Or(c[0].clone(), c[1].clone()),
+		Formula::Implies(_, _) => Formula::Implies(c[0].clone(), c[1].clone()),
+		Formula::Not(_) => Formula::Not(c[0].clone()),
+		Formula::Relation(v) => {
+			let mut c = c.into_iter();
+			Formula::Relation(v.iter().map(|e| match e {
+				Expr::Formula(_) => Expr::Formula((*c.next().unwrap()).clone()),
+				o => o.clone(),
+			}).collect())
+		}
+		Formula::ForAll(v, _) => Formula::ForAll(v.clone(), c[0].clone()),
+		Formula::Exists(v, _) => Formula::Exists(v.clone(), c[0].clone()),
+		Formula::ForAllSeq(a, v, _) => Formula::ForAllSeq(*a, v.clone(), c[0].clone()),
+		Formula::Subst(_, v, _) => Formula::Subst(c[0].clone(), v.clone(), c[1].clone()),
+		o => o.clone(),
+	}
+}
+
+fn at(f: &Form, path: &[usize]) -> Form {
+	match path.split_first() {
+		Some((i, rest)) => at(&children(f)[*i], rest),
+		None => f.clone(),
+	}
+}
+
+fn replace(f: &Form, path: &[usize], new: &Form) -> Form {
+	match path.split_first() {
+		Some((i, rest)) => {
+			let mut c = children(f);
+			c[*i] = replace(&c[*i], rest, new);
+			rebuild(f, c).ptr()
+		}
+		None => new.clone(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	struct Facts(Vec<Form>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.iter().any(|f| **f == *form)
+		}
+
+		fn has_const(&self, _c: &ConstName) -> bool {
+			false
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn konst(s: &str) -> Form {
+		Formula::Const(name(s)).ptr()
+	}
+
+	fn app(f: &str, args: &[&Form]) -> Form {
+		let mut v = vec!(Expr::Formula(Formula::Const(name(f))));
+		v.extend(args.iter().map(|a| Expr::Formula((***a).clone())));
+		Formula::Relation(v).ptr()
+	}
+
+	fn eq(l: &Form, r: &Form) -> Form { Formula::Eq(l.clone(), r.clone()).ptr() }
+
+	/// The rules `f(z) = b` and `a = c`, which overlap on `f(a)`.
+	fn rules() -> Vec<Form> {
+		let z = name("z");
+		let fz = app("f", &[&Formula::Free(z.clone()).ptr()]);
+		vec!(
+			Formula::ForAll(z, eq(&fz, &konst("b"))).ptr(),
+			eq(&konst("a"), &konst("c")))
+	}
+
+	/// Rewrites `f` and checks the proof with the kernel.
+	fn rewrite(f: &Form, strategy: Strategy, limit: usize) -> Rewritten {
+		let theorems = rules();
+		let rw = Rewriter::new(theorems.iter().filter_map(Rule::new).collect(), strategy, limit);
+		let r = rw.rewrite(f).unwrap();
+
+		let mut facts = theorems;
+		facts.push(f.clone());
+		assert!(r.proof.deduced(Facts(facts), &r.formula));
+		r
+	}
+
+	/// `ForAll(x, P(x, t))`.
+	fn under_binder(t: &Form) -> Form {
+		let x = name("x");
+		Formula::ForAll(x.clone(), app("P", &[&Formula::Free(x).ptr(), t])).ptr()
+	}
+
+	#[test]
+	fn innermost_rewrites_below_binders() {
+		let f = under_binder(&app("f", &[&konst("a")]));
+		let r = rewrite(&f, Strategy::Innermost, 10);
+		assert!(r.formula == under_binder(&konst("b")));
+		assert!(r.steps == 2);
+		assert!(r.normal);
+	}
+
+	#[test]
+	fn outermost_rewrites_below_binders() {
+		let f = under_binder(&app("f", &[&konst("a")]));
+		let r = rewrite(&f, Strategy::Outermost, 10);
+		assert!(r.formula == under_binder(&konst("b")));
+		assert!(r.steps == 1);
+		assert!(r.normal);
+	}
+
+	#[test]
+	fn rewrites_terms_of_the_bound_variable() {
+		let x = name("x");
+		let fx = app("f", &[&Formula::Free(x.clone()).ptr()]);
+		let f = Formula::ForAll(x.clone(), app("P", &[&fx])).ptr();
+		let r = rewrite(&f, Strategy::Innermost, 10);
+		assert!(r.formula == Formula::ForAll(x, app("P", &[&konst("b")])).ptr());
+		assert!(r.normal);
+	}
+
+	#[test]
+	fn step_limit_is_not_a_normal_form() {
+		let f = under_binder(&app("f", &[&konst("a")]));
+		let r = rewrite(&f, Strategy::Innermost, 1);
+		assert!(r.formula == under_binder(&app("f", &[&konst("c")])));
+		assert!(r.steps == 1);
+		assert!(!r.normal);
+	}
+}
@@ -0,0 +1,462 @@
+//! Propositional satisfiability: Tseitin encoding, DIMACS output and a
+//! conflict driven clause learning solver.
+//!
+//! Formulas are read as in `prover::tautology`: `True`, `False`, `And`,
+//! `Or`, `Not`, `Implies` and `IFF` are connectives and every other formula
+//! is an atom compared structurally. Unlike the provers, the solver decides
+//! on its own authority and produces no deduction, so it is meant for
+//! rejecting wrong goals quickly rather than for proving them.
+
+use std::fmt;
+
+use formula::{Formula, Form};
+
+/// A formula in conjunctive normal form over numbered variables.
+pub struct Cnf {
+	/// The atoms of the formula; atom `i` is variable `i + 1`.
+	pub atoms: Vec<Form>,
+	/// The number of variables, including those defining subformulas.
+	pub vars: usize,
+	/// Clauses of non-zero DIMACS literals.
+	pub clauses: Vec<Vec<i64>>,
+}
+
+impl Cnf {
+	/// The clauses in DIMACS format, with the atoms in comments.
+	pub fn dimacs(&self) -> String {
+		let mut out = String::new();
+		for (i, a) in self.atoms.iter().enumerate() {
+			out.push_str(&format!("c {} {}\n", i + 1, a.to_string()));
+		}
+		out.push_str(&format!("p cnf {} {}\n", self.vars, self.clauses.len()));
+		for c in self.clauses.iter() {
+			for l in c.iter() {
+				out.push_str(&format!("{} ", l));
+			}
+			out.push_str("0\n");
+		}
+		out
+	}
+
+	/// Searches for a model of the clauses, returning the truth value of
+	/// every variable. Fails if a clause has a literal which is not one of
+	/// the variables.
+	pub fn solve(&self) -> Result<Option<Vec<bool>>, String> {
+		let mut s = Solver::new(self.vars);
+		for c in self.clauses.iter() {
+			s.add_clause(c)?;
+		}
+		Ok(s.solve())
+	}
+}
+
+/// The Tseitin encoding of the conjunction of the formulas, satisfiable
+/// exactly when they are jointly satisfiable.
+pub fn tseitin(fs: &[Form]) -> Cnf {
+	let mut t = Tseitin { atoms: vec!(), defs: vec!(), cnf: vec!(), vars: 0 };
+	// Reserve the atoms first, so they take the smallest variables.
+	for f in fs.iter() {
+		t.collect_atoms(f);
+	}
+	t.vars = t.atoms.len();
+	for f in fs.iter() {
+		let v = t.encode(f);
+		t.cnf.push(vec!(v));
+	}
+	Cnf { atoms: t.atoms, vars: t.vars, clauses: t.cnf }
+}
+
+/// A truth value for each atom.
+#[derive(Clone)]
+pub struct Assignment(pub Vec<(Form, bool)>);
+
+impl Assignment {
+	pub fn value(&self, atom: &Formula) -> Option<bool> {
+		self.0.iter().find(|(a, _)| **a == *atom).map(|(_, b)| *b)
+	}
+}
+
+impl fmt::Display for Assignment {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (a, b) in self.0.iter() {
+			writeln!(f, "{} = {}", a.to_string(), b)?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns an assignment satisfying every formula, if there is one.
+pub fn satisfiable(fs: &[Form]) -> Option<Assignment> {
+	let cnf = tseitin(fs);
+	cnf.solve().expect("SAT: Tseitin clause over an undeclared variable")
+		.map(|model| Assignment(cnf.atoms.iter().cloned().zip(model).collect()))
+}
+
+/// Returns an assignment making the hypotheses true and the goal false, or
+/// `None` if the goal is a propositional consequence of the hypotheses.
+pub fn counterexample(hyps: &[Form], goal: &Form) -> Option<Assignment> {
+	let mut fs = hyps.to_vec();
+	fs.push(Formula::Not(goal.clone()).ptr());
+	satisfiable(&fs)
+}
+
+/// Returns whether the formula is a propositional tautology.
+pub fn is_valid(f: &Form) -> bool {
+	counterexample(&[], f).is_none()
+}
+
+struct Tseitin {
+	atoms: Vec<Form>,
+	/// The variables of the subformulas encoded so far.
+	defs: Vec<(Form, i64)>,
+	cnf: Vec<Vec<i64>>,
+	vars: usize,
+}
+
+impl Tseitin {
+	fn collect_atoms(&mut self, f: &Form) {
+		match &**f {
+			Formula::And(l, r) | Formula::Or(l, r) | Formula::Implies(l, r) | Formula::IFF(l, r) => {
+				self.collect_atoms(l);
+				self.collect_atoms(r);
+			}
+			Formula::Not(l) => self.collect_atoms(l),
+			Formula::True | Formula::False => (),
+			_ => if !self.atoms.contains(f) {
+				self.atoms.push(f.clone());
+			}
+		}
+	}
+
+	fn fresh(&mut self) -> i64 {
+		self.vars += 1;
+		self.vars as i64
+	}
+
+	/// Returns a literal equivalent to `f` under the clauses added.
+	fn encode(&mut self, f: &Form) -> i64 {
+		if let Some(i) = self.atoms.iter().position(|a| a == f) {
+			return i as i64 + 1;
+		}
+		if let Some((_, v)) = self.defs.iter().find(|(d, _)| d == f) {
+			return *v;
+		}
+
+		let v = match &**f {
+			Formula::Not(l) => return -self.encode(l),
+			Formula::True | Formula::False => {
+				let v = self.fresh();
+				self.cnf.push(vec!(if **f == Formula::True { v } else { -v }));
+				v
+			}
+			Formula::And(l, r) => {
+				let (a, b, v) = (self.encode(l), self.encode(r), self.fresh());
+				self.cnf.extend(vec!(vec!(-v, a), vec!(-v, b), vec!(v, -a, -b)));
+				v
+			}
+			Formula::Or(l, r) => {
+				let (a, b, v) = (self.encode(l), self.encode(r), self.fresh());
+				self.cnf.extend(vec!(vec!(v, -a), vec!(v, -b), vec!(-v, a, b)));
+				v
+			}
+			Formula::Implies(l, r) => {
+				let (a, b, v) = (self.encode(l), self.encode(r), self.fresh());
+				self.cnf.extend(vec!(vec!(v, a), vec!(v, -b), vec!(-v, -a, b)));
+				v
+			}
+			Formula::IFF(l, r) => {
+				let (a, b, v) = (self.encode(l), self.encode(r), self.fresh());
+				self.cnf.extend(vec!(
+					vec!(-v, -a, b), vec!(-v, a, -b),
+					vec!(v, a, b), vec!(v, -a, -b)));
+				v
+			}
+			_ => unreachable!(),
+		};
+		self.defs.push((f.clone(), v));
+		v
+	}
+}
+
+/// A literal: variable `l >> 1`, negated when `l & 1` is set.
+type Lit = usize;
+
+fn lit(dimacs: i64) -> Lit {
+	(dimacs.unsigned_abs() as usize - 1) << 1 | (dimacs < 0) as usize
+}
+
+fn var(l: Lit) -> usize {
+	l >> 1
+}
+
+/// A CDCL solver with two watched literals, first UIP learning,
+/// non-chronological backjumping, activity based decisions and restarts.
+pub struct Solver {
+	clauses: Vec<Vec<Lit>>,
+	/// For each literal, the clauses watching it.
+	watches: Vec<Vec<usize>>,
+	assign: Vec<Option<bool>>,
+	level: Vec<usize>,
+	reason: Vec<Option<usize>>,
+	trail: Vec<Lit>,
+	/// The start of each decision level on the trail.
+	levels: Vec<usize>,
+	head: usize,
+	activity: Vec<f64>,
+	bump: f64,
+	unsat: bool,
+}
+
+impl Solver {
+	pub fn new(vars: usize) -> Solver {
+		Solver {
+			clauses: vec!(),
+			watches: vec!(vec!(); 2 * vars),
+			assign: vec!(None; vars),
+			level: vec!(0; vars),
+			reason: vec!(None; vars),
+			trail: vec!(),
+			levels: vec!(),
+			head: 0,
+			activity: vec!(0.0; vars),
+			bump: 1.0,
+			unsat: false,
+		}
+	}
+
+	/// Adds a clause of DIMACS literals, before solving. Fails on `0` and
+	/// on literals of variables beyond those the solver was made for.
+	pub fn add_clause(&mut self, c: &[i64]) -> Result<(), String> {
+		let vars = self.assign.len();
+		if let Some(l) = c.iter().find(|l| **l == 0 || l.unsigned_abs() > vars as u64) {
+			return Err(format!("SAT: Literal {} is not one of the variables 1 to {}", l, vars));
+		}
+		let mut c: Vec<Lit> = c.iter().map(|l| lit(*l)).collect();
+		c.sort_unstable();
+		c.dedup();
+		if c.windows(2).any(|w| w[0] ^ 1 == w[1]) {
+			return Ok(());
+		}
+		c.retain(|l| self.value(*l) != Some(false));
+		if c.iter().any(|l| self.value(*l) == Some(true)) {
+			return Ok(());
+		}
+
+		match c.len() {
+			0 => self.unsat = true,
+			1 => self.enqueue(c[0], None),
+			_ => { self.attach(c); }
+		}
+		Ok(())
+	}
+
+	/// Searches for a model, returning the value of every variable.
+	pub fn solve(&mut self) -> Option<Vec<bool>> {
+		if self.unsat {
+			return None;
+		}
+
+		let mut conflicts = 0;
+		let mut restart = 100;
+		loop {
+			if let Some(conflict) = self.propagate() {
+				if self.levels.is_empty() {
+					self.unsat = true;
+					return None;
+				}
+				let (learnt, back) = self.analyze(conflict);
+				self.cancel(back);
+				if learnt.len() == 1 {
+					self.enqueue(learnt[0], None);
+				} else {
+					let first = learnt[0];
+					let c = self.attach(learnt);
+					self.enqueue(first, Some(c));
+				}
+				self.bump *= 1.05;
+
+				conflicts += 1;
+				if conflicts == restart {
+					conflicts = 0;
+					restart += restart / 2;
+					self.cancel(0);
+				}
+			} else {
+				match self.decide() {
+					Some(v) => {
+						self.levels.push(self.trail.len());
+						self.enqueue(v << 1 | 1, None);
+					}
+					None => return Some(self.assign.iter().map(|a| a.unwrap_or(false)).collect()),
+				}
+			}
+		}
+	}
+
+	fn value(&self, l: Lit) -> Option<bool> {
+		self.assign[var(l)].map(|b| b != (l & 1 == 1))
+	}
+
+	fn attach(&mut self, c: Vec<Lit>) -> usize {
+		let i = self.clauses.len();
+		self.watches[c[0]].push(i);
+		self.watches[c[1]].push(i);
+		self.clauses.push(c);
+		i
+	}
+
+	fn enqueue(&mut self, l: Lit, reason: Option<usize>) {
+		let v = var(l);
+		self.assign[v] = Some(l & 1 == 0);
+		self.level[v] = self.levels.len();
+		self.reason[v] = reason;
+		self.trail.push(l);
+	}
+
+	/// Propagates the assignments on the trail, returning a conflicting
+	/// clause if one becomes false.
+	fn propagate(&mut self) -> Option<usize> {
+		while self.head < self.trail.len() {
+			let falsified = self.trail[self.head] ^ 1;
+			self.head += 1;
+
+			let watching = std::mem::take(&mut self.watches[falsified]);
+			let mut kept = vec!();
+			let mut conflict = None;
+			for (n, &ci) in watching.iter().enumerate() {
+				if conflict.is_some() {
+					kept.extend_from_slice(&watching[n..]);
+					break;
+				}
+
+				if self.clauses[ci][0] == falsified {
+					self.clauses[ci].swap(0, 1);
+				}
+				let first = self.clauses[ci][0];
+				if self.value(first) == Some(true) {
+					kept.push(ci);
+					continue;
+				}
+				let other = (2..self.clauses[ci].len())
+					.find(|k| self.value(self.clauses[ci][*k]) != Some(false));
+				if let Some(k) = other {
+					self.clauses[ci].swap(1, k);
+					let w = self.clauses[ci][1];
+					self.watches[w].push(ci);
+					continue;
+				}
+
+				kept.push(ci);
+				if self.value(first) == Some(false) {
+					conflict = Some(ci);
+				} else {
+					self.enqueue(first, Some(ci));
+				}
+			}
+			self.watches[falsified] = kept;
+
+			if conflict.is_some() {
+				return conflict;
+			}
+		}
+		None
+	}
+
+	/// Learns the first UIP clause of a conflict, with the asserting literal
+	/// first and a literal of the backjump level second.
+	fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+		let current = self.levels.len();
+		let mut seen = vec!(false; self.assign.len());
+		let mut learnt = vec!(0);
+		let mut pending = 0;
+		let mut clause = conflict;
+		let mut skip_first = false;
+		let mut index = self.trail.len();
+
+		let uip = loop {
+			let start = if skip_first { 1 } else { 0 };
+			for k in start..self.clauses[clause].len() {
+				let q = self.clauses[clause][k];
+				let v = var(q);
+				if !seen[v] && self.level[v] > 0 {
+					seen[v] = true;
+					self.activity[v] += self.bump;
+					if self.level[v] == current {
+						pending += 1;
+					} else {
+						learnt.push(q);
+					}
+				}
+			}
+
+			loop {
+				index -= 1;
+				if seen[var(self.trail[index])] {
+					break;
+				}
+			}
+			let p = self.trail[index];
+			seen[var(p)] = false;
+			pending -= 1;
+			if pending == 0 {
+				break p;
+			}
+			clause = self.reason[var(p)].expect("SAT: Implied literal without a reason");
+			skip_first = true;
+		};
+		learnt[0] = uip ^ 1;
+
+		let mut back = 0;
+		for k in 1..learnt.len() {
+			let l = self.level[var(learnt[k])];
+			if l > back {
+				back = l;
+				learnt.swap(1, k);
+			}
+		}
+		(learnt, back)
+	}
+
+	/// Undoes the assignments above the given decision level.
+	fn cancel(&mut self, level: usize) {
+		if self.levels.len() <= level {
+			return;
+		}
+		let start = self.levels[level];
+		for l in self.trail.drain(start..) {
+			self.assign[var(l)] = None;
+			self.reason[var(l)] = None;
+		}
+		self.levels.truncate(level);
+		self.head = self.trail.len();
+	}
+
+	/// The unassigned variable with the highest activity.
+	fn decide(&self) -> Option<usize> {
+		(0..self.assign.len())
+			.filter(|v| self.assign[*v].is_none())
+			.fold(None, |best: Option<usize>, v| match best {
+				Some(b) if self.activity[b] >= self.activity[v] => Some(b),
+				_ => Some(v),
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn add_clause_rejects_literals_outside_the_variables() {
+		let mut s = Solver::new(2);
+		assert!(s.add_clause(&[1, 0]).is_err());
+		assert!(s.add_clause(&[3]).is_err());
+		assert!(s.add_clause(&[-3, 1]).is_err());
+		assert!(s.add_clause(&[-2, 1]).is_ok());
+		assert!(s.add_clause(&[2]).is_ok());
+		assert_eq!(s.solve(), Some(vec!(true, true)));
+
+		let cnf = Cnf { atoms: vec!(), vars: 1, clauses: vec!(vec!(2)) };
+		assert!(cnf.solve().is_err());
+	}
+}
@@ -0,0 +1,316 @@
+//! Skolemization and Herbrand instantiation, for reducing quantified
+//! problems to ground ones.
+//!
+//! Skolem functions are ordinary constants applied through `Relation`, as in
+//! `Relation([Const(f), x1, ..., xn])`. Skolemization is only satisfiability
+//! preserving, so the symbols it introduces are recorded: a Skolem constant
+//! can be eliminated again by the `ExistsExtract` step naming it.
+
+use std::fmt;
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::Deduction;
+use knowledge_base::KnowledgeBase;
+use normal;
+
+/// A function symbol introduced for an existential quantifier.
+#[derive(Clone)]
+pub struct Skolem {
+	/// The name of the new symbol.
+	pub name: ConstName,
+	/// The universally quantified variables the symbol is applied to.
+	pub args: Vec<ConstName>,
+	/// The existential formula eliminated, with the Skolem terms of the
+	/// enclosing existentials already substituted.
+	pub witness: Form,
+}
+
+impl Skolem {
+	pub fn arity(&self) -> usize {
+		self.args.len()
+	}
+
+	/// The term replacing the existentially quantified variable.
+	pub fn term(&self) -> Formula {
+		if self.args.is_empty() {
+			Formula::Const(self.name.clone())
+		} else {
+			let mut v = vec!(Expr::Formula(Formula::Const(self.name.clone())));
+			v.extend(self.args.iter().map(|a| Expr::Formula(Formula::Free(a.clone()))));
+			Formula::Relation(v)
+		}
+	}
+
+	/// For a Skolem constant, the step introducing it from its witness,
+	/// which must already be deduced.
+	pub fn extract(&self) -> Option<Deduction> {
+		match &*self.witness {
+			Formula::Exists(v, e) if self.args.is_empty() =>
+				Some(Deduction::ExistsExtract((v.clone(), e.clone()), self.name.clone())),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Skolem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}/{} for {}", self.name.to_string(), self.arity(), self.witness.to_string())
+	}
+}
+
+/// The result of Skolemizing a formula.
+pub struct Skolemized {
+	/// The formula in negation normal form without existential quantifiers.
+	pub formula: Formula,
+	/// The symbols introduced, outermost first.
+	pub symbols: Vec<Skolem>,
+}
+
+/// Skolemizes the negation normal form of `f`, with bound variables renamed
+/// apart. New symbols are neither declared in `kbase` nor used in `f`. Each
+/// existential is replaced with a fresh symbol applied to the universally
+/// quantified variables enclosing it.
+pub fn skolemize<K: KnowledgeBase>(kbase: &K, f: &Formula) -> Skolemized {
+	let f = normal::rename(&normal::nnf(f));
+	let mut s = Skolemizer { kbase, fresh: f.max_int(), symbols: vec!() };
+	let formula = s.skolemize(&f, &mut vec!());
+
+	Skolemized { formula, symbols: s.symbols }
+}
+
+struct Skolemizer<'a, K: 'a> {
+	kbase: &'a K,
+	fresh: u64,
+	symbols: Vec<Skolem>,
+}
+
+impl <'a, K: KnowledgeBase> Skolemizer<'a, K> {
+	fn fresh(&mut self) -> ConstName {
+		loop {
+			self.fresh += 1;
+			let c = ConstName::Int(self.fresh);
+			if !self.kbase.has_const(&c) {
+				return c;
+			}
+		}
+	}
+
+	fn skolemize(&mut self, f: &Formula, universals: &mut Vec<ConstName>) -> Formula {
+		match f {
+			Formula::And(l, r) => Formula::And(
+				self.skolemize(l, universals).ptr(),
+				self.skolemize(r, universals).ptr()),
+			Formula::Or(l, r) => Formula::Or(
+				self.skolemize(l, universals).ptr(),
+				self.skolemize(r, universals).ptr()),
+			Formula::ForAll(v, e) => {
+				universals.push(v.clone());
+				let e = self.skolemize(e, universals);
+				universals.pop();
+				Formula::ForAll(v.clone(), e.ptr())
+			}
+			Formula::Exists(v, e) => {
+				let sk = Skolem {
+					name: self.fresh(),
+					args: universals.clone(),
+					witness: f.clone().ptr(),
+				};
+				let body = e.substitute(v, &sk.term());
+				self.symbols.push(sk);
+				self.skolemize(&body, universals)
+			}
+			o => o.clone(),
+		}
+	}
+}
+
+/// A ground instance of a universally quantified formula.
+pub struct Instance {
+	/// The terms substituted for the quantified variables, outermost first.
+	pub terms: Vec<Form>,
+	/// The instantiated matrix.
+	pub formula: Form,
+	/// The `ForAllExtract` steps deducing the instance from the formula.
+	pub proof: Deduction,
+}
+
+/// The Herbrand universe of a signature: its constants, closed under the
+/// application of its function symbols.
+pub struct Herbrand {
+	pub constants: Vec<ConstName>,
+	pub functions: Vec<(ConstName, usize)>,
+}
+
+impl Herbrand {
+	/// The signature made of the constants declared in `kbase` and the given
+	/// Skolem symbols.
+	pub fn new<K: KnowledgeBase>(kbase: &K, symbols: &[Skolem]) -> Herbrand {
+		let mut h = Herbrand { constants: kbase.constants(), functions: vec!() };
+		for s in symbols.iter() {
+			if s.args.is_empty() {
+				h.constants.push(s.name.clone());
+			} else {
+				h.functions.push((s.name.clone(), s.arity()));
+			}
+		}
+		h
+	}
+
+	/// The ground terms with at most `depth` nested function applications.
+	/// Empty if there are no constants.
+	pub fn universe(&self, depth: usize) -> Vec<Form> {
+		let mut terms: Vec<Form> = self.constants.iter()
+			.map(|c| Formula::Const(c.clone()).ptr())
+			.collect();
+
+		for _ in 0..depth {
+			let mut next = terms.clone();
+			for (name, arity) in self.functions.iter() {
+				for args in tuples(&terms, *arity) {
+					let mut v = vec!(Expr::Formula(Formula::Const(name.clone())));
+					v.extend(args.iter().map(|a| Expr::Formula((**a).clone())));
+					let t = Formula::Relation(v).ptr();
+					if !next.contains(&t) {
+						next.push(t);
+					}
+				}
+			}
+			terms = next;
+		}
+
+		terms
+	}
+
+	/// Instantiates the leading `ForAll` quantifiers of `f` with every tuple
+	/// of terms from `universe(depth)`.
+	pub fn instances(&self, f: &Form, depth: usize) -> Vec<Instance> {
+		let mut vars = vec!();
+		let mut matrix = f.clone();
+		while let Formula::ForAll(v, e) = &*matrix.clone() {
+			vars.push(v.clone());
+			matrix = e.clone();
+		}
+
+		let universe = self.universe(depth);
+		tuples(&universe, vars.len()).into_iter().map(|terms| {
+			let mut steps = vec!();
+			let mut current = f.clone();
+			for t in terms.iter() {
+				let (v, e) = match &*current {
+					Formula::ForAll(v, e) => (v.clone(), e.clone()),
+					_ => unreachable!(),
+				};
+				steps.push(Deduction::ForAllExtract((v.clone(), e.clone()), t.clone()));
+				current = e.substitute(&v, t).ptr();
+			}
+
+			Instance { terms, formula: current, proof: Deduction::Sequence(steps) }
+		}).collect()
+	}
+}
+
+/// Every tuple of length `n` over `terms`.
+fn tuples(terms: &[Form], n: usize) -> Vec<Vec<Form>> {
+	let mut out: Vec<Vec<Form>> = vec!(vec!());
+	for _ in 0..n {
+		out = out.iter()
+			.flat_map(|prefix| terms.iter().map(move |t| {
+				let mut p = prefix.clone();
+				p.push(t.clone());
+				p
+			}))
+			.collect();
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	struct Facts(Vec<Formula>, Vec<ConstName>);
+
+	impl KnowledgeBase for Facts {
+		fn contains(&self, form: &Formula) -> bool {
+			self.0.contains(form)
+		}
+
+		fn has_const(&self, c: &ConstName) -> bool {
+			self.1.contains(c)
+		}
+
+		fn constants(&self) -> Vec<ConstName> {
+			self.1.clone()
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn app(f: &ConstName, args: &[Formula]) -> Formula {
+		let mut v = vec!(Expr::Formula(Formula::Const(f.clone())));
+		v.extend(args.iter().map(|a| Expr::Formula(a.clone())));
+		Formula::Relation(v)
+	}
+
+	#[test]
+	fn existentials_become_functions_of_enclosing_universals() {
+		let (x, y, r) = (name("x"), name("y"), name("R"));
+		let rxy = app(&r, &[Formula::Free(x.clone()), Formula::Free(y.clone())]).ptr();
+		let exists = Formula::Exists(y.clone(), rxy).ptr();
+		let f = Formula::ForAll(x.clone(), exists.clone());
+
+		let s = skolemize(&Facts(vec!(), vec!(ConstName::Int(1))), &f);
+		let sk = ConstName::Int(2);
+		let term = app(&sk, &[Formula::Free(x.clone())]);
+		assert!(s.formula == Formula::ForAll(x.clone(),
+			app(&r, &[Formula::Free(x.clone()), term.clone()]).ptr()));
+		assert!(s.symbols.len() == 1);
+		assert!(s.symbols[0].name == sk && s.symbols[0].args == vec!(x));
+		assert!(s.symbols[0].witness == exists);
+		assert!(s.symbols[0].term() == term);
+		assert!(s.symbols[0].extract().is_none());
+	}
+
+	#[test]
+	fn skolem_constant_is_extracted_from_its_witness() {
+		let (y, p) = (name("y"), name("P"));
+		let f = Formula::Exists(y.clone(), app(&p, &[Formula::Free(y)]).ptr());
+
+		let s = skolemize(&Facts(vec!(), vec!()), &f);
+		let c = Formula::Const(ConstName::Int(1));
+		assert!(s.formula == app(&p, &[c]));
+		let step = s.symbols[0].extract().unwrap();
+		assert!(step.deduced(Facts(vec!(f), vec!()), &s.formula));
+	}
+
+	#[test]
+	fn herbrand_universe_grows_with_depth() {
+		let (a, f) = (name("a"), name("f"));
+		let h = Herbrand { constants: vec!(a.clone()), functions: vec!((f.clone(), 1)) };
+		let ca = Formula::Const(a);
+		let fa = app(&f, std::slice::from_ref(&ca));
+		let ffa = app(&f, std::slice::from_ref(&fa));
+		assert!(h.universe(0) == vec!(ca.clone().ptr()));
+		assert!(h.universe(2) == vec!(ca.ptr(), fa.ptr(), ffa.ptr()));
+		assert!(Herbrand { constants: vec!(), functions: vec!((f, 1)) }.universe(3).is_empty());
+	}
+
+	#[test]
+	fn herbrand_instances_are_deduced() {
+		let (x, y, r, a, b) = (name("x"), name("y"), name("R"), name("a"), name("b"));
+		let rxy = app(&r, &[Formula::Free(x.clone()), Formula::Free(y.clone())]).ptr();
+		let f = Formula::ForAll(x, Formula::ForAll(y, rxy).ptr()).ptr();
+
+		let kbase = Facts(vec!(), vec!(a, b));
+		let instances = Herbrand::new(&kbase, &[]).instances(&f, 0);
+		assert!(instances.len() == 4);
+		for i in instances.iter() {
+			let ground: Vec<Formula> = i.terms.iter().map(|t| (**t).clone()).collect();
+			assert!(*i.formula == app(&r, &ground));
+			assert!(i.proof.deduced(Facts(vec!((*f).clone()), vec!()), &i.formula));
+		}
+	}
+}
@@ -0,0 +1,288 @@
+//! Export of goals to SMT-LIB 2 scripts.
+//!
+//! Every term has the single sort `U`. A constant applied through
+//! `Relation([Const(f), t1, ..., tn])` is declared as a function from `U`s
+//! to `Bool` in formula positions and to `U` in term positions, which are
+//! the sides of `Eq` and the arguments of relations. The script asserts the
+//! assumptions and the negated goal, so `unsat` means the goal follows.
+//!
+//! Names are deterministic. A `ConstName::String` which is a simple SMT-LIB
+//! symbol is kept, and other strings are written between bars. As `|x|` and
+//! `x` are the same symbol, strings naming a symbol of the Core theory, such
+//! as `ite` or `=`, are mangled, as are strings which cannot be written
+//! between bars or start with `#`: they are written `|##s|`, with `#`, `|`
+//! and `\` in `s` escaped as `#` and two hex digits. `ConstName::Int(i)` is
+//! written `|#i|`, and bound variables `|#vn|` in binding order, so symbols
+//! from solver output map back to constants with `unname`.
+
+use std::fmt;
+
+use formula::{Formula, ConstName, Expr};
+use knowledge_base::KnowledgeBase;
+use Ptr;
+
+/// An SMT-LIB script with the symbols it declares.
+pub struct Script {
+	pub text: String,
+	/// The declared constants with their SMT-LIB names.
+	pub symbols: Vec<(ConstName, String)>,
+}
+
+impl Script {
+	/// Rewrites the symbols of solver output, such as a model, as the
+	/// constants they name.
+	pub fn report(&self, output: &str) -> String {
+		let mut out = String::new();
+		let mut rest = output;
+		while let Some(start) = rest.find(|c: char| c == '|' || is_symbol_char(c)) {
+			out.push_str(&rest[..start]);
+			rest = &rest[start..];
+			let end = if let Some(quoted) = rest.strip_prefix('|') {
+				quoted.find('|').map_or(rest.len(), |e| e + 2)
+			} else {
+				rest.find(|c: char| !is_symbol_char(c)).unwrap_or(rest.len())
+			};
+			let symbol = &rest[..end];
+			match self.symbols.iter().find(|(_, s)| s == symbol) {
+				Some((c, _)) => out.push_str(&c.to_string()),
+				None => out.push_str(symbol),
+			}
+			rest = &rest[end..];
+		}
+		out.push_str(rest);
+		out
+	}
+}
+
+impl fmt::Display for Script {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.text)
+	}
+}
+
+/// The SMT-LIB symbol of a constant.
+pub fn name(c: &ConstName) -> String {
+	match c {
+		ConstName::Int(i) => format!("|#{}|", i),
+		ConstName::String(s) => if is_simple(s) {
+			s.to_string()
+		} else if CORE.contains(&s.as_str()) || s.starts_with('#') || s.contains(['|', '\\']) {
+			format!("|##{}|", escape(s))
+		} else {
+			format!("|{}|", s)
+		}
+	}
+}
+
+/// The constant named by an SMT-LIB symbol. Inverts `name`; returns `None`
+/// for the names of bound variables and malformed escapes.
+pub fn unname(symbol: &str) -> Option<ConstName> {
+	let s = if symbol.len() >= 2 && symbol.starts_with('|') && symbol.ends_with('|') {
+		&symbol[1..symbol.len() - 1]
+	} else {
+		symbol
+	};
+	if let Some(escaped) = s.strip_prefix("##") {
+		return unescape(escaped).map(|s| ConstName::String(Ptr::new(s)));
+	}
+	if let Some(rest) = s.strip_prefix('#') {
+		return rest.parse().ok().map(ConstName::Int);
+	}
+	Some(ConstName::String(Ptr::new(s.to_string())))
+}
+
+fn escape(s: &str) -> String {
+	s.chars().map(|c| match c {
+		'#' | '|' | '\\' => format!("#{:02x}", c as u32),
+		c => c.to_string(),
+	}).collect()
+}
+
+fn unescape(s: &str) -> Option<String> {
+	let mut out = String::new();
+	let mut chars = s.chars();
+	while let Some(c) = chars.next() {
+		if c == '#' {
+			let hex: String = chars.by_ref().take(2).collect();
+			match u8::from_str_radix(&hex, 16) {
+				Ok(b) if hex.len() == 2 && (b == b'#' || b == b'|' || b == b'\\') => out.push(b as char),
+				_ => return None,
+			}
+		} else {
+			out.push(c);
+		}
+	}
+	Some(out)
+}
+
+/// Reserved words, which may only be used as symbols between bars.
+const RESERVED: [&str; 13] = [
+	"_", "!", "as", "let", "exists", "forall", "match", "par", "NUMERAL", "DECIMAL", "STRING",
+	"BINARY", "HEXADECIMAL",
+];
+
+/// The sort and functions of the Core theory, which are the same symbols
+/// with or without bars.
+const CORE: [&str; 11] = ["Bool", "true", "false", "not", "and", "or", "xor", "=>", "=", "distinct", "ite"];
+
+fn is_symbol_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c)
+}
+
+fn is_simple(s: &str) -> bool {
+	match s.chars().next() {
+		Some(c) if !c.is_ascii_digit() && c != '@' && c != '.' =>
+			s.chars().all(is_symbol_char) && !RESERVED.contains(&s) && !CORE.contains(&s),
+		_ => false,
+	}
+}
+
+/// Writes a script checking that `goal` follows from `assumptions`.
+pub fn problem(assumptions: &[Formula], goal: &Formula) -> Result<Script, String> {
+	let mut e = Exporter { symbols: vec!(), vars: vec!(), next: 0, quantified: false };
+	let mut asserts = vec!();
+	for a in assumptions.iter() {
+		asserts.push(format!("(assert {})", e.formula(a)?));
+	}
+	asserts.push(format!("(assert (not {}))", e.formula(goal)?));
+
+	let mut text = String::new();
+	text.push_str("(set-option :produce-models true)\n");
+	text.push_str(&format!("(set-logic {})\n", if e.quantified { "UF" } else { "QF_UF" }));
+	text.push_str("(declare-sort U 0)\n");
+	for s in e.symbols.iter() {
+		let args = vec!("U"; s.arity).join(" ");
+		let sort = if s.predicate { "Bool" } else { "U" };
+		text.push_str(&format!("(declare-fun {} ({}) {})\n", name(&s.name), args, sort));
+	}
+	for a in asserts.iter() {
+		text.push_str(a);
+		text.push('\n');
+	}
+	text.push_str("(check-sat)\n");
+
+	let symbols = e.symbols.iter().map(|s| (s.name.clone(), name(&s.name))).collect();
+	Ok(Script { text, symbols })
+}
+
+/// Writes a script with the theorems of `kbase` as assumptions.
+pub fn kbase_problem<K: KnowledgeBase>(kbase: &K, goal: &Formula) -> Result<Script, String> {
+	problem(&kbase.theorems(), goal)
+}
+
+struct Symbol {
+	name: ConstName,
+	arity: usize,
+	predicate: bool,
+}
+
+struct Exporter {
+	symbols: Vec<Symbol>,
+	/// The bound variables in scope with their SMT-LIB names, innermost last.
+	vars: Vec<(ConstName, String)>,
+	next: usize,
+	quantified: bool,
+}
+
+impl Exporter {
+	fn formula(&mut self, f: &Formula) -> Result<String, String> {
+		Ok(match f {
+			Formula::True => "true".to_string(),
+			Formula::False => "false".to_string(),
+			Formula::Eq(l, r) => format!("(= {} {})", self.term(l)?, self.term(r)?),
+			Formula::IFF(l, r) => format!("(= {} {})", self.formula(l)?, self.formula(r)?),
+			Formula::And(l, r) => format!("(and {} {})", self.formula(l)?, self.formula(r)?),
+			Formula::Or(l, r) => format!("(or {} {})", self.formula(l)?, self.formula(r)?),
+			Formula::Implies(l, r) => format!("(=> {} {})", self.formula(l)?, self.formula(r)?),
+			Formula::Not(l) => format!("(not {})", self.formula(l)?),
+			Formula::Relation(v) => self.application(v, true)?,
+			Formula::ForAll(v, e) => self.quantified("forall", v, e)?,
+			Formula::Exists(v, e) => self.quantified("exists", v, e)?,
+			Formula::ForAllSeq(_, v, _) =>
+				return Err(format!("SMT-LIB: Cannot express sequence quantifier over {}", v.to_string())),
+			Formula::Subst(_, v, _) =>
+				return Err(format!("SMT-LIB: Cannot express substitution of {}", v.to_string())),
+			Formula::Const(c) => self.symbol(c, 0, true)?,
+			Formula::Free(v) => return Err(format!("SMT-LIB: Cannot use variable {} as a formula", v.to_string())),
+		})
+	}
+
+	fn quantified(&mut self, q: &str, v: &ConstName, body: &Formula) -> Result<String, String> {
+		let x = format!("|#v{}|", self.next);
+		self.next += 1;
+		self.quantified = true;
+		self.vars.push((v.clone(), x.clone()));
+		let body = self.formula(body);
+		self.vars.pop();
+
+		Ok(format!("({} (({} U)) {})", q, x, body?))
+	}
+
+	fn term(&mut self, f: &Formula) -> Result<String, String> {
+		match f {
+			Formula::Relation(v) => self.application(v, false),
+			Formula::Const(c) => self.symbol(c, 0, false),
+			Formula::Free(v) => match self.vars.iter().rev().find(|(n, _)| n == v) {
+				Some((_, x)) => Ok(x.clone()),
+				None => Err(format!("SMT-LIB: Free variable {} is not bound", v.to_string())),
+			},
+			o => Err(format!("SMT-LIB: Cannot use formula {} as a term", o.to_string())),
+		}
+	}
+
+	/// Declares the symbol on first use, and checks later uses against it.
+	fn symbol(&mut self, c: &ConstName, arity: usize, predicate: bool) -> Result<String, String> {
+		match self.symbols.iter().find(|s| s.name == *c) {
+			Some(s) if s.arity != arity || s.predicate != predicate =>
+				return Err(format!("SMT-LIB: Symbol {} is used with different arities or sorts", c.to_string())),
+			Some(_) => (),
+			None => self.symbols.push(Symbol { name: c.clone(), arity, predicate }),
+		}
+		Ok(name(c))
+	}
+
+	fn application(&mut self, v: &[Expr], predicate: bool) -> Result<String, String> {
+		let head = match v.first() {
+			Some(Expr::Formula(Formula::Const(c))) => c,
+			Some(e) => return Err(format!("SMT-LIB: Cannot express relation head {}", e.to_string())),
+			None => return Err("SMT-LIB: Cannot express empty relation".to_string()),
+		};
+		let head = self.symbol(head, v.len() - 1, predicate)?;
+		if v.len() == 1 {
+			return Ok(head);
+		}
+
+		let mut args = vec!();
+		for e in v[1..].iter() {
+			match e {
+				Expr::Formula(f) => args.push(self.term(f)?),
+				s => return Err(format!("SMT-LIB: Cannot express sequence {}", s.to_string())),
+			}
+		}
+		Ok(format!("({} {})", head, args.join(" ")))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn names_are_distinct_and_reversible() {
+		let strings = ["x", "ite", "|ite|", "=", "Bool", "let", "a b", "a|b", "a\\b", "#1", "##v0", "#7c", "1x", "x#y"];
+		let mut names: Vec<ConstName> = strings.iter().map(|s| ConstName::String(Ptr::new(s.to_string()))).collect();
+		names.push(ConstName::Int(1));
+
+		let mut symbols = vec!();
+		for c in names.iter() {
+			let symbol = name(c);
+			if let Some(inner) = symbol.strip_prefix('|').and_then(|s| s.strip_suffix('|')) {
+				assert!(!inner.contains(['|', '\\']), "{}", symbol);
+			}
+			assert!(!CORE.contains(&symbol.trim_matches('|')), "{}", symbol);
+			assert!(unname(&symbol) == Some(c.clone()), "{}", symbol);
+			assert!(!symbols.contains(&symbol), "{}", symbol);
+			symbols.push(symbol);
+		}
+	}
+}
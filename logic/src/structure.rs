@@ -0,0 +1,312 @@
+//! Evaluation of formulas in finite structures.
+//!
+//! As in `model`, a constant applied through `Relation([Const(f), t1, ...,
+//! tn])` is a relation in formula positions and a function in term
+//! positions, which are the sides of `Eq` and the arguments of relations.
+//! `ForAllSeq` ranges over the sequences of elements up to a given length,
+//! and sequence expressions are expanded the way `Formula::substitute_seq`
+//! expands them. `Subst(body, v, t)` is evaluated as `body` with `v` bound
+//! to the value of `t`.
+
+use formula::{Formula, ConstName, Expr, Seq};
+use knowledge_base::KnowledgeBase;
+
+/// An interpretation of constants, functions, relations and equality over
+/// a finite domain. Each method returns `None` for symbols it does not
+/// interpret.
+pub trait Structure {
+	type Element: Clone + PartialEq;
+
+	fn domain(&self) -> Vec<Self::Element>;
+
+	fn constant(&self, c: &ConstName) -> Option<Self::Element>;
+
+	fn function(&self, f: &ConstName, args: &[Self::Element]) -> Option<Self::Element>;
+
+	fn relation(&self, r: &ConstName, args: &[Self::Element]) -> Option<bool>;
+
+	/// The truth value of a constant used as a formula. Defaults to the
+	/// relation with no arguments.
+	fn proposition(&self, c: &ConstName) -> Option<bool> {
+		self.relation(c, &[])
+	}
+
+	/// Defaults to equality of the elements.
+	fn equal(&self, a: &Self::Element, b: &Self::Element) -> bool {
+		a == b
+	}
+}
+
+/// Decides closed formulas in a structure.
+pub struct Evaluator<'a, S: 'a + Structure> {
+	pub structure: &'a S,
+	/// The longest sequences `ForAllSeq` ranges over.
+	pub max_seq: usize,
+}
+
+/// The values of the variables and sequence variables in scope.
+struct Env<E> {
+	vars: Vec<(ConstName, E)>,
+	seqs: Vec<((u64, ConstName), Vec<E>)>,
+}
+
+impl <'a, S: Structure> Evaluator<'a, S> {
+	pub fn new(structure: &'a S, max_seq: usize) -> Evaluator<'a, S> {
+		Evaluator { structure, max_seq }
+	}
+
+	/// Whether the closed formula holds in the structure.
+	pub fn eval(&self, f: &Formula) -> Result<bool, String> {
+		self.formula(f, &mut Env { vars: vec!(), seqs: vec!() })
+	}
+
+	/// The value of a closed term.
+	pub fn term(&self, t: &Formula) -> Result<S::Element, String> {
+		self.value(t, &mut Env { vars: vec!(), seqs: vec!() })
+	}
+
+	/// Returns the theorems of `kbase` which do not hold in the structure.
+	pub fn failing<K: KnowledgeBase>(&self, kbase: &K) -> Result<Vec<Formula>, String> {
+		let mut out = vec!();
+		for f in kbase.theorems() {
+			if !self.eval(&f)? {
+				out.push(f);
+			}
+		}
+		Ok(out)
+	}
+
+	fn formula(&self, f: &Formula, env: &mut Env<S::Element>) -> Result<bool, String> {
+		Ok(match f {
+			Formula::True => true,
+			Formula::False => false,
+			Formula::Not(e) => !self.formula(e, env)?,
+			Formula::And(l, r) => self.formula(l, env)? && self.formula(r, env)?,
+			Formula::Or(l, r) => self.formula(l, env)? || self.formula(r, env)?,
+			Formula::Implies(l, r) => !self.formula(l, env)? || self.formula(r, env)?,
+			Formula::IFF(l, r) => self.formula(l, env)? == self.formula(r, env)?,
+			Formula::Eq(l, r) => {
+				let (a, b) = (self.value(l, env)?, self.value(r, env)?);
+				self.structure.equal(&a, &b)
+			}
+			Formula::ForAll(v, e) | Formula::Exists(v, e) => {
+				let universal = matches!(f, Formula::ForAll(_, _));
+				for d in self.structure.domain() {
+					env.vars.push((v.clone(), d));
+					let r = self.formula(e, env);
+					env.vars.pop();
+					if r? != universal {
+						return Ok(!universal);
+					}
+				}
+				universal
+			}
+			Formula::ForAllSeq(ar, v, e) => {
+				let domain = self.structure.domain();
+				let mut seqs: Vec<Vec<S::Element>> = vec!(vec!());
+				for len in 0..=self.max_seq {
+					for s in seqs.iter() {
+						env.seqs.push(((*ar, v.clone()), s.clone()));
+						let r = self.formula(e, env);
+						env.seqs.pop();
+						if !r? {
+							return Ok(false);
+						}
+					}
+					if len < self.max_seq {
+						seqs = seqs.iter()
+							.flat_map(|s| domain.iter().map(move |d| {
+								let mut s = s.clone();
+								s.push(d.clone());
+								s
+							}))
+							.collect();
+					}
+				}
+				true
+			}
+			Formula::Subst(body, v, sub) => {
+				let d = self.value(sub, env)?;
+				env.vars.push((v.clone(), d));
+				let r = self.formula(body, env);
+				env.vars.pop();
+				r?
+			}
+			Formula::Const(c) => self.structure.proposition(c)
+				.ok_or_else(|| format!("Eval: No truth value for {}", c.to_string()))?,
+			Formula::Relation(v) => {
+				let (head, args) = self.application(v, env)?;
+				self.structure.relation(head, &args)
+					.ok_or_else(|| format!("Eval: No interpretation for relation {}", head.to_string()))?
+			}
+			Formula::Free(v) => return Err(format!("Eval: Cannot use variable {} as a formula", v.to_string())),
+		})
+	}
+
+	fn value(&self, t: &Formula, env: &mut Env<S::Element>) -> Result<S::Element, String> {
+		match t {
+			Formula::Free(v) => env.vars.iter().rev()
+				.find(|(n, _)| n == v)
+				.map(|(_, d)| d.clone())
+				.ok_or_else(|| format!("Eval: Free variable {} is not bound", v.to_string())),
+			Formula::Const(c) => self.structure.constant(c)
+				.ok_or_else(|| format!("Eval: No interpretation for constant {}", c.to_string())),
+			Formula::Relation(v) => {
+				let (head, args) = self.application(v, env)?;
+				self.structure.function(head, &args)
+					.ok_or_else(|| format!("Eval: No interpretation for function {}", head.to_string()))
+			}
+			Formula::Subst(body, v, sub) => {
+				let d = self.value(sub, env)?;
+				env.vars.push((v.clone(), d));
+				let r = self.value(body, env);
+				env.vars.pop();
+				r
+			}
+			o => Err(format!("Eval: Cannot use formula {} as a term", o.to_string())),
+		}
+	}
+
+	/// The head of a relation and the values of its arguments, with
+	/// sequences expanded.
+	fn application<'f>(&self, v: &'f [Expr], env: &mut Env<S::Element>)
+	-> Result<(&'f ConstName, Vec<S::Element>), String> {
+		let head = match v.first() {
+			Some(Expr::Formula(Formula::Const(c))) => c,
+			Some(e) => return Err(format!("Eval: Cannot interpret relation head {}", e.to_string())),
+			None => return Err("Eval: Cannot interpret empty relation".to_string()),
+		};
+
+		let mut args = vec!();
+		for e in v[1..].iter() {
+			match e {
+				Expr::Formula(t) => args.push(self.value(t, env)?),
+				Expr::Seq(s) => args.extend(self.sequence(s, env)?),
+				Expr::Head(s) => args.extend(self.sequence(s, env)?.into_iter().take(1)),
+			}
+		}
+		Ok((head, args))
+	}
+
+	fn sequence(&self, s: &Seq, env: &Env<S::Element>) -> Result<Vec<S::Element>, String> {
+		match s {
+			Seq::Free(ar, v) => env.seqs.iter().rev()
+				.find(|(n, _)| n.0 == *ar && n.1 == *v)
+				.map(|(_, s)| s.clone())
+				.ok_or_else(|| format!("Eval: Sequence variable {} is not bound", v.to_string())),
+			Seq::Tail(p) => {
+				let mut s = self.sequence(p, env)?;
+				s.pop();
+				Ok(s)
+			}
+		}
+	}
+}
+
+/// Whether the closed formula holds in the structure, with sequences of at
+/// most `max_seq` elements.
+pub fn eval<S: Structure>(structure: &S, f: &Formula, max_seq: usize) -> Result<bool, String> {
+	Evaluator::new(structure, max_seq).eval(f)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	/// The integers modulo 3 with successor, `sorted` holding of
+	/// non-decreasing argument lists and `short` of lists of at most one.
+	struct Mod3;
+
+	impl Structure for Mod3 {
+		type Element = usize;
+
+		fn domain(&self) -> Vec<usize> {
+			vec!(0, 1, 2)
+		}
+
+		fn constant(&self, c: &ConstName) -> Option<usize> {
+			if *c == name("zero") { Some(0) } else { None }
+		}
+
+		fn function(&self, f: &ConstName, args: &[usize]) -> Option<usize> {
+			match args {
+				[x] if *f == name("s") => Some((x + 1) % 3),
+				_ => None,
+			}
+		}
+
+		fn relation(&self, r: &ConstName, args: &[usize]) -> Option<bool> {
+			if *r == name("sorted") {
+				Some(args.windows(2).all(|w| w[0] <= w[1]))
+			} else if *r == name("short") {
+				Some(args.len() <= 1)
+			} else {
+				None
+			}
+		}
+	}
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn app(f: &str, args: Vec<Expr>) -> Formula {
+		let mut v = vec!(Expr::Formula(Formula::Const(name(f))));
+		v.extend(args);
+		Formula::Relation(v)
+	}
+
+	fn var(s: &str) -> Expr {
+		Expr::Formula(Formula::Free(name(s)))
+	}
+
+	#[test]
+	fn quantifiers_range_over_the_domain() {
+		let (x, y) = (name("x"), name("y"));
+		let succ = Formula::Eq(Formula::Free(y.clone()).ptr(), app("s", vec!(var("x"))).ptr());
+		let total = Formula::ForAll(x.clone(), Formula::Exists(y, succ.ptr()).ptr());
+		assert_eq!(eval(&Mod3, &total, 0), Ok(true));
+
+		let fixed = Formula::Eq(app("s", vec!(var("x"))).ptr(), Formula::Free(x.clone()).ptr());
+		assert_eq!(eval(&Mod3, &Formula::Exists(x, fixed.ptr()), 0), Ok(false));
+	}
+
+	#[test]
+	fn sequence_quantifier_ranges_up_to_the_length() {
+		let s = name("s");
+		let seq = Expr::Seq(Seq::Free(1, s.clone()));
+		let sorted = Formula::ForAllSeq(1, s.clone(), app("sorted", vec!(seq)).ptr());
+		assert_eq!(eval(&Mod3, &sorted, 1), Ok(true));
+		assert_eq!(eval(&Mod3, &sorted, 2), Ok(false));
+
+		let tail = Expr::Seq(Seq::Tail(Ptr::new(Seq::Free(1, s.clone()))));
+		let short = Formula::ForAllSeq(1, s.clone(), app("short", vec!(tail)).ptr());
+		assert_eq!(eval(&Mod3, &short, 2), Ok(true));
+		assert_eq!(eval(&Mod3, &short, 3), Ok(false));
+
+		let head = Expr::Head(Seq::Free(1, s.clone()));
+		let first = Formula::ForAllSeq(1, s, app("short", vec!(head)).ptr());
+		assert_eq!(eval(&Mod3, &first, 3), Ok(true));
+	}
+
+	#[test]
+	fn substitution_binds_the_value() {
+		let x = name("x");
+		let body = Formula::Eq(app("s", vec!(var("x"))).ptr(), Formula::Const(name("zero")).ptr());
+		let zero = Formula::Const(name("zero")).ptr();
+		let f = Formula::Subst(body.clone().ptr(), x.clone(), zero.clone());
+		assert_eq!(eval(&Mod3, &f, 0), Ok(false));
+		let two = app("s", vec!(Expr::Formula(app("s", vec!(Expr::Formula((*zero).clone())))))).ptr();
+		assert_eq!(Evaluator::new(&Mod3, 0).term(&two), Ok(2));
+		assert_eq!(eval(&Mod3, &Formula::Subst(body.ptr(), x, two), 0), Ok(true));
+	}
+
+	#[test]
+	fn uninterpreted_symbols_are_errors() {
+		assert!(eval(&Mod3, &Formula::Const(name("p")), 0).is_err());
+		assert!(eval(&Mod3, &app("sorted", vec!(var("x"))), 0).is_err());
+		let unknown = Formula::Eq(Formula::Const(name("one")).ptr(), Formula::Const(name("zero")).ptr());
+		assert!(eval(&Mod3, &unknown, 0).is_err());
+	}
+}
@@ -0,0 +1,191 @@
+//! Export of formulas to TPTP first-order form.
+//!
+//! `Relation([Const(f), t1, ..., tn])` is written `f(t1, ..., tn)`, as a
+//! predicate in formula positions and as a function in term positions,
+//! which are the sides of `Eq` and the arguments of relations. Bound
+//! variables are renamed `X0`, `X1`, ... in binding order.
+
+use formula::{Formula, ConstName, Expr};
+use knowledge_base::KnowledgeBase;
+use tptp::name;
+
+/// Export settings.
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+	/// Write `ForAllSeq(n, v, e)` as `n` universally quantified variables
+	/// instead of rejecting it.
+	pub expand_seqs: bool,
+}
+
+/// Writes a closed formula in TPTP syntax.
+pub fn formula(f: &Formula, opts: Options) -> Result<String, String> {
+	Exporter { opts, vars: vec!(), next: 0 }.formula(f)
+}
+
+/// Writes a TPTP problem with the given axioms and conjecture.
+pub fn problem(axioms: &[Formula], conjecture: &Formula, opts: Options) -> Result<String, String> {
+	let mut out = String::new();
+	for (i, a) in axioms.iter().enumerate() {
+		out.push_str(&format!("fof(ax{}, axiom, {}).\n", i, formula(a, opts)?));
+	}
+	out.push_str(&format!("fof(goal, conjecture, {}).\n", formula(conjecture, opts)?));
+	Ok(out)
+}
+
+/// Writes a TPTP problem with the theorems of `kbase` as axioms.
+pub fn kbase_problem<K: KnowledgeBase>(kbase: &K, conjecture: &Formula, opts: Options)
+-> Result<String, String> {
+	problem(&kbase.theorems(), conjecture, opts)
+}
+
+struct Exporter {
+	opts: Options,
+	/// The bound variables in scope with their TPTP names, innermost last.
+	vars: Vec<(ConstName, String)>,
+	next: usize,
+}
+
+impl Exporter {
+	fn formula(&mut self, f: &Formula) -> Result<String, String> {
+		Ok(match f {
+			Formula::True => "$true".to_string(),
+			Formula::False => "$false".to_string(),
+			Formula::Eq(l, r) => format!("({} = {})", self.term(l)?, self.term(r)?),
+			Formula::IFF(l, r) => format!("({} <=> {})", self.formula(l)?, self.formula(r)?),
+			Formula::And(l, r) => format!("({} & {})", self.formula(l)?, self.formula(r)?),
+			Formula::Or(l, r) => format!("({} | {})", self.formula(l)?, self.formula(r)?),
+			Formula::Implies(l, r) => format!("({} => {})", self.formula(l)?, self.formula(r)?),
+			Formula::Not(l) => format!("~ {}", self.formula(l)?),
+			Formula::Relation(v) => self.application(v)?,
+			Formula::ForAll(v, e) => self.quantified("!", std::slice::from_ref(v), e)?,
+			Formula::Exists(v, e) => self.quantified("?", std::slice::from_ref(v), e)?,
+			Formula::ForAllSeq(ar, v, e) => {
+				if !self.opts.expand_seqs {
+					return Err(format!("TPTP: Cannot express sequence quantifier over {}", v.to_string()));
+				}
+				let top = e.max_int() + 1;
+				let names: Vec<ConstName> = (0..*ar).map(|i| ConstName::Int(top + i)).collect();
+				let elems = names.iter().map(|n| Formula::Free(n.clone())).collect();
+				self.quantified("!", &names, &e.substitute_seq(*ar, v, &elems))?
+			}
+			Formula::Subst(_, v, _) =>
+				return Err(format!("TPTP: Cannot express substitution of {}", v.to_string())),
+			Formula::Const(c) => name(c),
+			Formula::Free(v) => self.var(v)?,
+		})
+	}
+
+	fn quantified(&mut self, q: &str, names: &[ConstName], body: &Formula) -> Result<String, String> {
+		if names.is_empty() {
+			return self.formula(body);
+		}
+
+		let mut bound = vec!();
+		for n in names.iter() {
+			let v = format!("X{}", self.next);
+			self.next += 1;
+			self.vars.push((n.clone(), v.clone()));
+			bound.push(v);
+		}
+		let body = self.formula(body);
+		self.vars.truncate(self.vars.len() - names.len());
+
+		Ok(format!("({} [{}] : {})", q, bound.join(", "), body?))
+	}
+
+	fn term(&mut self, f: &Formula) -> Result<String, String> {
+		match f {
+			Formula::Relation(v) => self.application(v),
+			Formula::Const(c) => Ok(name(c)),
+			Formula::Free(v) => self.var(v),
+			o => Err(format!("TPTP: Cannot use formula {} as a term", o.to_string())),
+		}
+	}
+
+	fn var(&self, v: &ConstName) -> Result<String, String> {
+		match self.vars.iter().rev().find(|(n, _)| n == v) {
+			Some((_, x)) => Ok(x.clone()),
+			None => Err(format!("TPTP: Free variable {} is not bound", v.to_string())),
+		}
+	}
+
+	fn application(&mut self, v: &[Expr]) -> Result<String, String> {
+		let head = match v.first() {
+			Some(Expr::Formula(Formula::Const(c))) => name(c),
+			Some(e) => return Err(format!("TPTP: Cannot express relation head {}", e.to_string())),
+			None => return Err("TPTP: Cannot express empty relation".to_string()),
+		};
+		if v.len() == 1 {
+			return Ok(head);
+		}
+
+		let mut args = vec!();
+		for e in v[1..].iter() {
+			match e {
+				Expr::Formula(f) => args.push(self.term(f)?),
+				s => return Err(format!("TPTP: Cannot express sequence {}", s.to_string())),
+			}
+		}
+		Ok(format!("{}({})", head, args.join(", ")))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use formula::Seq;
+	use Ptr;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn app(f: &str, args: &[Formula]) -> Formula {
+		let mut v = vec!(Expr::Formula(Formula::Const(name(f))));
+		v.extend(args.iter().map(|a| Expr::Formula(a.clone())));
+		Formula::Relation(v)
+	}
+
+	#[test]
+	fn formulas_are_written_with_renamed_variables() {
+		let (x, y) = (name("x"), name("y"));
+		let body = Formula::Implies(
+			app("p", &[Formula::Free(x.clone())]).ptr(),
+			Formula::Exists(y.clone(), Formula::Eq(
+				Formula::Free(y.clone()).ptr(),
+				app("f", &[Formula::Free(x.clone()), Formula::Const(name("c"))]).ptr()).ptr()).ptr());
+		let f = Formula::ForAll(x, body.ptr());
+		assert_eq!(formula(&f, Options::default()).unwrap(),
+			"(! [X0] : (p(X0) => (? [X1] : (X1 = f(X0, c)))))");
+		assert_eq!(formula(&Formula::Not(Formula::True.ptr()), Options::default()).unwrap(),
+			"~ $true");
+	}
+
+	#[test]
+	fn problem_lists_axioms_before_the_conjecture() {
+		let (p, q) = (Formula::Const(name("p")), Formula::Const(name("q")));
+		let out = problem(&[p.clone(), Formula::Or(p.ptr(), q.clone().ptr())], &q, Options::default());
+		assert_eq!(out.unwrap(), concat!(
+			"fof(ax0, axiom, p).\n",
+			"fof(ax1, axiom, (p | q)).\n",
+			"fof(goal, conjecture, q).\n"));
+	}
+
+	#[test]
+	fn inexpressible_formulas_are_rejected() {
+		let x = name("x");
+		let opts = Options::default();
+		assert!(formula(&app("p", &[Formula::Free(x.clone())]), opts).is_err());
+		let subst = Formula::Subst(Formula::True.ptr(), x.clone(), Formula::True.ptr());
+		assert!(formula(&subst, opts).is_err());
+		let equation = Formula::Eq(Formula::True.ptr(), Formula::Const(x.clone()).ptr());
+		assert!(formula(&equation, opts).is_err());
+
+		let seq = Formula::ForAllSeq(2, x.clone(), Formula::Relation(vec!(
+			Expr::Formula(Formula::Const(name("p"))),
+			Expr::Seq(Seq::Free(2, x)))).ptr());
+		assert!(formula(&seq, opts).is_err());
+		assert_eq!(formula(&seq, Options { expand_seqs: true }).unwrap(),
+			"(! [X0, X1] : p(X0, X1))");
+	}
+}
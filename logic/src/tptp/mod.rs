@@ -0,0 +1,79 @@
+//! Interchange with automated provers in the TPTP language.
+//!
+//! Constants are written as TPTP lower words when they are one, and single
+//! quoted otherwise. `ConstName::Int(i)` is written `'#i'`, and a string
+//! name starting with `#` gets one more `#`, so the mapping is injective.
+
+use formula::ConstName;
+use Ptr;
+
+pub mod export;
+pub mod parse;
+pub mod tstp;
+
+/// The TPTP name of a constant, functor or predicate.
+pub fn name(c: &ConstName) -> String {
+	match c {
+		ConstName::Int(i) => format!("'#{}'", i),
+		ConstName::String(s) => if is_lower_word(s) {
+			s.to_string()
+		} else if s.starts_with('#') {
+			quote(&format!("#{}", s))
+		} else {
+			quote(s)
+		}
+	}
+}
+
+/// The constant with the given TPTP name, without its quotes. Inverts
+/// `name`.
+pub fn unname(s: &str) -> ConstName {
+	if s.starts_with("##") {
+		return ConstName::String(Ptr::new(s[1..].to_string()));
+	}
+	if s.starts_with('#') && s.len() > 1 {
+		if let Ok(i) = s[1..].parse() {
+			return ConstName::Int(i);
+		}
+	}
+	ConstName::String(Ptr::new(s.to_string()))
+}
+
+fn is_lower_word(s: &str) -> bool {
+	let mut chars = s.chars();
+	match chars.next() {
+		Some(c) if c.is_ascii_lowercase() => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+		_ => false,
+	}
+}
+
+fn quote(s: &str) -> String {
+	format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn s(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	#[test]
+	fn names_are_quoted_unless_lower_words() {
+		assert_eq!(name(&s("abc_1")), "abc_1");
+		assert_eq!(name(&s("Abc")), "'Abc'");
+		assert_eq!(name(&s("it's")), "'it\\'s'");
+		assert_eq!(name(&ConstName::Int(3)), "'#3'");
+		assert_eq!(name(&s("#3")), "'##3'");
+	}
+
+	#[test]
+	fn unname_inverts_name() {
+		for c in [s("abc"), s("Abc"), s("#3"), s("#"), s("##x"), ConstName::Int(3)] {
+			let n = name(&c);
+			let unquoted = n.trim_matches('\'').replace("\\'", "'").replace("\\\\", "\\");
+			assert!(unname(&unquoted) == c);
+		}
+	}
+}
@@ -0,0 +1,780 @@
+//! Import of TPTP problems in first-order and clause normal form.
+//!
+//! Formulas are read back the way `tptp::export` writes them: applications
+//! become `Relation([Const(f), t1, ..., tn])`, nullary symbols become
+//! `Const`, and variables become `Free` names bound by `ForAll` and
+//! `Exists`. The free variables of a clause are universally quantified in
+//! order of appearance. The connectives `<=`, `<~>`, `~|`, `~&` and `!=` are
+//! expanded into `Implies`, `IFF`, `Or`, `And` and `Eq` under `Not`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use formula::{Formula, ConstName, Expr};
+use knowledge_base::{KnowledgeBase, ResultBase};
+use tptp::unname;
+use Ptr;
+
+/// The formulas of a TPTP problem.
+#[derive(Clone, Default)]
+pub struct Problem {
+	/// The named axioms, hypotheses and other assumed formulas, including
+	/// negated conjectures, in file order.
+	pub axioms: Vec<(String, Formula)>,
+	/// The conjecture, if the problem has one.
+	pub conjecture: Option<(String, Formula)>,
+	/// The symbols used by the problem, in order of first use.
+	pub constants: Vec<ConstName>,
+}
+
+impl Problem {
+	/// The formula to prove: the conjecture, or `False` for a problem given
+	/// as a set of clauses to refute.
+	pub fn goal(&self) -> Formula {
+		match &self.conjecture {
+			Some((_, f)) => f.clone(),
+			None => Formula::False,
+		}
+	}
+
+	/// Extends `kbase` with the constants and axioms of the problem.
+	pub fn context<K: KnowledgeBase>(&self, kbase: ResultBase<K>) -> ResultBase<K> {
+		let kbase = self.constants.iter()
+			.fold(kbase, |k, c| k.result_const(c.clone()));
+		self.axioms.iter()
+			.fold(kbase, |k, (_, f)| k.result_form(f.clone()))
+	}
+
+	fn push(&mut self, name: String, role: &str, f: Formula) -> Result<(), String> {
+		let mut symbols = vec!();
+		symbols_of(&f, &mut symbols);
+		for c in symbols {
+			if !self.constants.contains(&c) {
+				self.constants.push(c);
+			}
+		}
+
+		match role {
+			"conjecture" => {
+				if let Some((other, _)) = &self.conjecture {
+					return Err(format!("Second conjecture {} after {}", name, other));
+				}
+				self.conjecture = Some((name, f));
+			}
+			"axiom" | "hypothesis" | "definition" | "assumption" | "lemma" | "theorem"
+			| "corollary" | "negated_conjecture" | "plain" => self.axioms.push((name, f)),
+			r => return Err(format!("Unsupported role {} of {}", r, name)),
+		}
+		Ok(())
+	}
+}
+
+impl KnowledgeBase for Problem {
+	fn contains(&self, form: &Formula) -> bool {
+		self.axioms.iter().any(|(_, f)| f == form)
+	}
+
+	fn has_const(&self, c: &ConstName) -> bool {
+		self.constants.contains(c)
+	}
+
+	fn constants(&self) -> Vec<ConstName> {
+		self.constants.clone()
+	}
+
+	fn theorems(&self) -> Vec<Formula> {
+		self.axioms.iter().map(|(_, f)| f.clone()).collect()
+	}
+
+	fn theorem(&self, name: &ConstName) -> Option<Formula> {
+		match name {
+			ConstName::String(s) => self.axioms.iter()
+				.find(|(n, _)| n == &**s)
+				.map(|(_, f)| f.clone()),
+			ConstName::Int(_) => None,
+		}
+	}
+}
+
+/// Parses a problem. Included files are resolved as for a file in the
+/// current directory.
+pub fn parse(text: &str) -> Result<Problem, String> {
+	let mut loader = Loader { problem: Problem::default(), files: vec!() };
+	loader.text(text, Path::new("."), None)
+		.map_err(|e| format!("TPTP: {}", e))?;
+	Ok(loader.problem)
+}
+
+/// Reads a problem from a file. Included files are looked up in the
+/// directory named by the `TPTP` environment variable, then in the directory
+/// of the including file and its ancestors.
+pub fn load(path: &Path) -> Result<Problem, String> {
+	let mut loader = Loader { problem: Problem::default(), files: vec!() };
+	loader.file(path, None)?;
+	Ok(loader.problem)
+}
+
+struct Loader {
+	problem: Problem,
+	/// The files being read, to reject cyclic includes.
+	files: Vec<PathBuf>,
+}
+
+impl Loader {
+	fn file(&mut self, path: &Path, select: Option<&[String]>) -> Result<(), String> {
+		if self.files.iter().any(|f| f == path) {
+			return Err(format!("TPTP: {}: Cyclic include", path.display()));
+		}
+		let text = fs::read_to_string(path)
+			.map_err(|e| format!("TPTP: {}: {}", path.display(), e))?;
+		let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+		self.files.push(path.to_path_buf());
+		let r = self.text(&text, dir, select)
+			.map_err(|e| if e.starts_with("TPTP: ") { e } else { format!("TPTP: {}: {}", path.display(), e) });
+		self.files.pop();
+		r
+	}
+
+	fn text(&mut self, text: &str, dir: &Path, select: Option<&[String]>) -> Result<(), String> {
+		let mut parser = Parser { tokens: lex(text)?, pos: 0, bound: vec!(), free: None };
+		while let Some(statement) = parser.statement()? {
+			match statement {
+				Statement::Include(file, names) => {
+					let path = resolve(&file, dir)
+						.ok_or_else(|| format!("Cannot find included file {}", file))?;
+					self.file(&path, names.as_ref().map(|n| &n[..]))?;
+				}
+				Statement::Formula(a) => {
+					if select.is_none_or(|s| s.contains(&a.name)) {
+						self.problem.push(a.name, &a.role, a.formula)?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+fn resolve(file: &str, dir: &Path) -> Option<PathBuf> {
+	let mut candidates = vec!();
+	if let Some(root) = env::var_os("TPTP") {
+		candidates.push(Path::new(&root).join(file));
+	}
+	candidates.extend(dir.ancestors().map(|d| d.join(file)));
+	candidates.into_iter().find(|p| p.is_file())
+}
+
+/// Collects the constants of `f`, in order of first use.
+fn symbols_of(f: &Formula, out: &mut Vec<ConstName>) {
+	match f {
+		Formula::Const(c) => if !out.contains(c) {
+			out.push(c.clone());
+		}
+		Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+		| Formula::Or(l, r) | Formula::Implies(l, r) | Formula::Subst(l, _, r) => {
+			symbols_of(l, out);
+			symbols_of(r, out);
+		}
+		Formula::Not(e) | Formula::ForAll(_, e) | Formula::Exists(_, e) | Formula::ForAllSeq(_, _, e) =>
+			symbols_of(e, out),
+		Formula::Relation(v) => for e in v.iter() {
+			if let Expr::Formula(e) = e {
+				symbols_of(e, out);
+			}
+		}
+		Formula::Free(_) | Formula::True | Formula::False => (),
+	}
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+	Lower(String),
+	Upper(String),
+	Quoted(String),
+	Distinct(String),
+	Number(String),
+	Dollar(String),
+	Punct(&'static str),
+	End,
+}
+
+impl Token {
+	fn describe(&self) -> String {
+		match self {
+			Token::Lower(s) | Token::Upper(s) | Token::Number(s) => s.clone(),
+			Token::Quoted(s) => format!("'{}'", s),
+			Token::Distinct(s) => format!("\"{}\"", s),
+			Token::Dollar(s) => format!("${}", s),
+			Token::Punct(p) => p.to_string(),
+			Token::End => "end of input".to_string(),
+		}
+	}
+}
+
+const PUNCTUATION: [&str; 20] = [
+	"<~>", "<=>", "=>", "<=", "~|", "~&", "!=",
+	"(", ")", "[", "]", ",", ".", ":", "!", "?", "~", "&", "|", "=",
+];
+
+/// Splits the text into tokens with their line numbers.
+fn lex(text: &str) -> Result<Vec<(Token, usize)>, String> {
+	let chars: Vec<char> = text.chars().collect();
+	let mut tokens = vec!();
+	let mut line = 1;
+	let mut i = 0;
+	let word = |i: &mut usize| {
+		let start = *i;
+		while *i < chars.len() && (chars[*i].is_ascii_alphanumeric() || chars[*i] == '_') {
+			*i += 1;
+		}
+		chars[start..*i].iter().collect::<String>()
+	};
+	let digit = |i: usize| chars.get(i).is_some_and(|d| d.is_ascii_digit());
+
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '\n' {
+			line += 1;
+			i += 1;
+		} else if c.is_whitespace() {
+			i += 1;
+		} else if c == '%' {
+			while i < chars.len() && chars[i] != '\n' {
+				i += 1;
+			}
+		} else if c == '/' && chars.get(i + 1) == Some(&'*') {
+			i += 2;
+			while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+				if chars[i] == '\n' {
+					line += 1;
+				}
+				i += 1;
+			}
+			if i >= chars.len() {
+				return Err(format!("line {}: Unterminated comment", line));
+			}
+			i += 2;
+		} else if c == '\'' || c == '"' {
+			let start = line;
+			let mut s = String::new();
+			i += 1;
+			loop {
+				match chars.get(i) {
+					None => return Err(format!("line {}: Unterminated quote", start)),
+					Some('\\') => {
+						match chars.get(i + 1) {
+							Some(e) => s.push(*e),
+							None => return Err(format!("line {}: Unterminated quote", start)),
+						}
+						i += 2;
+					}
+					Some(q) if *q == c => {
+						i += 1;
+						break;
+					}
+					Some(o) => {
+						if *o == '\n' {
+							line += 1;
+						}
+						s.push(*o);
+						i += 1;
+					}
+				}
+			}
+			tokens.push((if c == '\'' { Token::Quoted(s) } else { Token::Distinct(s) }, start));
+		} else if c == '$' {
+			i += 1;
+			tokens.push((Token::Dollar(word(&mut i)), line));
+		} else if c.is_ascii_digit() || (c == '-' || c == '+') && digit(i + 1) {
+			let start = i;
+			i += 1;
+			while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' && digit(i + 1) || chars[i] == '/') {
+				i += 1;
+			}
+			tokens.push((Token::Number(chars[start..i].iter().collect()), line));
+		} else if c.is_ascii_lowercase() {
+			tokens.push((Token::Lower(word(&mut i)), line));
+		} else if c.is_ascii_uppercase() || c == '_' {
+			tokens.push((Token::Upper(word(&mut i)), line));
+		} else {
+			match PUNCTUATION.iter().find(|p| p.chars().enumerate().all(|(j, pc)| chars.get(i + j) == Some(&pc))) {
+				Some(p) => {
+					i += p.len();
+					tokens.push((Token::Punct(p), line));
+				}
+				None => return Err(format!("line {}: Unexpected character {}", line, c)),
+			}
+		}
+	}
+
+	tokens.push((Token::End, line));
+	Ok(tokens)
+}
+
+/// A `fof` or `cnf` record.
+#[derive(Clone)]
+pub struct Annotated {
+	pub name: String,
+	pub role: String,
+	pub formula: Formula,
+	/// Where the formula comes from, such as `file(...)` or
+	/// `inference(rule, info, parents)`.
+	pub source: Option<GeneralTerm>,
+}
+
+/// The general terms of TPTP annotations.
+#[derive(Clone, PartialEq)]
+pub enum GeneralTerm {
+	/// A word, number or quoted string applied to arguments, or `:` applied
+	/// to its two sides. Formula data such as `$fof(...)` keeps only its
+	/// word.
+	Term(String, Vec<GeneralTerm>),
+	List(Vec<GeneralTerm>),
+	Var(String),
+}
+
+impl GeneralTerm {
+	/// The word and arguments of a term, if it is one.
+	pub fn term(&self) -> Option<(&str, &[GeneralTerm])> {
+		match self {
+			GeneralTerm::Term(w, args) => Some((w, args)),
+			_ => None,
+		}
+	}
+}
+
+/// Reads the `fof` and `cnf` records of a file without resolving includes,
+/// as for the derivations written by provers.
+pub fn annotated(text: &str) -> Result<Vec<Annotated>, String> {
+	let tokens = lex(text).map_err(|e| format!("TPTP: {}", e))?;
+	let mut parser = Parser { tokens, pos: 0, bound: vec!(), free: None };
+	let mut records = vec!();
+	while let Some(statement) = parser.statement().map_err(|e| format!("TPTP: {}", e))? {
+		match statement {
+			Statement::Include(file, _) => return Err(format!("TPTP: Unexpected include of {}", file)),
+			Statement::Formula(a) => records.push(a),
+		}
+	}
+	Ok(records)
+}
+
+enum Statement {
+	Include(String, Option<Vec<String>>),
+	Formula(Annotated),
+}
+
+struct Parser {
+	tokens: Vec<(Token, usize)>,
+	pos: usize,
+	/// The variables bound by enclosing quantifiers.
+	bound: Vec<String>,
+	/// The free variables of the clause being read, if any are allowed.
+	free: Option<Vec<String>>,
+}
+
+impl Parser {
+	fn peek(&self) -> &Token {
+		&self.tokens[self.pos].0
+	}
+
+	fn error<T>(&self, expected: &str) -> Result<T, String> {
+		let (t, line) = &self.tokens[self.pos];
+		Err(format!("line {}: Expected {}, found {}", line, expected, t.describe()))
+	}
+
+	fn is(&self, p: &str) -> bool {
+		matches!(self.peek(), Token::Punct(q) if *q == p)
+	}
+
+	/// Consumes the next token if `f` accepts it.
+	fn take<T, F: Fn(Token) -> Option<T>>(&mut self, expected: &str, f: F) -> Result<T, String> {
+		match f(self.peek().clone()) {
+			Some(t) => {
+				self.pos += 1;
+				Ok(t)
+			}
+			None => self.error(expected),
+		}
+	}
+
+	fn expect(&mut self, p: &str) -> Result<(), String> {
+		if self.is(p) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			self.error(p)
+		}
+	}
+
+	fn statement(&mut self) -> Result<Option<Statement>, String> {
+		if *self.peek() == Token::End {
+			return Ok(None);
+		}
+		let kind = self.take("fof, cnf or include", |t| match t {
+			Token::Lower(s) => Some(s),
+			_ => None,
+		})?;
+		if kind != "include" && kind != "fof" && kind != "cnf" {
+			self.pos -= 1;
+			return self.error("fof, cnf or include");
+		}
+		self.expect("(")?;
+
+		let statement = match &kind[..] {
+			"include" => {
+				let file = self.take("file name", |t| match t {
+					Token::Quoted(f) => Some(f),
+					_ => None,
+				})?;
+				let names = if self.is(",") {
+					self.pos += 1;
+					self.expect("[")?;
+					let mut names = vec!();
+					while !self.is("]") {
+						if !names.is_empty() {
+							self.expect(",")?;
+						}
+						names.push(self.name()?);
+					}
+					self.pos += 1;
+					Some(names)
+				} else {
+					None
+				};
+				Statement::Include(file, names)
+			}
+			_ => {
+				let name = self.name()?;
+				self.expect(",")?;
+				let role = self.take("role", |t| match t {
+					Token::Lower(r) => Some(r),
+					_ => None,
+				})?;
+				self.expect(",")?;
+
+				self.bound.clear();
+				self.free = if kind == "cnf" { Some(vec!()) } else { None };
+				let body = self.formula()?;
+				let f = match self.free.take() {
+					Some(vars) => vars.into_iter().rev()
+						.fold(body, |e, v| Formula::ForAll(ConstName::String(Ptr::new(v)), e.ptr())),
+					None => body,
+				};
+
+				let source = if self.is(",") {
+					self.pos += 1;
+					let source = self.general_term()?;
+					if self.is(",") {
+						self.pos += 1;
+						self.general_term()?;
+					}
+					Some(source)
+				} else {
+					None
+				};
+				Statement::Formula(Annotated { name, role, formula: f, source })
+			}
+		};
+
+		self.expect(")")?;
+		self.expect(".")?;
+		Ok(Some(statement))
+	}
+
+	fn name(&mut self) -> Result<String, String> {
+		self.take("name", |t| match t {
+			Token::Lower(s) | Token::Quoted(s) | Token::Number(s) => Some(s),
+			_ => None,
+		})
+	}
+
+	fn general_term(&mut self) -> Result<GeneralTerm, String> {
+		let t = if self.is("[") {
+			self.pos += 1;
+			GeneralTerm::List(self.general_terms("]")?)
+		} else {
+			match self.peek().clone() {
+				Token::Upper(v) => {
+					self.pos += 1;
+					GeneralTerm::Var(v)
+				}
+				Token::Dollar(w) => {
+					self.pos += 1;
+					if self.is("(") {
+						self.skip_parenthesized()?;
+					}
+					GeneralTerm::Term(format!("${}", w), vec!())
+				}
+				Token::Lower(w) | Token::Quoted(w) | Token::Number(w) | Token::Distinct(w) => {
+					self.pos += 1;
+					let args = if self.is("(") {
+						self.pos += 1;
+						self.general_terms(")")?
+					} else {
+						vec!()
+					};
+					GeneralTerm::Term(w, args)
+				}
+				_ => return self.error("general term"),
+			}
+		};
+
+		if self.is(":") {
+			self.pos += 1;
+			return Ok(GeneralTerm::Term(":".to_string(), vec!(t, self.general_term()?)));
+		}
+		Ok(t)
+	}
+
+	/// Reads comma separated general terms up to the closing punctuation.
+	fn general_terms(&mut self, close: &str) -> Result<Vec<GeneralTerm>, String> {
+		let mut v = vec!();
+		while !self.is(close) {
+			if !v.is_empty() {
+				self.expect(",")?;
+			}
+			v.push(self.general_term()?);
+		}
+		self.pos += 1;
+		Ok(v)
+	}
+
+	fn skip_parenthesized(&mut self) -> Result<(), String> {
+		let mut depth = 0;
+		loop {
+			match self.peek() {
+				Token::End => return self.error(")"),
+				Token::Punct("(") => depth += 1,
+				Token::Punct(")") => {
+					depth -= 1;
+					if depth == 0 {
+						self.pos += 1;
+						return Ok(());
+					}
+				}
+				_ => (),
+			}
+			self.pos += 1;
+		}
+	}
+
+	fn formula(&mut self) -> Result<Formula, String> {
+		let l = self.unitary()?;
+		let binary = ["<=>", "=>", "<=", "<~>", "~|", "~&"];
+		if let Some(op) = binary.iter().find(|op| self.is(op)) {
+			self.pos += 1;
+			let r = self.unitary()?.ptr();
+			let l = l.ptr();
+			return Ok(match *op {
+				"<=>" => Formula::IFF(l, r),
+				"=>" => Formula::Implies(l, r),
+				"<=" => Formula::Implies(r, l),
+				"<~>" => Formula::Not(Formula::IFF(l, r).ptr()),
+				"~|" => Formula::Not(Formula::Or(l, r).ptr()),
+				_ => Formula::Not(Formula::And(l, r).ptr()),
+			});
+		}
+
+		for op in ["&", "|"].iter() {
+			if self.is(op) {
+				let mut f = l;
+				while self.is(op) {
+					self.pos += 1;
+					let r = self.unitary()?.ptr();
+					f = if *op == "&" { Formula::And(f.ptr(), r) } else { Formula::Or(f.ptr(), r) };
+				}
+				return Ok(f);
+			}
+		}
+		Ok(l)
+	}
+
+	fn unitary(&mut self) -> Result<Formula, String> {
+		if self.is("(") {
+			self.pos += 1;
+			let f = self.formula()?;
+			self.expect(")")?;
+			Ok(f)
+		} else if self.is("~") {
+			self.pos += 1;
+			Ok(Formula::Not(self.unitary()?.ptr()))
+		} else if self.is("!") || self.is("?") {
+			let universal = self.is("!");
+			self.pos += 1;
+			self.expect("[")?;
+			let mut vars = vec!();
+			loop {
+				vars.push(self.take("variable", |t| match t {
+					Token::Upper(v) => Some(v),
+					_ => None,
+				})?);
+				if self.is("]") {
+					break;
+				}
+				self.expect(",")?;
+			}
+			self.pos += 1;
+			self.expect(":")?;
+
+			let depth = self.bound.len();
+			self.bound.extend(vars.iter().cloned());
+			let body = self.unitary();
+			self.bound.truncate(depth);
+			Ok(vars.into_iter().rev().fold(body?, |e, v| {
+				let v = ConstName::String(Ptr::new(v));
+				if universal { Formula::ForAll(v, e.ptr()) } else { Formula::Exists(v, e.ptr()) }
+			}))
+		} else {
+			self.atom()
+		}
+	}
+
+	fn atom(&mut self) -> Result<Formula, String> {
+		match self.peek().clone() {
+			Token::Dollar(_) => self.take("$true or $false", |t| match t {
+				Token::Dollar(ref d) if d == "true" => Some(Formula::True),
+				Token::Dollar(ref d) if d == "false" => Some(Formula::False),
+				_ => None,
+			}),
+			_ => {
+				let l = self.term()?;
+				if self.is("=") || self.is("!=") {
+					let negated = self.is("!=");
+					self.pos += 1;
+					let eq = Formula::Eq(l.ptr(), self.term()?.ptr());
+					return Ok(if negated { Formula::Not(eq.ptr()) } else { eq });
+				}
+				match l {
+					Formula::Free(_) => { self.pos -= 1; self.error("predicate") }
+					f => Ok(f),
+				}
+			}
+		}
+	}
+
+	fn term(&mut self) -> Result<Formula, String> {
+		if let Token::Upper(v) = self.peek().clone() {
+			if !self.bound.contains(&v) {
+				match &mut self.free {
+					Some(free) => if !free.contains(&v) {
+						free.push(v.clone());
+					}
+					None => return self.error("bound variable"),
+				}
+			}
+			self.pos += 1;
+			return Ok(Formula::Free(ConstName::String(Ptr::new(v))));
+		}
+		let head = self.take("term", |t| match t {
+			Token::Lower(s) | Token::Number(s) => Some(ConstName::String(Ptr::new(s))),
+			Token::Quoted(s) => Some(unname(&s)),
+			_ => None,
+		})?;
+		if !self.is("(") {
+			return Ok(Formula::Const(head));
+		}
+
+		self.pos += 1;
+		let mut v = vec!(Expr::Formula(Formula::Const(head)));
+		loop {
+			v.push(Expr::Formula(self.term()?));
+			if self.is(")") {
+				break;
+			}
+			self.expect(",")?;
+		}
+		self.pos += 1;
+		Ok(Formula::Relation(v))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tptp::export;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn app(f: &str, args: &[Formula]) -> Formula {
+		let mut v = vec!(Expr::Formula(Formula::Const(name(f))));
+		v.extend(args.iter().map(|a| Expr::Formula(a.clone())));
+		Formula::Relation(v)
+	}
+
+	fn var(s: &str) -> Formula {
+		Formula::Free(name(s))
+	}
+
+	/// A directory of its own for the files of one test.
+	fn scratch(test: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("logic-tptp-{}-{}", test, std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	#[test]
+	fn exported_problems_read_back() {
+		let x = name("X0");
+		let axiom = Formula::ForAll(x.clone(), Formula::Implies(
+			app("p", &[var("X0")]).ptr(),
+			Formula::Eq(var("X0").ptr(), app("f", &[Formula::Const(name("Two words"))]).ptr()).ptr()).ptr());
+		let conjecture = Formula::Exists(x, Formula::Not(app("p", &[var("X0")]).ptr()).ptr());
+
+		let text = export::problem(&[axiom.clone(), Formula::True], &conjecture, export::Options::default());
+		let p = parse(&text.unwrap()).unwrap();
+		assert!(p.axioms == vec!(("ax0".to_string(), axiom), ("ax1".to_string(), Formula::True)));
+		assert!(p.conjecture == Some(("goal".to_string(), conjecture)));
+		assert!(p.constants == vec!(name("p"), name("f"), name("Two words")));
+	}
+
+	#[test]
+	fn clauses_are_universally_closed() {
+		let p = parse("cnf(c, negated_conjecture, p(X) | ~ q(X, Y)).").unwrap();
+		let (x, y) = (name("X"), name("Y"));
+		let clause = Formula::Or(
+			app("p", &[var("X")]).ptr(),
+			Formula::Not(app("q", &[var("X"), var("Y")]).ptr()).ptr());
+		assert!(p.axioms[0].1 == Formula::ForAll(x, Formula::ForAll(y, clause.ptr()).ptr()));
+		assert!(p.goal() == Formula::False);
+	}
+
+	#[test]
+	fn derived_connectives_are_expanded() {
+		let p = parse("fof(a, axiom, (a ~| b) & a != b).").unwrap();
+		let (a, b) = (Formula::Const(name("a")).ptr(), Formula::Const(name("b")).ptr());
+		assert!(p.axioms[0].1 == Formula::And(
+			Formula::Not(Formula::Or(a.clone(), b.clone()).ptr()).ptr(),
+			Formula::Not(Formula::Eq(a, b).ptr()).ptr()));
+		assert!(parse("fof(a, conjecture, a). fof(b, conjecture, b).").is_err());
+		assert!(parse("fof(a, axiom, a").is_err());
+	}
+
+	#[test]
+	fn include_selects_the_listed_formulas() {
+		let dir = scratch("include");
+		fs::write(dir.join("axioms.ax"), "fof(a1, axiom, p).\nfof(a2, axiom, q).\nfof(a3, axiom, r).\n").unwrap();
+		fs::write(dir.join("all.p"), "include('axioms.ax').\nfof(g, conjecture, p).\n").unwrap();
+		fs::write(dir.join("some.p"), "include('axioms.ax', [a3, a1]).\nfof(g, conjecture, p).\n").unwrap();
+
+		let names = |p: &Problem| p.axioms.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+		assert!(names(&load(&dir.join("all.p")).unwrap()) == vec!("a1", "a2", "a3"));
+		assert!(names(&load(&dir.join("some.p")).unwrap()) == vec!("a1", "a3"));
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	#[test]
+	fn cyclic_includes_are_rejected() {
+		let dir = scratch("cycle");
+		fs::write(dir.join("a.p"), "include('b.p').\n").unwrap();
+		fs::write(dir.join("b.p"), "include('a.p').\n").unwrap();
+		let e = load(&dir.join("a.p")).err().unwrap();
+		assert!(e.contains("Cyclic include"));
+		assert!(load(&dir.join("missing.p")).is_err());
+		let _ = fs::remove_dir_all(&dir);
+	}
+}
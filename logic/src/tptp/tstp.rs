@@ -0,0 +1,406 @@
+//! Reconstruction of TSTP derivations as `Deduction`s.
+//!
+//! A derivation written by an external prover is never trusted: each of its
+//! steps is deduced again from the formulas of its parents, and the steps
+//! are chained into a refutation of the negated conjecture checked by the
+//! kernel. Inference rules are not replayed one by one. Clausification
+//! steps are proved by `prover::tautology` when they are propositional,
+//! and resolution, paramodulation and the other first-order steps by
+//! `prover::tableau`. Steps introducing Skolem constants, including
+//! Vampire's choice axioms, are deduced as the existential closure over the
+//! new constants followed by `ExistsExtract`. Skolem functions and
+//! definitions introduced by the prover cannot be deduced and are reported.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use formula::{Formula, Form, ConstName, Expr};
+use deduction::Deduction;
+use knowledge_base::{KnowledgeBase, ResultBase};
+use prover::{tautology, tableau};
+use tptp::parse::{self, Annotated, GeneralTerm, Problem};
+use Ptr;
+
+/// Reconstruction settings.
+#[derive(Clone, Copy)]
+pub struct Options {
+	/// The bound given to `prover::tableau` for each step.
+	pub bound: usize,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options { bound: 8 }
+	}
+}
+
+/// A step which could not be deduced.
+pub struct Failure {
+	pub name: String,
+	/// The inference rule named by the step, if any.
+	pub rule: String,
+	pub reason: String,
+}
+
+/// The report of an unsuccessful reconstruction.
+pub struct Unreconstructed {
+	pub failures: Vec<Failure>,
+}
+
+impl fmt::Display for Unreconstructed {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Could not reconstruct {} step(s):", self.failures.len())?;
+		for s in self.failures.iter() {
+			if s.name.is_empty() {
+				writeln!(f, "\t{}", s.reason)?;
+			} else {
+				writeln!(f, "\t{} ({}): {}", s.name, s.rule, s.reason)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Reads the records of a derivation from a file.
+pub fn load(path: &Path) -> Result<Vec<Annotated>, String> {
+	let text = fs::read_to_string(path)
+		.map_err(|e| format!("TPTP: {}: {}", path.display(), e))?;
+	parse::annotated(&text)
+		.map_err(|e| format!("TPTP: {}: {}", path.display(), &e["TPTP: ".len()..]))
+}
+
+/// Deduces the goal of `problem` from the refutation in `derivation`. On
+/// success the deduction is checked by `Deduction::deduced` against
+/// `problem`, and against any knowledge base with its axioms and constants.
+/// Otherwise every step which could not be deduced is reported; the later
+/// steps are still checked, assuming the failed ones.
+pub fn reconstruct(problem: &Problem, derivation: &[Annotated], opts: Options)
+-> Result<Deduction, Unreconstructed> {
+	let negated = problem.conjecture.as_ref().map(|(_, c)| Formula::Not(c.clone().ptr()));
+	let mut ctx = ResultBase::new(problem.clone());
+	if let Some(n) = &negated {
+		ctx = ctx.result_form(n.clone());
+	}
+
+	let mut known: Vec<(String, Form)> = vec!();
+	let mut steps = vec!();
+	let mut failures = vec!();
+	for a in derivation.iter() {
+		let origin = Origin::of(a);
+		let fail = |reason: String| Failure { name: a.name.clone(), rule: origin.rule(), reason };
+
+		// A conjecture stands for its negation, assumed for the refutation.
+		if a.role == "conjecture" {
+			match &negated {
+				Some(n) => known.push((a.name.clone(), n.clone().ptr())),
+				None => failures.push(fail("The problem has no conjecture".to_string())),
+			}
+			continue;
+		}
+
+		let formula = a.formula.clone().ptr();
+		match origin.premises(problem, &known).and_then(|p| step(&ctx, &p, &formula, opts)) {
+			Ok(d) => steps.push(d),
+			Err(reason) => failures.push(fail(reason)),
+		}
+
+		let mut symbols = vec!();
+		symbols_of(&formula, &mut symbols);
+		for (c, _) in symbols {
+			if !ctx.has_const(&c) {
+				ctx = ctx.result_const(c);
+			}
+		}
+		ctx = ctx.result_ptr(formula.clone());
+		known.push((a.name.clone(), formula));
+	}
+
+	if !ctx.contains(&Formula::False) {
+		failures.push(Failure {
+			name: String::new(),
+			rule: String::new(),
+			reason: "The derivation does not reach $false".to_string(),
+		});
+	}
+	if !failures.is_empty() {
+		return Err(Unreconstructed { failures });
+	}
+
+	let refutation = Deduction::Sequence(steps);
+	Ok(match &problem.conjecture {
+		Some((_, c)) => Deduction::Contradiction(c.clone().ptr(), Ptr::new(refutation)),
+		None => refutation,
+	})
+}
+
+/// The source of a record.
+enum Origin {
+	/// An input formula, with its name in the problem if given.
+	Input(Option<String>),
+	Inference(String, Vec<String>),
+	/// A formula introduced by the prover, such as a definition.
+	Introduced(String),
+}
+
+impl Origin {
+	fn of(a: &Annotated) -> Origin {
+		let (word, args) = match a.source.as_ref().and_then(|s| s.term()) {
+			Some(t) => t,
+			None => return Origin::Input(None),
+		};
+		match (word, args) {
+			("file", [_, GeneralTerm::Term(n, _)]) => Origin::Input(Some(n.clone())),
+			("file", _) => Origin::Input(None),
+			("inference", [GeneralTerm::Term(rule, _), _, GeneralTerm::List(parents)]) => {
+				let mut names = vec!();
+				parents_of(parents, &mut names);
+				Origin::Inference(rule.clone(), names)
+			}
+			("introduced", [GeneralTerm::Term(kind, _), ..]) => Origin::Introduced(kind.clone()),
+			(name, []) => Origin::Inference("copy".to_string(), vec!(name.to_string())),
+			(w, _) => Origin::Introduced(w.to_string()),
+		}
+	}
+
+	fn rule(&self) -> String {
+		match self {
+			Origin::Input(_) => "input".to_string(),
+			Origin::Inference(r, _) => r.clone(),
+			Origin::Introduced(k) => format!("introduced {}", k),
+		}
+	}
+
+	/// The sets of premises to try, in order.
+	fn premises(&self, problem: &Problem, known: &[(String, Form)]) -> Result<Vec<Vec<Form>>, String> {
+		match self {
+			Origin::Input(name) => {
+				let named = name.as_ref()
+					.and_then(|n| problem.theorem(&ConstName::String(Ptr::new(n.clone()))));
+				Ok(match named {
+					Some(f) => vec!(vec!(f.ptr())),
+					None => problem.axioms.iter().map(|(_, f)| vec!(f.clone().ptr())).collect(),
+				})
+			}
+			Origin::Inference(_, parents) => {
+				let mut premises = vec!();
+				for p in parents.iter() {
+					match known.iter().rev().find(|(n, _)| n == p) {
+						Some((_, f)) => premises.push(f.clone()),
+						None => return Err(format!("Unknown parent {}", p)),
+					}
+				}
+				Ok(vec!(premises))
+			}
+			Origin::Introduced(_) => Ok(vec!(vec!())),
+		}
+	}
+}
+
+/// Collects the names of the parent records, looking through nested
+/// inferences and skipping theories.
+fn parents_of(terms: &[GeneralTerm], out: &mut Vec<String>) {
+	for t in terms.iter() {
+		match t {
+			GeneralTerm::Term(w, args) if w == "inference" => if let Some(GeneralTerm::List(ps)) = args.get(2) {
+				parents_of(ps, out);
+			}
+			GeneralTerm::Term(w, args) if w == ":" => parents_of(&args[..1], out),
+			GeneralTerm::Term(w, args) if args.is_empty() => out.push(w.clone()),
+			_ => (),
+		}
+	}
+}
+
+/// Deduces `f` from one of the sets of premises.
+fn step<K: KnowledgeBase>(ctx: &ResultBase<K>, candidates: &[Vec<Form>], f: &Form, opts: Options)
+-> Result<Deduction, String> {
+	if ctx.contains(f) {
+		return Ok(Deduction::EmptyStep);
+	}
+
+	let mut symbols = vec!();
+	symbols_of(f, &mut symbols);
+	let new: Vec<ConstName> = symbols.into_iter()
+		.filter(|(c, _)| !ctx.has_const(c))
+		.map(|(c, arity)| if arity == 0 { Ok(c) } else { Err(c) })
+		.collect::<Result<_, _>>()
+		.map_err(|c| format!("Cannot introduce Skolem function {}", c.to_string()))?;
+
+	let mut reason = "No premises".to_string();
+	for premises in candidates.iter() {
+		let d = if new.is_empty() {
+			derive(ctx, premises, f, opts)
+		} else {
+			skolem(ctx, premises, f, &new, opts)
+		};
+		match d {
+			Ok(d) => if d.deduced(ctx.clone(), f) {
+				return Ok(d);
+			} else {
+				reason = "The deduction does not check".to_string();
+			}
+			Err(e) => reason = e,
+		}
+	}
+	Err(reason)
+}
+
+fn derive<K: KnowledgeBase>(ctx: &ResultBase<K>, premises: &[Form], f: &Form, opts: Options)
+-> Result<Deduction, String> {
+	if let Some(d) = tautology::prove(premises, f.clone()) {
+		return Ok(d);
+	}
+	tableau::prove(ctx, premises, f.clone(), opts.bound)
+		.map_err(|g| format!("Not deduced within bound {} ({} open branch(es))", opts.bound, g.open.len()))
+}
+
+/// Deduces the existential closure of `f` over the new constants, then
+/// extracts them one at a time.
+fn skolem<K: KnowledgeBase>(ctx: &ResultBase<K>, premises: &[Form], f: &Form, new: &[ConstName], opts: Options)
+-> Result<Deduction, String> {
+	let top = premises.iter().map(|p| p.max_int()).fold(f.max_int(), |a, b| a.max(b));
+	let vars: Vec<ConstName> = (0..new.len() as u64).map(|i| ConstName::Int(top + 1 + i)).collect();
+
+	let body = new.iter().zip(vars.iter())
+		.fold((**f).clone(), |e, (c, v)| abstract_const(&e, c, v));
+	let closure = vars.iter().rev()
+		.fold(body, |e, v| Formula::Exists(v.clone(), e.ptr()));
+
+	let mut steps = vec!(derive(ctx, premises, &closure.clone().ptr(), opts)?);
+	let mut current = closure;
+	for (c, v) in new.iter().zip(vars.iter()) {
+		let e = match current {
+			Formula::Exists(_, e) => e,
+			_ => unreachable!(),
+		};
+		steps.push(Deduction::ExistsExtract((v.clone(), e.clone()), c.clone()));
+		current = e.substitute(v, &Formula::Const(c.clone()));
+	}
+	Ok(Deduction::Sequence(steps))
+}
+
+/// Replaces the constant `c` with the variable `v`.
+fn abstract_const(f: &Formula, c: &ConstName, v: &ConstName) -> Formula {
+	let sub = |e: &Form| abstract_const(e, c, v).ptr();
+	match f {
+		Formula::Const(d) if d == c => Formula::Free(v.clone()),
+		Formula::Eq(l, r) => Formula::Eq(sub(l), sub(r)),
+		Formula::IFF(l, r) => Formula::IFF(sub(l), sub(r)),
+		Formula::And(l, r) => Formula::And(sub(l), sub(r)),
+		Formula::Or(l, r) => Formula::Or(sub(l), sub(r)),
+		Formula::Implies(l, r) => Formula::Implies(sub(l), sub(r)),
+		Formula::Not(e) => Formula::Not(sub(e)),
+		Formula::ForAll(x, e) => Formula::ForAll(x.clone(), sub(e)),
+		Formula::Exists(x, e) => Formula::Exists(x.clone(), sub(e)),
+		Formula::ForAllSeq(a, x, e) => Formula::ForAllSeq(*a, x.clone(), sub(e)),
+		Formula::Subst(b, x, s) => Formula::Subst(sub(b), x.clone(), sub(s)),
+		Formula::Relation(args) => Formula::Relation(args.iter().map(|e| match e {
+			Expr::Formula(e) => Expr::Formula(abstract_const(e, c, v)),
+			o => o.clone(),
+		}).collect()),
+		o => o.clone(),
+	}
+}
+
+/// Collects the constants of `f` with the number of arguments they are
+/// applied to, in order of first use.
+fn symbols_of(f: &Formula, out: &mut Vec<(ConstName, usize)>) {
+	let add = |c: &ConstName, arity: usize, out: &mut Vec<(ConstName, usize)>| {
+		if !out.iter().any(|(d, _)| d == c) {
+			out.push((c.clone(), arity));
+		}
+	};
+	match f {
+		Formula::Const(c) => add(c, 0, out),
+		Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+		| Formula::Or(l, r) | Formula::Implies(l, r) | Formula::Subst(l, _, r) => {
+			symbols_of(l, out);
+			symbols_of(r, out);
+		}
+		Formula::Not(e) | Formula::ForAll(_, e) | Formula::Exists(_, e) | Formula::ForAllSeq(_, _, e) =>
+			symbols_of(e, out),
+		Formula::Relation(v) => {
+			if let Some(Expr::Formula(Formula::Const(c))) = v.first() {
+				add(c, v.len() - 1, out);
+			}
+			for e in v.iter() {
+				if let Expr::Formula(e) = e {
+					symbols_of(e, out);
+				}
+			}
+		}
+		Formula::Free(_) | Formula::True | Formula::False => (),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PROBLEM: &str = "fof(a1, axiom, p => q).\nfof(a2, axiom, p).\nfof(g, conjecture, q).\n";
+
+	fn refute(problem: &str, derivation: &str) -> Result<(Problem, Deduction), Unreconstructed> {
+		let problem = parse::parse(problem).unwrap();
+		let derivation = parse::annotated(derivation).unwrap();
+		let d = reconstruct(&problem, &derivation, Options::default())?;
+		Ok((problem, d))
+	}
+
+	fn reasons(e: Unreconstructed) -> Vec<(String, String)> {
+		e.failures.into_iter().map(|f| (f.name, f.reason)).collect()
+	}
+
+	#[test]
+	fn refutation_proves_the_conjecture() {
+		let (problem, d) = refute(PROBLEM, concat!(
+			"fof(a1, axiom, p => q, file('x.p', a1)).\n",
+			"fof(a2, axiom, p, file('x.p', a2)).\n",
+			"fof(g, conjecture, q, file('x.p', g)).\n",
+			"fof(ng, negated_conjecture, ~ q, inference(negated_conjecture, [status(cth)], [g])).\n",
+			"fof(c1, plain, q, inference(mp, [status(thm)], [a1, a2])).\n",
+			"fof(f, plain, $false, inference(resolution, [status(thm)], [c1, ng])).\n",
+		)).ok().unwrap();
+		assert!(d.deduced(problem.clone(), &problem.goal()));
+	}
+
+	#[test]
+	fn skolem_constants_are_extracted() {
+		let (problem, d) = refute(
+			"fof(a1, axiom, ? [X] : r(X)).\nfof(a2, axiom, ! [X] : ~ r(X)).\n",
+			concat!(
+				"fof(a1, axiom, ? [X] : r(X), file('x.p', a1)).\n",
+				"fof(a2, axiom, ! [X] : ~ r(X), file('x.p', a2)).\n",
+				"fof(s, plain, r(sk0), inference(skolemisation, [status(esa)], [a1])).\n",
+				"fof(f, plain, $false, inference(resolution, [status(thm)], [s, a2])).\n",
+			)).ok().unwrap();
+		assert!(d.deduced(problem, &Formula::False));
+	}
+
+	#[test]
+	fn unsound_steps_are_reported() {
+		let e = refute(PROBLEM, concat!(
+			"fof(ng, negated_conjecture, ~ q, inference(negated_conjecture, [status(cth)], [g])).\n",
+			"fof(g, conjecture, q, file('x.p', g)).\n",
+			"fof(c1, plain, q, inference(mp, [status(thm)], [a2])).\n",
+			"fof(f, plain, $false, inference(resolution, [status(thm)], [c1, ng])).\n",
+		)).err().unwrap();
+		let r = reasons(e);
+		assert!(r.len() == 2);
+		assert!(r[0].0 == "ng" && r[0].1 == "Unknown parent g");
+		assert!(r[1].0 == "c1");
+	}
+
+	#[test]
+	fn skolem_functions_and_open_derivations_are_reported() {
+		let e = refute(
+			"fof(a1, axiom, ! [X] : ? [Y] : r(X, Y)).\n",
+			concat!(
+				"fof(a1, axiom, ! [X] : ? [Y] : r(X, Y), file('x.p', a1)).\n",
+				"fof(s, plain, ! [X] : r(X, sk0(X)), inference(skolemisation, [status(esa)], [a1])).\n",
+			)).err().unwrap();
+		let r = reasons(e);
+		assert!(r[0].0 == "s" && r[0].1.starts_with("Cannot introduce Skolem function"));
+		assert!(r[1].1 == "The derivation does not reach $false");
+	}
+}
@@ -0,0 +1,583 @@
+//! Syntactic unification and one-way matching of formulas.
+//!
+//! Free variables and sequence variables which are not bound within the
+//! formulas act as pattern variables. `ResultBase::contains` compares
+//! formulas structurally, so binders are never renamed: a quantifier only
+//! matches a quantifier over the same name, and a variable is never bound to
+//! a term mentioning a variable bound around it.
+//!
+//! A sequence variable `Seq::Free(n, v)` stands for exactly `n` formulas, so
+//! `Relation` argument lists are aligned position by position, with `Tail`
+//! and `Head` constraining a prefix of the sequence. Positions a pattern
+//! never mentions are instantiated with `True`.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use formula::{Formula, ConstName, Expr, Seq};
+
+/// The names which may be instantiated.
+pub enum Vars {
+	/// Every free variable and sequence variable.
+	All,
+	/// Only the listed variables and sequence variables.
+	Only(Vec<ConstName>, Vec<(u64, ConstName)>),
+}
+
+impl Vars {
+	fn term(&self, c: &ConstName) -> bool {
+		match self {
+			Vars::All => true,
+			Vars::Only(t, _) => t.contains(c),
+		}
+	}
+
+	fn seq(&self, ar: u64, c: &ConstName) -> bool {
+		match self {
+			Vars::All => true,
+			Vars::Only(_, s) => s.iter().any(|(a, v)| *a == ar && v == c),
+		}
+	}
+}
+
+/// A simultaneous substitution of terms for variables and of formula lists
+/// for sequence variables. Each binding can be given to `substitute` or
+/// `substitute_seq`, or as the witness of `ForAllExtract` and
+/// `ForAllSeqExtract`.
+#[derive(Clone, Default)]
+pub struct Substitution {
+	pub terms: Vec<(ConstName, Formula)>,
+	pub seqs: Vec<(u64, ConstName, Vec<Formula>)>,
+}
+
+impl Substitution {
+	pub fn new() -> Substitution {
+		Substitution::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.terms.is_empty() && self.seqs.is_empty()
+	}
+
+	pub fn term(&self, c: &ConstName) -> Option<&Formula> {
+		self.terms.iter().find(|(v, _)| v == c).map(|(_, t)| t)
+	}
+
+	pub fn seq(&self, ar: u64, c: &ConstName) -> Option<&Vec<Formula>> {
+		self.seqs.iter().find(|(a, v, _)| *a == ar && v == c).map(|(_, _, t)| t)
+	}
+
+	/// Applies every binding at once, so a term bound to one variable is
+	/// never rewritten by the binding of another.
+	pub fn apply(&self, f: &Formula) -> Formula {
+		let mut fresh = self.terms.iter().map(|(_, t)| t.max_int())
+			.chain(self.seqs.iter().flat_map(|(_, _, v)| v.iter().map(|t| t.max_int())))
+			.fold(f.max_int(), |a, b| a.max(b));
+		let mut placeholder = || { fresh += 1; ConstName::Int(fresh) };
+
+		let mut pending = vec!();
+		let mut g = f.clone();
+		for (v, t) in self.terms.iter() {
+			let p = placeholder();
+			g = g.substitute(v, &Formula::Free(p.clone()));
+			pending.push((p, t));
+		}
+		for (ar, v, ts) in self.seqs.iter() {
+			let ps: Vec<Formula> = ts.iter().map(|t| {
+				let p = placeholder();
+				pending.push((p.clone(), t));
+				Formula::Free(p)
+			}).collect();
+			g = g.substitute_seq(*ar, v, &ps);
+		}
+
+		for (p, t) in pending {
+			g = g.substitute(&p, t);
+		}
+		g
+	}
+}
+
+impl fmt::Display for Substitution {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let terms = self.terms.iter()
+			.map(|(v, t)| format!("{} := {}", v.to_string(), t.to_string()));
+		let seqs = self.seqs.iter().map(|(ar, v, ts)| format!(
+			"{}...{} := [{}]",
+			v.to_string(),
+			ar,
+			ts.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")));
+		write!(f, "{{{}}}", terms.chain(seqs).collect::<Vec<_>>().join(", "))
+	}
+}
+
+/// Finds a substitution instantiating the variables of `pattern` so that it
+/// becomes `target`. The free names of `target` are never instantiated.
+pub fn matching(pattern: &Formula, target: &Formula, vars: &Vars) -> Option<Substitution> {
+	Unifier::new(vars, false).run(Goal::Form(
+		pattern.clone(), true,
+		target.clone(), false,
+		Scope::default()))
+}
+
+/// Like `matching`, for `Relation` argument lists.
+pub fn matching_args(pattern: &[Expr], target: &[Expr], vars: &Vars) -> Option<Substitution> {
+	matching(&Formula::Relation(pattern.to_vec()), &Formula::Relation(target.to_vec()), vars)
+}
+
+/// Finds a most general substitution making `a` and `b` equal, where the
+/// variables of both formulas may be instantiated. Sequence variables are
+/// only ever bound to formulas, so two distinct sequence variables at the
+/// same position do not unify.
+pub fn unify(a: &Formula, b: &Formula, vars: &Vars) -> Option<Substitution> {
+	Unifier::new(vars, true).run(Goal::Form(
+		a.clone(), true,
+		b.clone(), true,
+		Scope::default()))
+}
+
+/// Like `unify`, for `Relation` argument lists.
+pub fn unify_args(a: &[Expr], b: &[Expr], vars: &Vars) -> Option<Substitution> {
+	unify(&Formula::Relation(a.to_vec()), &Formula::Relation(b.to_vec()), vars)
+}
+
+/// The names bound around the formulas being compared.
+#[derive(Clone, Default)]
+struct Scope {
+	terms: Vec<ConstName>,
+	seqs: Vec<(u64, ConstName)>,
+}
+
+/// One position of a flattened argument list.
+#[derive(Clone)]
+enum Cell {
+	Form(Formula, bool),
+	/// The position of a sequence variable.
+	Pos(u64, ConstName, usize),
+	/// A sequence expression which is not a variable.
+	Rigid(Expr),
+}
+
+/// Two formulas or positions to make equal. The flags tell whether the
+/// variables of each side may be instantiated.
+enum Goal {
+	Form(Formula, bool, Formula, bool, Scope),
+	Cells(Cell, Cell, Scope),
+}
+
+enum Step {
+	Done,
+	Fail,
+	Stuck(Goal),
+}
+
+struct Unifier<'a> {
+	vars: &'a Vars,
+	/// Whether bound terms may themselves contain variables.
+	unify: bool,
+	terms: Vec<(ConstName, Formula)>,
+	seqs: Vec<(u64, ConstName, Vec<Option<Formula>>)>,
+}
+
+impl <'a> Unifier<'a> {
+	fn new(vars: &'a Vars, unify: bool) -> Unifier<'a> {
+		Unifier { vars, unify, terms: vec!(), seqs: vec!() }
+	}
+
+	fn run(mut self, goal: Goal) -> Option<Substitution> {
+		let mut goals = VecDeque::new();
+		goals.push_back(goal);
+
+		let mut stuck = 0;
+		while let Some(g) = goals.pop_front() {
+			match self.step(g, &mut goals) {
+				Step::Done => stuck = 0,
+				Step::Fail => return None,
+				Step::Stuck(g) => {
+					goals.push_back(g);
+					stuck += 1;
+					if stuck >= goals.len() {
+						return None;
+					}
+				}
+			}
+		}
+
+		let terms = self.terms.iter()
+			.map(|(v, t)| (v.clone(), self.resolve_all(t)))
+			.collect();
+		let seqs = self.seqs.iter()
+			.map(|(ar, v, ts)| (*ar, v.clone(), ts.iter().map(|t| match t {
+				Some(t) => self.resolve_all(t),
+				None => Formula::True,
+			}).collect()))
+			.collect();
+		Some(Substitution { terms, seqs })
+	}
+
+	fn step(&mut self, goal: Goal, goals: &mut VecDeque<Goal>) -> Step {
+		match goal {
+			Goal::Form(l, lf, r, rf, scope) => self.step_form(l, lf, r, rf, scope, goals),
+			Goal::Cells(a, b, scope) => self.step_cells(a, b, scope, goals),
+		}
+	}
+
+	fn step_form(&mut self,
+		l: Formula, lf: bool,
+		r: Formula, rf: bool,
+		scope: Scope,
+		goals: &mut VecDeque<Goal>)
+	-> Step {
+		let (l, lf) = self.resolve(l, lf, &scope);
+		let (r, rf) = self.resolve(r, rf, &scope);
+
+		if let Some(x) = self.var(&l, lf, &scope) {
+			if self.var(&r, rf, &scope).as_ref() == Some(&x) {
+				return Step::Done;
+			}
+			return self.bind(x, r, &scope);
+		}
+		if let Some(y) = self.var(&r, rf, &scope) {
+			return self.bind(y, l, &scope);
+		}
+		if !lf && !rf {
+			return check(l == r);
+		}
+
+		let mut push = |a: &Formula, b: &Formula, scope: &Scope|
+			goals.push_back(Goal::Form(a.clone(), lf, b.clone(), rf, scope.clone()));
+
+		match (&l, &r) {
+			(Formula::True, Formula::True) | (Formula::False, Formula::False) => Step::Done,
+			(Formula::Eq(a, b), Formula::Eq(c, d))
+			| (Formula::IFF(a, b), Formula::IFF(c, d))
+			| (Formula::And(a, b), Formula::And(c, d))
+			| (Formula::Or(a, b), Formula::Or(c, d))
+			| (Formula::Implies(a, b), Formula::Implies(c, d)) => {
+				push(a, c, &scope);
+				push(b, d, &scope);
+				Step::Done
+			}
+			(Formula::Not(a), Formula::Not(b)) => {
+				push(a, b, &scope);
+				Step::Done
+			}
+			(Formula::ForAll(x, a), Formula::ForAll(y, b))
+			| (Formula::Exists(x, a), Formula::Exists(y, b)) if x == y => {
+				let mut inner = scope.clone();
+				inner.terms.push(x.clone());
+				push(a, b, &inner);
+				Step::Done
+			}
+			(Formula::Subst(a, x, s), Formula::Subst(b, y, t)) if x == y => {
+				let mut inner = scope.clone();
+				inner.terms.push(x.clone());
+				push(a, b, &inner);
+				push(s, t, &scope);
+				Step::Done
+			}
+			(Formula::ForAllSeq(n, x, a), Formula::ForAllSeq(m, y, b)) if n == m && x == y => {
+				let mut inner = scope.clone();
+				inner.seqs.push((*n, x.clone()));
+				push(a, b, &inner);
+				Step::Done
+			}
+			(Formula::Relation(a), Formula::Relation(b)) => {
+				let a = self.flatten(a, lf, &scope);
+				let b = self.flatten(b, rf, &scope);
+				if a.len() != b.len() {
+					return Step::Fail;
+				}
+				for (x, y) in a.into_iter().zip(b) {
+					goals.push_back(Goal::Cells(x, y, scope.clone()));
+				}
+				Step::Done
+			}
+			(Formula::Free(a), Formula::Free(b))
+			| (Formula::Const(a), Formula::Const(b)) => check(a == b),
+			_ => Step::Fail,
+		}
+	}
+
+	fn step_cells(&mut self, a: Cell, b: Cell, scope: Scope, goals: &mut VecDeque<Goal>) -> Step {
+		match (self.resolve_cell(a), self.resolve_cell(b)) {
+			(Cell::Pos(n, v, i), Cell::Pos(m, w, j)) =>
+				if n == m && v == w && i == j {
+					Step::Done
+				} else {
+					Step::Stuck(Goal::Cells(Cell::Pos(n, v, i), Cell::Pos(m, w, j), scope))
+				},
+			(Cell::Pos(n, v, i), Cell::Form(t, _))
+			| (Cell::Form(t, _), Cell::Pos(n, v, i)) => self.bind_pos(n, v, i, t, &scope),
+			(Cell::Form(x, xf), Cell::Form(y, yf)) => {
+				goals.push_back(Goal::Form(x, xf, y, yf, scope));
+				Step::Done
+			}
+			(Cell::Rigid(x), Cell::Rigid(y)) => check(x == y),
+			_ => Step::Fail,
+		}
+	}
+
+	/// The variable `f` is, if it may be instantiated.
+	fn var(&self, f: &Formula, flag: bool, scope: &Scope) -> Option<ConstName> {
+		match f {
+			Formula::Free(x) if flag && !scope.terms.contains(x) && self.vars.term(x) =>
+				Some(x.clone()),
+			_ => None,
+		}
+	}
+
+	fn seq_var(&self, ar: u64, v: &ConstName, flag: bool, scope: &Scope) -> bool {
+		flag && !scope.seqs.iter().any(|(a, w)| *a == ar && w == v) && self.vars.seq(ar, v)
+	}
+
+	/// Replaces a bound variable with its term.
+	fn resolve(&self, f: Formula, flag: bool, scope: &Scope) -> (Formula, bool) {
+		let mut f = (f, flag);
+		while let Some(x) = self.var(&f.0, f.1, scope) {
+			match self.terms.iter().find(|(v, _)| *v == x) {
+				Some((_, t)) => f = (t.clone(), self.unify),
+				None => break,
+			}
+		}
+		f
+	}
+
+	fn resolve_cell(&self, c: Cell) -> Cell {
+		if let Cell::Pos(n, v, i) = &c {
+			if let Some(Some(t)) = self.position(*n, v, *i) {
+				return Cell::Form(t.clone(), self.unify);
+			}
+		}
+		c
+	}
+
+	fn position(&self, ar: u64, v: &ConstName, i: usize) -> Option<&Option<Formula>> {
+		self.seqs.iter()
+			.find(|(a, w, _)| *a == ar && w == v)
+			.map(|(_, _, ts)| &ts[i])
+	}
+
+	/// Applies the bindings made so far, up to sequence variables which are
+	/// only partially known.
+	fn resolve_all(&self, f: &Formula) -> Formula {
+		if !self.unify {
+			return f.clone();
+		}
+
+		let mut f = f.clone();
+		for _ in 0..=self.terms.len() + self.seqs.len() {
+			let mut g = f.clone();
+			for (v, t) in self.terms.iter() {
+				g = g.substitute(v, t);
+			}
+			for (ar, v, ts) in self.seqs.iter() {
+				if let Some(ts) = ts.iter().cloned().collect::<Option<Vec<_>>>() {
+					g = g.substitute_seq(*ar, v, &ts);
+				}
+			}
+			if g == f {
+				break;
+			}
+			f = g;
+		}
+		f
+	}
+
+	fn bind(&mut self, x: ConstName, t: Formula, scope: &Scope) -> Step {
+		if mentions(&t, &scope.terms, &scope.seqs) {
+			return Step::Fail;
+		}
+		if self.unify && mentions(&self.resolve_all(&t), std::slice::from_ref(&x), &[]) {
+			return Step::Fail;
+		}
+
+		self.terms.push((x, t));
+		Step::Done
+	}
+
+	fn bind_pos(&mut self, ar: u64, v: ConstName, i: usize, t: Formula, scope: &Scope) -> Step {
+		if mentions(&t, &scope.terms, &scope.seqs) {
+			return Step::Fail;
+		}
+		if self.unify && mentions(&self.resolve_all(&t), &[], &[(ar, v.clone())]) {
+			return Step::Fail;
+		}
+
+		match self.seqs.iter_mut().find(|(a, w, _)| *a == ar && *w == v) {
+			Some((_, _, ts)) => ts[i] = Some(t),
+			None => {
+				let mut ts = vec!(None; ar as usize);
+				ts[i] = Some(t);
+				self.seqs.push((ar, v, ts));
+			}
+		}
+		Step::Done
+	}
+
+	/// Splits an argument list into positions, one per formula and one per
+	/// element of a sequence variable.
+	fn flatten(&self, args: &[Expr], flag: bool, scope: &Scope) -> Vec<Cell> {
+		let mut cells = vec!();
+		for e in args.iter() {
+			match e {
+				Expr::Formula(f) => cells.push(Cell::Form(f.clone(), flag)),
+				Expr::Head(s) | Expr::Seq(s) => {
+					let (ar, v, tails) = base(s);
+					if !self.seq_var(ar, v, flag, scope) {
+						cells.push(Cell::Rigid(e.clone()));
+						continue;
+					}
+					let len = (ar as usize).saturating_sub(tails);
+					let len = match e {
+						Expr::Head(_) => len.min(1),
+						_ => len,
+					};
+					cells.extend((0..len).map(|i| Cell::Pos(ar, v.clone(), i)));
+				}
+			}
+		}
+		cells
+	}
+}
+
+fn check(b: bool) -> Step {
+	if b { Step::Done } else { Step::Fail }
+}
+
+/// The sequence variable under a sequence expression, with the number of
+/// `Tail`s applied to it.
+fn base(s: &Seq) -> (u64, &ConstName, usize) {
+	match s {
+		Seq::Free(ar, v) => (*ar, v, 0),
+		Seq::Tail(p) => {
+			let (ar, v, n) = base(p);
+			(ar, v, n + 1)
+		}
+	}
+}
+
+/// Whether any of the names occurs free in the formula.
+fn mentions(f: &Formula, terms: &[ConstName], seqs: &[(u64, ConstName)]) -> bool {
+	if terms.is_empty() && seqs.is_empty() {
+		return false;
+	}
+
+	let without = |v: &ConstName| -> Vec<ConstName> {
+		terms.iter().filter(|x| *x != v).cloned().collect()
+	};
+	match f {
+		Formula::Eq(l, r) | Formula::IFF(l, r) | Formula::And(l, r)
+		| Formula::Or(l, r) | Formula::Implies(l, r) =>
+			mentions(l, terms, seqs) || mentions(r, terms, seqs),
+		Formula::Not(l) => mentions(l, terms, seqs),
+		Formula::Relation(l) => l.iter().any(|e| match e {
+			Expr::Formula(e) => mentions(e, terms, seqs),
+			Expr::Head(s) | Expr::Seq(s) => {
+				let (ar, v, _) = base(s);
+				seqs.iter().any(|(a, w)| *a == ar && w == v)
+			}
+		}),
+		Formula::ForAll(v, e) | Formula::Exists(v, e) => mentions(e, &without(v), seqs),
+		Formula::Subst(body, v, sub) =>
+			mentions(body, &without(v), seqs) || mentions(sub, terms, seqs),
+		Formula::ForAllSeq(ar, v, e) => {
+			let inner: Vec<(u64, ConstName)> = seqs.iter()
+				.filter(|(a, w)| !(a == ar && w == v))
+				.cloned()
+				.collect();
+			mentions(e, terms, &inner)
+		}
+		Formula::Free(v) => terms.contains(v),
+		Formula::Const(_) | Formula::True | Formula::False => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use Ptr;
+
+	fn name(s: &str) -> ConstName {
+		ConstName::String(Ptr::new(s.to_string()))
+	}
+
+	fn var(s: &str) -> Formula {
+		Formula::Free(name(s))
+	}
+
+	fn konst(s: &str) -> Formula {
+		Formula::Const(name(s))
+	}
+
+	fn app(f: &str, args: &[Formula]) -> Formula {
+		let mut v = vec!(Expr::Formula(konst(f)));
+		v.extend(args.iter().map(|a| Expr::Formula(a.clone())));
+		Formula::Relation(v)
+	}
+
+	#[test]
+	fn unify_binds_both_sides() {
+		let a = app("f", &[var("x"), konst("b")]);
+		let b = app("f", &[konst("a"), var("y")]);
+		let s = unify(&a, &b, &Vars::All).unwrap();
+		assert!(s.term(&name("x")) == Some(&konst("a")));
+		assert!(s.term(&name("y")) == Some(&konst("b")));
+		assert!(s.apply(&a) == s.apply(&b));
+
+		assert!(unify(&a, &app("g", &[konst("a"), var("y")]), &Vars::All).is_none());
+	}
+
+	#[test]
+	fn unify_checks_occurrences() {
+		let x = var("x");
+		assert!(unify(&x, &app("f", std::slice::from_ref(&x)), &Vars::All).is_none());
+		let s = unify(&x, &app("f", &[var("y")]), &Vars::All).unwrap();
+		assert!(s.apply(&x) == app("f", &[var("y")]));
+	}
+
+	#[test]
+	fn matching_instantiates_the_pattern_only() {
+		let pattern = app("P", &[var("x"), var("x")]);
+		let s = matching(&pattern, &app("P", &[konst("a"), konst("a")]), &Vars::All).unwrap();
+		assert!(s.term(&name("x")) == Some(&konst("a")));
+		assert!(matching(&pattern, &app("P", &[konst("a"), konst("b")]), &Vars::All).is_none());
+		assert!(matching(&app("P", &[konst("a")]), &app("P", &[var("y")]), &Vars::All).is_none());
+
+		let only = Vars::Only(vec!(name("y")), vec!());
+		assert!(matching(&pattern, &app("P", &[konst("a"), konst("a")]), &only).is_none());
+	}
+
+	#[test]
+	fn bound_variables_are_not_captured() {
+		let y = name("y");
+		let pattern = Formula::ForAll(y.clone(), app("P", &[var("x"), var("y")]).ptr());
+		let captured = Formula::ForAll(y.clone(), app("P", &[var("y"), var("y")]).ptr());
+		assert!(matching(&pattern, &captured, &Vars::All).is_none());
+
+		let target = Formula::ForAll(y.clone(), app("P", &[konst("a"), var("y")]).ptr());
+		let s = matching(&pattern, &target, &Vars::All).unwrap();
+		assert!(s.term(&name("x")) == Some(&konst("a")));
+		assert!(s.term(&y).is_none());
+	}
+
+	#[test]
+	fn apply_substitutes_simultaneously() {
+		let mut s = Substitution::new();
+		s.terms.push((name("x"), var("y")));
+		s.terms.push((name("y"), var("x")));
+		assert!(s.apply(&app("P", &[var("x"), var("y")])) == app("P", &[var("y"), var("x")]));
+	}
+
+	#[test]
+	fn sequence_variable_matches_its_arguments() {
+		let s = name("s");
+		let pattern = Formula::Relation(vec!(
+			Expr::Formula(konst("R")),
+			Expr::Seq(Seq::Free(2, s.clone()))));
+		let target = app("R", &[konst("a"), konst("b")]);
+		let sub = matching(&pattern, &target, &Vars::All).unwrap();
+		assert!(sub.seq(2, &s) == Some(&vec!(konst("a"), konst("b"))));
+		assert!(matching(&pattern, &app("R", &[konst("a")]), &Vars::All).is_none());
+	}
+}
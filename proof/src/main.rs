@@ -1,23 +1,35 @@
 extern crate sqlite;
+extern crate serde_json;
 extern crate logic;
 
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::env;
+use std::path::Path;
+use std::process;
 use logic::{
+	batch,
 	knowledge_base,
 	formula,
-	deduction
+	check,
+	deduction,
+	deps,
+	doc,
+	hash,
+	sat,
+	tptp
 };
 
 use logic::formula::Formula;
-use logic::deduction::Deduction;
+use logic::deduction::{Deduction, CalcRel};
 
-pub type Ptr<K> = Rc<K>;
+pub use logic::Ptr;
 
 
 const DB_FILE_NAME: &str = "PROOF_DATABASE.db";
+/// `theorem` and `proof` hold the statement and the checked deduction as
+/// JSON, and `proof_raw` the source of the proof.
 const DB_INIT_STR1: &str = "
-	CREATE TABLE theories (
+	CREATE TABLE IF NOT EXISTS theories (
+		name TEXT NOT NULL,
 		theorem TEXT NOT NULL, 
 		description TEXT NOT NULL,
 		theory TEXT NOT NULL,
@@ -27,13 +39,33 @@ const DB_INIT_STR1: &str = "
 ";
 
 const DB_INIT_STR2: &str = "
-	CREATE TABLE constants (
+	CREATE TABLE IF NOT EXISTS constants (
 		name TEXT NOT NULL, 
 		description TEXT NOT NULL,
 		theory TEXT NOT NULL
 	)
 ";
 
+/// The direct dependencies of each theorem, as found by `deps::Graph`.
+const DB_INIT_STR3: &str = "
+	CREATE TABLE IF NOT EXISTS dependencies (
+		theorem TEXT NOT NULL,
+		kind TEXT NOT NULL,
+		dependency TEXT NOT NULL
+	)
+";
+
+/// The successful checks, keyed by the content hashes of what they
+/// checked, written as hex by `hash::ContentHash`.
+const DB_INIT_STR4: &str = "
+	CREATE TABLE IF NOT EXISTS proof_cache (
+		theorem TEXT NOT NULL,
+		statement TEXT NOT NULL,
+		proof TEXT NOT NULL,
+		dependencies TEXT NOT NULL
+	)
+";
+
 struct Database {
 	theories: sqlite::Connection
 }
@@ -42,13 +74,111 @@ impl Database {
 	fn new() -> Database {
 		let conn = sqlite::open(DB_FILE_NAME);
 
-		Database {
+		let db = Database {
 			theories: conn.unwrap()
+		};
+		db.init();
+		db
+	}
+
+	/// Creates the tables which do not exist yet.
+	fn init(&self) {
+		for table in [DB_INIT_STR1, DB_INIT_STR2, DB_INIT_STR3, DB_INIT_STR4].iter() {
+			self.theories.execute(table).unwrap();
+		}
+	}
+
+	/// Reads the theorems and constants of every theory.
+	fn library(&self) -> Result<doc::Library, String> {
+		let mut library = doc::Library::default();
+
+		let mut rows = self.theories
+			.prepare("SELECT name, theorem, description, theory, proof, proof_raw FROM theories")
+			.map_err(|e| e.to_string())?;
+		while let sqlite::State::Row = rows.next().map_err(|e| e.to_string())? {
+			let read = |i: usize| rows.read::<String, _>(i).map_err(|e| e.to_string());
+			let name = read(0)?;
+			let statement = serde_json::from_str(&read(1)?)
+				.map_err(|e| format!("Theorem {} has an unreadable statement: {}", name, e))?;
+			library.theorems.push(doc::Theorem {
+				name,
+				statement,
+				description: read(2)?,
+				theory: read(3)?,
+				proof: serde_json::from_str(&read(4)?).ok(),
+				source: read(5)?,
+			});
+		}
+
+		let mut rows = self.theories
+			.prepare("SELECT name, description, theory FROM constants")
+			.map_err(|e| e.to_string())?;
+		while let sqlite::State::Row = rows.next().map_err(|e| e.to_string())? {
+			let read = |i: usize| rows.read::<String, _>(i).map_err(|e| e.to_string());
+			library.constants.push(doc::Constant {
+				name: read(0)?,
+				description: read(1)?,
+				theory: read(2)?,
+			});
+		}
+		Ok(library)
+	}
+
+	/// Replaces the recorded dependencies with those of the graph.
+	fn record_dependencies(&self, graph: &deps::Graph) -> Result<(), String> {
+		self.theories.execute("DELETE FROM dependencies").map_err(|e| e.to_string())?;
+		for n in graph.nodes.iter() {
+			for d in n.deps.iter() {
+				let mut insert = self.theories
+					.prepare("INSERT INTO dependencies (theorem, kind, dependency) VALUES (?, ?, ?)")
+					.map_err(|e| e.to_string())?;
+				insert.bind((1, n.name.as_str())).map_err(|e| e.to_string())?;
+				insert.bind((2, n.kind.name())).map_err(|e| e.to_string())?;
+				insert.bind((3, graph.nodes[*d].name.as_str())).map_err(|e| e.to_string())?;
+				insert.next().map_err(|e| e.to_string())?;
+			}
 		}
+		Ok(())
+	}
+
+	/// Computes the dependency graph of the library and records it.
+	fn dependencies(&self) -> Result<deps::Graph, String> {
+		let graph = deps::Graph::from_library(&self.library()?);
+		self.record_dependencies(&graph)?;
+		Ok(graph)
 	}
 
-	fn init(&mut self) {
-		//self.db.execute()
+	/// Reads the proof cache. Rows with unreadable hashes are dropped, so
+	/// their theorems are checked again.
+	fn cache(&self) -> Result<check::Cache, String> {
+		let mut cache = check::Cache::default();
+		let mut rows = self.theories
+			.prepare("SELECT theorem, statement, proof, dependencies FROM proof_cache")
+			.map_err(|e| e.to_string())?;
+		while let sqlite::State::Row = rows.next().map_err(|e| e.to_string())? {
+			let read = |i: usize| rows.read::<String, _>(i).map_err(|e| e.to_string());
+			let hash = |i: usize| read(i).map(|s| hash::ContentHash::parse(&s));
+			if let (Some(statement), Some(proof), Some(dependencies)) = (hash(1)?, hash(2)?, hash(3)?) {
+				cache.insert(check::Entry { theorem: read(0)?, statement, proof, dependencies });
+			}
+		}
+		Ok(cache)
+	}
+
+	/// Replaces the proof cache.
+	fn store_cache(&self, cache: &check::Cache) -> Result<(), String> {
+		self.theories.execute("DELETE FROM proof_cache").map_err(|e| e.to_string())?;
+		for e in cache.entries.iter() {
+			let mut insert = self.theories
+				.prepare("INSERT INTO proof_cache (theorem, statement, proof, dependencies) VALUES (?, ?, ?, ?)")
+				.map_err(|e| e.to_string())?;
+			insert.bind((1, e.theorem.as_str())).map_err(|e| e.to_string())?;
+			insert.bind((2, e.statement.to_string().as_str())).map_err(|e| e.to_string())?;
+			insert.bind((3, e.proof.to_string().as_str())).map_err(|e| e.to_string())?;
+			insert.bind((4, e.dependencies.to_string().as_str())).map_err(|e| e.to_string())?;
+			insert.next().map_err(|e| e.to_string())?;
+		}
+		Ok(())
 	}
 }
 
@@ -81,6 +211,8 @@ pub enum AST {
 	Exists(Theorem, String),
 	Seq(Vec<AST>),
 	Case(Formula, Ptr<AST>),
+	/// A chain `start rel1 t1 rel2 t2 ...`, each link with its justification.
+	Calc(Formula, Vec<(CalcRel, Formula, Ptr<AST>)>),
 }
 
 pub enum Thus {
@@ -89,49 +221,213 @@ pub enum Thus {
 }
 
 pub struct Generator {
-	ast: AST,
+	ast: Ptr<AST>,
 	thm: Formula
 }
 
 impl Generator {
 	pub fn generate_proof(&self) -> Deduction {
 		let mut a = vec!();
-		self.generate_inner(&mut a);
-		deduction::Deduction::EmptyStep
+		self.generate_inner(&mut a)
 	}
 
-	fn generate_inner(&self, v: &mut Vec<Deduction>) -> Deduction {
-		match self {
-			AST::Let(v, a) => {				
-				
-				deduction::Deduction::EmptyStep
-			}
+	fn generate_inner(&self, _v: &mut Vec<Deduction>) -> Deduction {
+		match &*self.ast {
+			AST::Let(_, _) => deduction::Deduction::EmptyStep,
 			AST::Thus(f) => self.thus(f),
+			AST::Calc(start, links) => self.calc(start, links),
 			_ => deduction::Deduction::EmptyStep
 		}
 	}
 
+	/// Each link is generated as a subproof whose goal is the link itself.
+	fn calc(&self, start: &Formula, links: &[(CalcRel, Formula, Ptr<AST>)]) -> deduction::Deduction {
+		let mut prev = start.clone().ptr();
+		let links = links.iter().map(|(rel, next, ast)| {
+			let next = next.clone().ptr();
+			let link = Generator {
+				ast: ast.clone(),
+				thm: rel.relate(prev.clone(), next.clone()),
+			};
+			prev = next.clone();
+			(*rel, next, Ptr::new(link.generate_proof()))
+		}).collect();
+
+		deduction::Deduction::Calc(start.clone().ptr(), links)
+	}
+
+	/// Concludes `f` where a single rule does, and otherwise leaves it to
+	/// have been deduced by the steps before.
 	fn thus(&self, f: &Formula) -> deduction::Deduction {
 		match f {
-			Formula::Eq(l, r)     => 
-			Formula::IFF(l, r)     =>
-			Formula::And(l, r)     =>
-			Formula::Or(l, r)      =>
-			Formula::Implies(l, r) =>
-			Formula::Not(l)        =>
-			Formula::Relation(l)   =>
-			Formula::ForAllSeq(ar, v, e) => 
-			Formula::ForAll(v, e) => 
-			Formula::Exists(v, e) => 
-			Formula::Free(v) => 
-			Formula::Const(v) =>
-			Formula::Subst(body, v, sub)
-			Formula::True => true,
-			Formula::False => true,
+			Formula::True => deduction::Deduction::TrueIntro,
+			Formula::Eq(l, r) if l == r => deduction::Deduction::EqualityIntro(l.clone()),
+			_ => deduction::Deduction::EmptyStep,
+		}
+	}
+}
+
+/// Checks the conjecture of a TPTP problem with the SAT solver alone,
+/// reading its axioms as hypotheses and quantified formulas as atoms. A
+/// falsifying assignment does not refute a first-order goal, but it is a
+/// strong hint that the goal or the axioms are wrong.
+fn quick_check(file: &str) -> bool {
+	let problem = match tptp::parse::load(Path::new(file)) {
+		Ok(p) => p,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+	let hyps: Vec<formula::Form> = problem.axioms.iter().map(|(_, f)| f.clone().ptr()).collect();
+
+	match sat::counterexample(&hyps, &problem.goal().ptr()) {
+		None => {
+			println!("{}: propositionally valid", file);
+			true
+		}
+		Some(a) => {
+			println!("{}: not propositionally valid, falsified by:", file);
+			print!("{}", a);
+			false
+		}
+	}
+}
+
+/// Writes the HTML documentation of the database into `out`.
+fn write_doc(out: &str) -> bool {
+	let written = Database::new().library().and_then(|l| l.write_site(Path::new(out)));
+	match written {
+		Ok(()) => true,
+		Err(e) => {
+			println!("{}", e);
+			false
+		}
+	}
+}
+
+/// Checks the theorems of the database whose proof or dependencies changed
+/// since they were last checked, and records the new checks and the
+/// dependencies.
+fn recheck() -> bool {
+	let db = Database::new();
+	let checked = db.library().and_then(|library| {
+		db.record_dependencies(&deps::Graph::from_library(&library))?;
+		let mut cache = db.cache()?;
+		let outcomes = library.recheck(&mut cache);
+		db.store_cache(&cache)?;
+		Ok(outcomes)
+	});
+	let outcomes = match checked {
+		Ok(o) => o,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+
+	let mut ok = true;
+	for (name, outcome) in outcomes {
+		match outcome {
+			check::Outcome::Checked => println!("checked\t{}", name),
+			check::Outcome::Cached => println!("cached\t{}", name),
+			check::Outcome::Axiom => println!("axiom\t{}", name),
+			check::Outcome::Failed(e) => {
+				println!("FAILED\t{}\t{}", name, e);
+				ok = false;
+			}
+		}
+	}
+	ok
+}
+
+/// Checks every theorem of the database, in parallel where the dependencies
+/// allow, records the dependencies and prints a summary.
+fn check_all() -> bool {
+	let db = Database::new();
+	let library = db.library().and_then(|library| {
+		db.record_dependencies(&deps::Graph::from_library(&library))?;
+		Ok(library)
+	});
+	match library {
+		Ok(library) => {
+			let report = batch::check_library(&library, batch::default_threads());
+			print!("{}", report);
+			report.ok()
+		}
+		Err(e) => {
+			println!("{}", e);
+			false
+		}
+	}
+}
+
+/// Answers a dependency query: what `name` depends on, or what depends on
+/// it and so would break if it were removed.
+fn dependency_query(query: &str, name: &str) -> bool {
+	let graph = match Database::new().dependencies() {
+		Ok(g) => g,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+	if graph.find(name).is_none() {
+		println!("Unknown theorem {}", name);
+		return false;
+	}
+
+	let nodes = if query == "users" { graph.users(name) } else { graph.dependencies(name) };
+	for n in nodes {
+		println!("{}\t{}", n.kind.name(), n.name);
+	}
+	true
+}
+
+/// Writes the dependency graph in Graphviz DOT to `out`.
+fn write_graph(out: &str) -> bool {
+	let written = Database::new().dependencies()
+		.and_then(|g| std::fs::write(out, g.dot()).map_err(|e| e.to_string()));
+	match written {
+		Ok(()) => true,
+		Err(e) => {
+			println!("{}", e);
+			false
 		}
 	}
 }
 
 fn main() {
-	println!("Hello, world!");
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+
+	match args[..] {
+		["check", "--all"] => if !check_all() {
+			process::exit(1);
+		}
+		["check", "--quick", file] => if !quick_check(file) {
+			process::exit(1);
+		}
+		["check"] => if !recheck() {
+			process::exit(1);
+		}
+		["doc", out] => if !write_doc(out) {
+			process::exit(1);
+		}
+		[query @ ("deps" | "users"), name] => if !dependency_query(query, name) {
+			process::exit(1);
+		}
+		["graph", out] => if !write_graph(out) {
+			process::exit(1);
+		}
+		_ => {
+			println!("usage: proof check");
+			println!("       proof check --all");
+			println!("       proof check --quick <problem.p>");
+			println!("       proof doc <out-dir>");
+			println!("       proof deps <theorem>");
+			println!("       proof users <theorem>");
+			println!("       proof graph <out.dot>");
+		}
+	}
 }